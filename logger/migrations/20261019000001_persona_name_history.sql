@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS persona_name_history (
+    persona_id BIGINT UNSIGNED NOT NULL,
+    name VARCHAR(32) NOT NULL,
+    clan_tag VARCHAR(8) NOT NULL DEFAULT '',
+    first_seen INT UNSIGNED NOT NULL,
+    last_seen INT UNSIGNED NOT NULL,
+    PRIMARY KEY (persona_id, name, clan_tag),
+    KEY persona_name_history_name (name)
+);
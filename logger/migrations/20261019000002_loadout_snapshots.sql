@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS loadout_snapshots (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    persona_id BIGINT UNSIGNED NOT NULL,
+    created_at INT UNSIGNED NOT NULL,
+    loadout MEDIUMTEXT NOT NULL,
+    PRIMARY KEY (id),
+    KEY loadout_snapshots_persona (persona_id, created_at)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS response_cache (
+    cache_key VARCHAR(191) NOT NULL,
+    body MEDIUMTEXT NOT NULL,
+    fetched_at INT UNSIGNED NOT NULL,
+    expires_at INT UNSIGNED NULL,
+    PRIMARY KEY (cache_key)
+);
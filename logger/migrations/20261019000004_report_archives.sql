@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS battlereport_archives (
+    report_id BIGINT UNSIGNED NOT NULL,
+    data LONGBLOB NOT NULL,
+    archived_at INT UNSIGNED NOT NULL,
+    PRIMARY KEY (report_id)
+);
+
+CREATE TABLE IF NOT EXISTS playerreport_archives (
+    report_id BIGINT UNSIGNED NOT NULL,
+    persona_id BIGINT UNSIGNED NOT NULL,
+    data LONGBLOB NOT NULL,
+    archived_at INT UNSIGNED NOT NULL,
+    PRIMARY KEY (report_id, persona_id)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS backfill_cursors (
+    persona_id BIGINT UNSIGNED NOT NULL,
+    timestamp INT UNSIGNED NOT NULL,
+    cutoff INT UNSIGNED NOT NULL,
+    reports_ingested INT UNSIGNED NOT NULL DEFAULT 0,
+    reports_failed INT UNSIGNED NOT NULL DEFAULT 0,
+    completed TINYINT(1) NOT NULL DEFAULT 0,
+    updated_at INT UNSIGNED NOT NULL,
+    PRIMARY KEY (persona_id)
+);
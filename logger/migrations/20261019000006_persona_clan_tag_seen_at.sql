@@ -0,0 +1 @@
+ALTER TABLE personas ADD COLUMN clan_tag_seen_at INT UNSIGNED NULL;
@@ -0,0 +1,102 @@
+CREATE TABLE IF NOT EXISTS servers (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    guid VARCHAR(64) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS personas (
+    id BIGINT PRIMARY KEY,
+    name VARCHAR(32) NULL,
+    clan_tag VARCHAR(8) NULL,
+    gravatar_md5 VARCHAR(32) NULL,
+    processed BOOLEAN NOT NULL DEFAULT FALSE,
+    last_updated BIGINT NULL
+);
+CREATE INDEX IF NOT EXISTS personas_name ON personas (name);
+CREATE INDEX IF NOT EXISTS personas_clan_tag ON personas (clan_tag);
+
+CREATE TABLE IF NOT EXISTS battlereports (
+    id BIGINT PRIMARY KEY,
+    duration BIGINT NOT NULL,
+    winner SMALLINT NOT NULL,
+    server_id INTEGER NOT NULL,
+    map VARCHAR(64) NOT NULL,
+    mode VARCHAR(64) NOT NULL,
+    created_at BIGINT NOT NULL,
+    processed SMALLINT NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS battlereports_server ON battlereports (server_id, created_at);
+
+CREATE TABLE IF NOT EXISTS playerreports (
+    report_id BIGINT NOT NULL,
+    persona_id BIGINT NOT NULL,
+    kills BIGINT NOT NULL,
+    deaths BIGINT NOT NULL,
+    shots_hit REAL NOT NULL,
+    shots_fired REAL NOT NULL,
+    vehicle_destroyed BIGINT NOT NULL,
+    assists BIGINT NOT NULL,
+    spm BIGINT NOT NULL,
+    kd_ratio REAL NOT NULL,
+    skill INTEGER NOT NULL,
+    vehicle_assists BIGINT NOT NULL,
+    accuracy BIGINT NOT NULL,
+    sc_unlock BIGINT NOT NULL,
+    sc_bomber BIGINT NOT NULL,
+    sc_vehiclesh BIGINT NOT NULL,
+    sc_vehicleajet BIGINT NOT NULL,
+    sc_engineer BIGINT NOT NULL,
+    sc_commander BIGINT NOT NULL,
+    sc_assault BIGINT NOT NULL,
+    vehicle BIGINT NOT NULL,
+    sc_vehicleaa BIGINT NOT NULL,
+    sc_award BIGINT NOT NULL,
+    sc_vehicleifv BIGINT NOT NULL,
+    sc_recon BIGINT NOT NULL,
+    sc_vehicleah BIGINT NOT NULL,
+    sc_support BIGINT NOT NULL,
+    sc_vehiclesjet BIGINT NOT NULL,
+    total BIGINT NOT NULL,
+    sc_vehiclembt BIGINT NOT NULL,
+    sc_vehicleaboat BIGINT NOT NULL,
+    heals INTEGER NOT NULL,
+    revives INTEGER NOT NULL,
+    team SMALLINT NOT NULL,
+    kill_streak INTEGER NOT NULL,
+    squad_id SMALLINT NOT NULL,
+    accuracy_detailed REAL NOT NULL,
+    dnf BOOLEAN NOT NULL,
+    is_commander BOOLEAN NOT NULL,
+    is_soldier BOOLEAN NOT NULL,
+    PRIMARY KEY (report_id, persona_id)
+);
+CREATE INDEX IF NOT EXISTS playerreports_persona ON playerreports (persona_id);
+
+CREATE TABLE IF NOT EXISTS game_expansions (
+    id BIGINT PRIMARY KEY,
+    value VARCHAR(64) NULL,
+    explanation VARCHAR(255) NULL
+);
+-- Battlelog game ids the expansions of a user are grouped by, same rows as in MySQL
+INSERT INTO game_expansions (id, value, explanation) VALUES
+    (2, 'bf3', 'Battlefield 3'),
+    (2048, 'bf4', 'Battlefield 4'),
+    (4096, 'mohw', 'Medal of Honor Warfighter'),
+    (8192, 'bfh', 'Battlefield Hardline')
+ON CONFLICT (id) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS persona_game_expansions (
+    persona_id BIGINT NOT NULL,
+    game_expansion_id BIGINT NOT NULL,
+    platforms BIGINT NOT NULL,
+    PRIMARY KEY (persona_id, game_expansion_id)
+);
+
+CREATE TABLE IF NOT EXISTS persona_infos (
+    persona_id BIGINT PRIMARY KEY,
+    locality VARCHAR(255) NULL,
+    location VARCHAR(255) NULL,
+    presentation TEXT NULL,
+    login_counter BIGINT NULL,
+    last_login BIGINT NULL
+);
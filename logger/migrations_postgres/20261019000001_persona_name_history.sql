@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS persona_name_history (
+    persona_id BIGINT NOT NULL,
+    name VARCHAR(32) NOT NULL,
+    clan_tag VARCHAR(8) NOT NULL DEFAULT '',
+    first_seen BIGINT NOT NULL,
+    last_seen BIGINT NOT NULL,
+    PRIMARY KEY (persona_id, name, clan_tag)
+);
+CREATE INDEX IF NOT EXISTS persona_name_history_name ON persona_name_history (name);
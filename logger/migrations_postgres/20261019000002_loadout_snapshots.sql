@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS loadout_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    persona_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    loadout TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS loadout_snapshots_persona ON loadout_snapshots (persona_id, created_at);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS response_cache (
+    cache_key VARCHAR(191) PRIMARY KEY,
+    body TEXT NOT NULL,
+    fetched_at BIGINT NOT NULL,
+    expires_at BIGINT NULL
+);
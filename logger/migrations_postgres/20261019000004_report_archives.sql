@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS battlereport_archives (
+    report_id BIGINT PRIMARY KEY,
+    data BYTEA NOT NULL,
+    archived_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS playerreport_archives (
+    report_id BIGINT NOT NULL,
+    persona_id BIGINT NOT NULL,
+    data BYTEA NOT NULL,
+    archived_at BIGINT NOT NULL,
+    PRIMARY KEY (report_id, persona_id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS backfill_cursors (
+    persona_id BIGINT PRIMARY KEY,
+    timestamp BIGINT NOT NULL,
+    cutoff BIGINT NOT NULL,
+    reports_ingested BIGINT NOT NULL DEFAULT 0,
+    reports_failed BIGINT NOT NULL DEFAULT 0,
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at BIGINT NOT NULL
+);
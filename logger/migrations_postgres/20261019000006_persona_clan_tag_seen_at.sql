@@ -0,0 +1 @@
+ALTER TABLE personas ADD COLUMN IF NOT EXISTS clan_tag_seen_at BIGINT NULL;
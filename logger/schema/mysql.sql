@@ -0,0 +1,109 @@
+-- Tables that predate logger/migrations, for a fresh MySQL database such as the one CI checks the
+-- queries against. Apply this first, then the migrations.
+CREATE TABLE IF NOT EXISTS servers (
+    id INT NOT NULL AUTO_INCREMENT,
+    name VARCHAR(255) NOT NULL,
+    guid VARCHAR(64) NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY servers_guid (guid)
+);
+
+CREATE TABLE IF NOT EXISTS personas (
+    id BIGINT UNSIGNED NOT NULL,
+    name VARCHAR(32) NULL,
+    clan_tag VARCHAR(4) NULL,
+    gravatar_md5 VARCHAR(255) NULL,
+    processed TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    last_updated INT UNSIGNED NULL,
+    PRIMARY KEY (id),
+    KEY personas_name (name),
+    KEY personas_clan_tag (clan_tag)
+);
+
+CREATE TABLE IF NOT EXISTS battlereports (
+    id BIGINT UNSIGNED NOT NULL,
+    duration INT UNSIGNED NOT NULL,
+    winner TINYINT NOT NULL,
+    server_id INT NOT NULL,
+    map VARCHAR(255) NOT NULL,
+    mode VARCHAR(255) NOT NULL,
+    created_at INT UNSIGNED NOT NULL,
+    processed TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (id),
+    KEY battlereports_server (server_id, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS playerreports (
+    report_id BIGINT UNSIGNED NOT NULL,
+    persona_id BIGINT UNSIGNED NOT NULL,
+    kills INT UNSIGNED NOT NULL,
+    deaths INT UNSIGNED NOT NULL,
+    shots_hit FLOAT NOT NULL,
+    shots_fired FLOAT NOT NULL,
+    vehicle_destroyed INT UNSIGNED NOT NULL,
+    assists INT UNSIGNED NOT NULL,
+    spm INT UNSIGNED NOT NULL,
+    kd_ratio FLOAT NOT NULL,
+    skill INT NOT NULL,
+    vehicle_assists INT UNSIGNED NOT NULL,
+    accuracy INT UNSIGNED NOT NULL,
+    sc_unlock INT UNSIGNED NOT NULL,
+    sc_bomber INT UNSIGNED NOT NULL,
+    sc_vehiclesh INT UNSIGNED NOT NULL,
+    sc_vehicleajet INT UNSIGNED NOT NULL,
+    sc_engineer INT UNSIGNED NOT NULL,
+    sc_commander INT UNSIGNED NOT NULL,
+    sc_assault INT UNSIGNED NOT NULL,
+    vehicle INT UNSIGNED NOT NULL,
+    sc_vehicleaa INT UNSIGNED NOT NULL,
+    sc_award INT UNSIGNED NOT NULL,
+    sc_vehicleifv INT UNSIGNED NOT NULL,
+    sc_recon INT UNSIGNED NOT NULL,
+    sc_vehicleah INT UNSIGNED NOT NULL,
+    sc_support INT UNSIGNED NOT NULL,
+    sc_vehiclesjet INT UNSIGNED NOT NULL,
+    total INT UNSIGNED NOT NULL,
+    sc_vehiclembt INT UNSIGNED NOT NULL,
+    sc_vehicleaboat INT UNSIGNED NOT NULL,
+    heals SMALLINT UNSIGNED NOT NULL,
+    revives SMALLINT UNSIGNED NOT NULL,
+    team TINYINT NOT NULL,
+    kill_streak SMALLINT UNSIGNED NOT NULL,
+    squad_id TINYINT NOT NULL,
+    accuracy_detailed FLOAT NOT NULL,
+    dnf BOOLEAN NOT NULL,
+    is_commander BOOLEAN NOT NULL,
+    is_soldier BOOLEAN NOT NULL,
+    PRIMARY KEY (report_id, persona_id),
+    KEY playerreports_persona (persona_id)
+);
+
+CREATE TABLE IF NOT EXISTS game_expansions (
+    id BIGINT UNSIGNED NOT NULL,
+    value VARCHAR(32) NULL,
+    explanation VARCHAR(255) NULL,
+    PRIMARY KEY (id)
+);
+-- Battlelog game ids the expansions of a user are grouped by
+INSERT IGNORE INTO game_expansions (id, value, explanation) VALUES
+    (2, 'bf3', 'Battlefield 3'),
+    (2048, 'bf4', 'Battlefield 4'),
+    (4096, 'mohw', 'Medal of Honor Warfighter'),
+    (8192, 'bfh', 'Battlefield Hardline');
+
+CREATE TABLE IF NOT EXISTS persona_game_expansions (
+    persona_id BIGINT UNSIGNED NOT NULL,
+    game_expansion_id BIGINT UNSIGNED NOT NULL,
+    platforms INT UNSIGNED NOT NULL,
+    PRIMARY KEY (persona_id, game_expansion_id)
+);
+
+CREATE TABLE IF NOT EXISTS persona_infos (
+    persona_id BIGINT UNSIGNED NOT NULL,
+    locality VARCHAR(255) NULL,
+    location VARCHAR(255) NULL,
+    presentation TEXT NULL,
+    login_counter INT UNSIGNED NULL,
+    last_login INT UNSIGNED NULL,
+    PRIMARY KEY (persona_id)
+);
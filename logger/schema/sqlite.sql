@@ -0,0 +1,151 @@
+CREATE TABLE IF NOT EXISTS servers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    guid TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS personas (
+    id INTEGER PRIMARY KEY,
+    name TEXT NULL,
+    clan_tag TEXT NULL,
+    gravatar_md5 TEXT NULL,
+    processed INTEGER NOT NULL DEFAULT 0,
+    last_updated INTEGER NULL,
+    clan_tag_seen_at INTEGER NULL
+);
+CREATE INDEX IF NOT EXISTS personas_name ON personas (name);
+CREATE INDEX IF NOT EXISTS personas_clan_tag ON personas (clan_tag);
+
+CREATE TABLE IF NOT EXISTS battlereports (
+    id INTEGER PRIMARY KEY,
+    duration INTEGER NOT NULL,
+    winner INTEGER NOT NULL,
+    server_id INTEGER NOT NULL,
+    map TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    processed INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS battlereports_server ON battlereports (server_id, created_at);
+
+CREATE TABLE IF NOT EXISTS playerreports (
+    report_id INTEGER NOT NULL,
+    persona_id INTEGER NOT NULL,
+    kills INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    shots_hit REAL NOT NULL,
+    shots_fired REAL NOT NULL,
+    vehicle_destroyed INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    spm INTEGER NOT NULL,
+    kd_ratio REAL NOT NULL,
+    skill INTEGER NOT NULL,
+    vehicle_assists INTEGER NOT NULL,
+    accuracy INTEGER NOT NULL,
+    sc_unlock INTEGER NOT NULL,
+    sc_bomber INTEGER NOT NULL,
+    sc_vehiclesh INTEGER NOT NULL,
+    sc_vehicleajet INTEGER NOT NULL,
+    sc_engineer INTEGER NOT NULL,
+    sc_commander INTEGER NOT NULL,
+    sc_assault INTEGER NOT NULL,
+    vehicle INTEGER NOT NULL,
+    sc_vehicleaa INTEGER NOT NULL,
+    sc_award INTEGER NOT NULL,
+    sc_vehicleifv INTEGER NOT NULL,
+    sc_recon INTEGER NOT NULL,
+    sc_vehicleah INTEGER NOT NULL,
+    sc_support INTEGER NOT NULL,
+    sc_vehiclesjet INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    sc_vehiclembt INTEGER NOT NULL,
+    sc_vehicleaboat INTEGER NOT NULL,
+    heals INTEGER NOT NULL,
+    revives INTEGER NOT NULL,
+    team INTEGER NOT NULL,
+    kill_streak INTEGER NOT NULL,
+    squad_id INTEGER NOT NULL,
+    accuracy_detailed REAL NOT NULL,
+    dnf INTEGER NOT NULL,
+    is_commander INTEGER NOT NULL,
+    is_soldier INTEGER NOT NULL,
+    PRIMARY KEY (report_id, persona_id)
+);
+CREATE INDEX IF NOT EXISTS playerreports_persona ON playerreports (persona_id);
+
+CREATE TABLE IF NOT EXISTS game_expansions (
+    id INTEGER PRIMARY KEY,
+    value TEXT NULL,
+    explanation TEXT NULL
+);
+-- Battlelog game ids the expansions of a user are grouped by, same rows as in MySQL
+INSERT OR IGNORE INTO game_expansions (id, value, explanation) VALUES
+    (2, 'bf3', 'Battlefield 3'),
+    (2048, 'bf4', 'Battlefield 4'),
+    (4096, 'mohw', 'Medal of Honor Warfighter'),
+    (8192, 'bfh', 'Battlefield Hardline');
+
+CREATE TABLE IF NOT EXISTS persona_game_expansions (
+    persona_id INTEGER NOT NULL,
+    game_expansion_id INTEGER NOT NULL,
+    platforms INTEGER NOT NULL,
+    PRIMARY KEY (persona_id, game_expansion_id)
+);
+
+CREATE TABLE IF NOT EXISTS persona_infos (
+    persona_id INTEGER PRIMARY KEY,
+    locality TEXT NULL,
+    location TEXT NULL,
+    presentation TEXT NULL,
+    login_counter INTEGER NULL,
+    last_login INTEGER NULL
+);
+
+CREATE TABLE IF NOT EXISTS persona_name_history (
+    persona_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    clan_tag TEXT NOT NULL DEFAULT '',
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    PRIMARY KEY (persona_id, name, clan_tag)
+);
+CREATE INDEX IF NOT EXISTS persona_name_history_name ON persona_name_history (name);
+
+CREATE TABLE IF NOT EXISTS loadout_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    persona_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    loadout TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS loadout_snapshots_persona ON loadout_snapshots (persona_id, created_at);
+
+CREATE TABLE IF NOT EXISTS response_cache (
+    cache_key TEXT PRIMARY KEY,
+    body TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    expires_at INTEGER NULL
+);
+
+CREATE TABLE IF NOT EXISTS battlereport_archives (
+    report_id INTEGER PRIMARY KEY,
+    data BLOB NOT NULL,
+    archived_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS playerreport_archives (
+    report_id INTEGER NOT NULL,
+    persona_id INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    archived_at INTEGER NOT NULL,
+    PRIMARY KEY (report_id, persona_id)
+);
+
+CREATE TABLE IF NOT EXISTS backfill_cursors (
+    persona_id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    cutoff INTEGER NOT NULL,
+    reports_ingested INTEGER NOT NULL DEFAULT 0,
+    reports_failed INTEGER NOT NULL DEFAULT 0,
+    completed INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL
+);
@@ -0,0 +1,389 @@
+use std::{collections::HashMap, future::Future, sync::Mutex, time::{Duration, Instant}};
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::metrics::BATTLELOG_REQUEST_DURATION;
+
+use super::{endpoint::BattlelogEndpoint, token_bucket::TokenBucket};
+
+/// The endpoint paths are relative to this when no `base_url` is configured.
+const BATTLELOG_URL: &str = "https://battlelog.battlefield.com";
+
+static CLIENT: Lazy<BattlelogClient> = Lazy::new(|| BattlelogClient::new(BattlelogClientConfig::from_env()));
+
+/// The Battlelog client shared by every caller, so the limits apply to the whole process.
+pub fn battlelog() -> &'static BattlelogClient {
+    &CLIENT
+}
+
+#[derive(Debug, Clone)]
+pub struct BattlelogClientConfig {
+    /// Requests per second.
+    pub rate_limit: f64,
+    pub burst: u32,
+    pub max_in_flight: usize,
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every retry after it.
+    pub backoff: Duration,
+    /// Limit of a whole request, from connecting to reading the body.
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Requests go to this server instead of Battlelog, used to replay recorded responses.
+    pub base_url: Option<String>,
+}
+
+impl BattlelogClientConfig {
+    pub fn from_env() -> Self {
+        Self {
+            rate_limit: dotenv::var("BATTLELOG_RATE_LIMIT").map(|var| var.parse::<f64>()).unwrap_or(Ok(5.0)).unwrap(),
+            burst: dotenv::var("BATTLELOG_BURST").map(|var| var.parse::<u32>()).unwrap_or(Ok(10)).unwrap(),
+            max_in_flight: dotenv::var("BATTLELOG_MAX_IN_FLIGHT").map(|var| var.parse::<usize>()).unwrap_or(Ok(4)).unwrap(),
+            max_retries: dotenv::var("BATTLELOG_MAX_RETRIES").map(|var| var.parse::<u32>()).unwrap_or(Ok(3)).unwrap(),
+            backoff: Duration::from_millis(dotenv::var("BATTLELOG_BACKOFF_MS").map(|var| var.parse::<u64>()).unwrap_or(Ok(500)).unwrap()),
+            timeout: Duration::from_millis(dotenv::var("BATTLELOG_TIMEOUT_MS").map(|var| var.parse::<u64>()).unwrap_or(Ok(30000)).unwrap()),
+            connect_timeout: Duration::from_millis(dotenv::var("BATTLELOG_CONNECT_TIMEOUT_MS").map(|var| var.parse::<u64>()).unwrap_or(Ok(10000)).unwrap()),
+            base_url: dotenv::var("BATTLELOG_BASE_URL").ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EndpointMetrics {
+    pub requests: u64,
+    pub failures: u64,
+    pub retries: u64,
+    pub total_latency_ms: u64,
+    pub max_latency_ms: u64,
+}
+
+impl EndpointMetrics {
+    fn record(&mut self, latency: Duration, success: bool) {
+        let latency_ms = latency.as_millis() as u64;
+        self.requests += 1;
+        if !success {
+            self.failures += 1;
+        }
+        self.total_latency_ms += latency_ms;
+        self.max_latency_ms = self.max_latency_ms.max(latency_ms);
+    }
+}
+
+/// Every Battlelog request goes through `execute`, which waits for the rate limit and a free
+/// request slot, retries transient failures with an exponential backoff and records the metrics.
+pub struct BattlelogClient {
+    config: BattlelogClientConfig,
+    bucket: TokenBucket,
+    in_flight: Semaphore,
+    http: reqwest::Client,
+    metrics: Mutex<HashMap<&'static str, EndpointMetrics>>,
+}
+
+impl BattlelogClient {
+    pub fn new(config: BattlelogClientConfig) -> Self {
+        info!("Battlelog requests limited to {}/s, burst of {} and {} in flight", config.rate_limit, config.burst, config.max_in_flight);
+        if let Some(base_url) = &config.base_url {
+            warn!("Battlelog requests are sent to {}", base_url);
+        }
+        Self {
+            bucket: TokenBucket::new(config.rate_limit, config.burst),
+            in_flight: Semaphore::new(config.max_in_flight.max(1)),
+            http: reqwest::Client::builder()
+                .timeout(config.timeout)
+                .connect_timeout(config.connect_timeout)
+                .build()
+                .expect("Failed to build the Battlelog HTTP client"),
+            metrics: Mutex::new(HashMap::new()),
+            config,
+        }
+    }
+
+    /// Runs `live` against Battlelog, or fetches the same response type from the endpoint path of
+    /// the `base_url` server when one is configured.
+    pub async fn request<T, E, F, Fut>(&self, endpoint: BattlelogEndpoint<'_>, live: F) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        match &self.config.base_url {
+            Some(base_url) => {
+                let url = format!("{}{}", base_url.trim_end_matches('/'), endpoint.path());
+                self.execute(endpoint.name(), || fetch_json::<T>(&self.http, &url)).await
+            },
+            None => self.execute(endpoint.name(), live).await,
+        }
+    }
+
+    /// Fetches the response body of the endpoint as Battlelog sent it and parses it into `T`, for
+    /// callers that keep the original JSON. The `battlelog` crate only hands out the parsed value,
+    /// so this requests the endpoint path itself, also from Battlelog. A body that doesn't parse
+    /// isn't retried.
+    pub async fn request_raw<T: DeserializeOwned>(&self, endpoint: BattlelogEndpoint<'_>) -> anyhow::Result<(T, String)> {
+        let base_url = self.config.base_url.as_deref().unwrap_or(BATTLELOG_URL);
+        let url = format!("{}{}", base_url.trim_end_matches('/'), endpoint.path());
+        let body = self.execute(endpoint.name(), || fetch_text(&self.http, &url)).await?;
+        let value = serde_json::from_str::<T>(&body)
+            .with_context(|| format!("Unexpected Battlelog {} response", endpoint.name()))?;
+
+        Ok((value, body))
+    }
+
+    /// Runs the request built by `request`, which is called again for every retry. Only failures
+    /// that may pass on their own are retried, see `is_retryable`.
+    pub async fn execute<T, E, F, Fut>(&self, endpoint: &'static str, request: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let _permit = self.in_flight.acquire().await?;
+                self.bucket.acquire().await;
+
+                let started = Instant::now();
+                let result = request().await.map_err(|err| err.into());
+                self.record(endpoint, started.elapsed(), result.is_ok(), attempt > 0);
+                result
+            };
+
+            match result {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < self.config.max_retries && is_retryable(&err) => {
+                    let delay = backoff_delay(self.config.backoff, attempt);
+                    warn!("Battlelog {} failed, retrying in {}ms: {}", endpoint, delay.as_millis(), err);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+                Err(err) => {
+                    error!("Battlelog {} failed after {} attempts: {}", endpoint, attempt + 1, err);
+                    return Err(err);
+                },
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> HashMap<&'static str, EndpointMetrics> {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    fn record(&self, endpoint: &'static str, latency: Duration, success: bool, retry: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        BATTLELOG_REQUEST_DURATION.with_label_values(&[endpoint, outcome]).observe(latency.as_secs_f64());
+
+        let mut metrics = self.metrics.lock().unwrap();
+        let endpoint_metrics = metrics.entry(endpoint).or_default();
+        endpoint_metrics.record(latency, success);
+        if retry {
+            endpoint_metrics.retries += 1;
+        }
+    }
+}
+
+async fn fetch_json<T: DeserializeOwned>(http: &reqwest::Client, url: &str) -> anyhow::Result<T> {
+    let response = http.get(url).send().await?.error_for_status()?;
+    Ok(response.json::<T>().await?)
+}
+
+async fn fetch_text(http: &reqwest::Client, url: &str) -> anyhow::Result<String> {
+    let response = http.get(url).send().await?.error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Connection failures, timeouts, 5xx and 429 responses are retried. Other 4xx responses, bodies
+/// that don't parse and anything else unrecognized fail right away.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            match err.status() {
+                Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+                None => err.is_timeout() || err.is_connect() || err.is_request() || err.is_body(),
+            }
+        }
+        else {
+            cause.is::<std::io::Error>() || cause.is::<tokio::time::error::Elapsed>()
+        }
+    })
+}
+
+fn backoff_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff * 2u32.saturating_pow(attempt.min(16))
+}
+
+#[cfg(test)]
+mod tests {
+    use battlelog::{BattlereportResponse, PlayerreportResponse, UserResult};
+
+    use crate::database::battlelog::{battlereports::BattlelogBattlereport, playerreports::BattlelogPlayerreport};
+    use crate::fixture_server::FixtureServer;
+
+    use super::*;
+
+    fn test_config() -> BattlelogClientConfig {
+        BattlelogClientConfig {
+            rate_limit: 1000.0,
+            burst: 10,
+            max_in_flight: 2,
+            max_retries: 2,
+            backoff: Duration::from_millis(1),
+            timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(1),
+            base_url: None,
+        }
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Duration::from_millis(500);
+        assert_eq!(backoff_delay(backoff, 0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(backoff, 2), Duration::from_millis(2000));
+    }
+
+    #[tokio::test]
+    async fn test_execute_retries_until_success() {
+        let client = BattlelogClient::new(test_config());
+        let calls = std::sync::atomic::AtomicU32::new(0);
+
+        let result = client.execute("test", || async {
+            match calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => Err(anyhow::Error::new(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "Temporary failure"))),
+                n => Ok(n),
+            }
+        }).await;
+
+        assert_eq!(result.unwrap(), 1);
+        let metrics = client.metrics();
+        let metrics = metrics.get("test").unwrap();
+        assert_eq!(metrics.requests, 2);
+        assert_eq!(metrics.failures, 1);
+        assert_eq!(metrics.retries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_gives_up_after_retries() {
+        let client = BattlelogClient::new(test_config());
+
+        let result: anyhow::Result<()> = client.execute("test", || async { Err(anyhow::Error::new(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "Down"))) }).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.metrics().get("test").unwrap().requests, 3);
+    }
+
+    #[tokio::test]
+    async fn test_execute_doesnt_retry_parse_errors() {
+        let client = BattlelogClient::new(test_config());
+
+        let result: anyhow::Result<serde_json::Value> = client.execute("test", || async { serde_json::from_str::<serde_json::Value>("<html>") }).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.metrics().get("test").unwrap().requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_replays_fixtures() {
+        let server = FixtureServer::start().await.unwrap();
+        let client = BattlelogClient::new(BattlelogClientConfig { base_url: Some(server.base_url.clone()), ..test_config() });
+
+        let report_id = "1500000000000000001";
+        let persona_id = "1000000001";
+        let report: BattlereportResponse = client.request(BattlelogEndpoint::Battlereport { report_id }, || battlelog::battlereport(report_id)).await.unwrap();
+        let playerreport: PlayerreportResponse = client.request(BattlelogEndpoint::Playerreport { report_id, persona_id }, || battlelog::playerreport(report_id, persona_id)).await.unwrap();
+
+        let battlereport = BattlelogBattlereport::from_battlereport_response(&report, 1);
+        assert_eq!(battlereport.id, 1500000000000000001);
+        assert_eq!(battlereport.winner, 1);
+
+        let player = report.get_player_by_personaid(1000000001).unwrap();
+        let playerreport = BattlelogPlayerreport::from_response_and_report(battlereport.id, &playerreport, &player);
+        assert_eq!(playerreport.persona_id, 1000000001);
+        assert_eq!(playerreport.kills, 31);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_request_raw_keeps_the_body() {
+        let server = FixtureServer::start().await.unwrap();
+        let client = BattlelogClient::new(BattlelogClientConfig { base_url: Some(server.base_url.clone()), ..test_config() });
+
+        let report_id = "1500000000000000001";
+        let (report, body): (BattlereportResponse, String) = client.request_raw(BattlelogEndpoint::Battlereport { report_id }).await.unwrap();
+
+        let fixture = std::fs::read_to_string(crate::fixture_server::fixtures_dir().join("battlelog/battlereport/1500000000000000001.json")).unwrap();
+        assert_eq!(body, fixture);
+        assert_eq!(report.created_at, serde_json::from_str::<BattlereportResponse>(&fixture).unwrap().created_at);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_every_endpoint_is_served_by_the_fixtures() {
+        let server = FixtureServer::start().await.unwrap();
+        let client = BattlelogClient::new(BattlelogClientConfig { base_url: Some(server.base_url.clone()), ..test_config() });
+
+        let soldier_names = vec!["StubSoldier".to_string()];
+        let persona_ids = vec!["1000000001".to_string()];
+        let endpoints = vec![
+            BattlelogEndpoint::Battlereport { report_id: "1500000000000000001" },
+            BattlelogEndpoint::Playerreport { report_id: "1500000000000000001", persona_id: "1000000001" },
+            BattlelogEndpoint::BattlereportsPopulateMore { persona_id: "1000000001", timestamp: "1666160000" },
+            BattlelogEndpoint::Users { soldier_names: &soldier_names },
+            BattlelogEndpoint::UsersByPersonaIds { persona_ids: &persona_ids },
+            BattlelogEndpoint::Loadout { soldier_name: "StubSoldier", persona_id: "1000000001" },
+        ];
+        for endpoint in endpoints {
+            let result = client.request_raw::<serde_json::Value>(endpoint.clone()).await;
+            assert!(result.is_ok(), "{} isn't served: {:?}", endpoint.path(), result.err());
+        }
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_request_times_out() {
+        // Accepts connections through the backlog but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let client = BattlelogClient::new(BattlelogClientConfig { max_retries: 0, timeout: Duration::from_millis(100), base_url: Some(base_url), ..test_config() });
+
+        let result = client.request_raw::<serde_json::Value>(BattlelogEndpoint::Battlereport { report_id: "1500000000000000001" }).await;
+
+        let err = result.unwrap_err();
+        assert!(err.chain().any(|cause| cause.downcast_ref::<reqwest::Error>().map(|err| err.is_timeout()).unwrap_or(false)));
+    }
+
+    #[tokio::test]
+    async fn test_request_leaves_out_unknown_users() {
+        let server = FixtureServer::start().await.unwrap();
+        let client = BattlelogClient::new(BattlelogClientConfig { base_url: Some(server.base_url.clone()), ..test_config() });
+
+        let persona_ids = vec!["1000000001".to_string(), "1000000009".to_string()];
+        let users: Vec<UserResult> = client.request(BattlelogEndpoint::UsersByPersonaIds { persona_ids: &persona_ids }, || battlelog::get_users_by_persona_ids(persona_ids.clone())).await.unwrap();
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].persona_id, "1000000001");
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_request_missing_fixture_fails() {
+        let server = FixtureServer::start().await.unwrap();
+        let client = BattlelogClient::new(BattlelogClientConfig { base_url: Some(server.base_url.clone()), ..test_config() });
+
+        let report_id = "1";
+        let result: anyhow::Result<BattlereportResponse> = client.request(BattlelogEndpoint::Battlereport { report_id }, || battlelog::battlereport(report_id)).await;
+
+        // A 404 won't go away by asking again
+        assert!(result.is_err());
+        assert_eq!(client.metrics().get("battlereport").unwrap().failures, 1);
+
+        server.stop().await;
+    }
+}
@@ -0,0 +1,49 @@
+/// The Battlelog requests made through the client, the name is used for the metrics and the path
+/// when the requests are sent to `BATTLELOG_BASE_URL` instead of Battlelog. `request_raw` also
+/// sends its live requests to the path on Battlelog, so each path has to stay the URL the
+/// `battlelog` crate function of the variant requests. `FixtureServer` serves every path.
+#[derive(Debug, Clone)]
+pub enum BattlelogEndpoint<'a> {
+    /// `battlelog::battlereport`
+    Battlereport { report_id: &'a str },
+    /// `battlelog::playerreport`
+    Playerreport { report_id: &'a str, persona_id: &'a str },
+    /// `battlelog::warsawbattlereportspopulatemore`, BF4 reports of the persona older than `timestamp`
+    BattlereportsPopulateMore { persona_id: &'a str, timestamp: &'a str },
+    /// `battlelog::get_users`
+    Users { soldier_names: &'a [String] },
+    /// `battlelog::get_users_by_persona_ids`
+    UsersByPersonaIds { persona_ids: &'a [String] },
+    /// `battlelog::get_loadout`
+    Loadout { soldier_name: &'a str, persona_id: &'a str },
+}
+
+impl<'a> BattlelogEndpoint<'a> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BattlelogEndpoint::Battlereport { .. } => "battlereport",
+            BattlelogEndpoint::Playerreport { .. } => "playerreport",
+            BattlelogEndpoint::BattlereportsPopulateMore { .. } => "battlereports_populate_more",
+            BattlelogEndpoint::Users { .. } => "users",
+            BattlelogEndpoint::UsersByPersonaIds { .. } => "users_by_persona_ids",
+            BattlelogEndpoint::Loadout { .. } => "loadout",
+        }
+    }
+
+    pub fn path(&self) -> String {
+        match self {
+            BattlelogEndpoint::Battlereport { report_id } =>
+                format!("/bf4/battlereport/loadgeneralreport/{}/1/1/", report_id),
+            BattlelogEndpoint::Playerreport { report_id, persona_id } =>
+                format!("/bf4/battlereport/loadplayerreport/{}/1/{}/", report_id, persona_id),
+            BattlelogEndpoint::BattlereportsPopulateMore { persona_id, timestamp } =>
+                format!("/bf4/warsawbattlereportspopulatemore/{}/2048/1/{}/", persona_id, timestamp),
+            BattlelogEndpoint::Users { soldier_names } =>
+                format!("/bf4/users/?names={}", soldier_names.join(",")),
+            BattlelogEndpoint::UsersByPersonaIds { persona_ids } =>
+                format!("/bf4/users/?persona_ids={}", persona_ids.join(",")),
+            BattlelogEndpoint::Loadout { soldier_name, persona_id } =>
+                format!("/bf4/loadout/get/{}/{}/1/", soldier_name, persona_id),
+        }
+    }
+}
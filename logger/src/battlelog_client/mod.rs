@@ -0,0 +1,3 @@
+pub mod client;
+pub mod endpoint;
+pub mod token_bucket;
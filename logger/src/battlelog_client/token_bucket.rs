@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket shared by every Battlelog request, refills `rate` tokens per second up to `burst`.
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = (burst.max(1)) as f64;
+        Self {
+            rate: rate.max(0.001),
+            burst,
+            state: Mutex::new(BucketState { tokens: burst, refilled_at: Instant::now() }),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = match self.try_acquire().await {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long until the next one is.
+    pub async fn try_acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().await;
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.refilled_at = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        }
+        else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_burst_then_wait() {
+        let bucket = TokenBucket::new(1.0, 2);
+
+        assert!(bucket.try_acquire().await.is_ok());
+        assert!(bucket.try_acquire().await.is_ok());
+
+        let wait = bucket.try_acquire().await.unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1 @@
+pub mod response_cache;
\ No newline at end of file
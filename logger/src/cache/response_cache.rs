@@ -0,0 +1,305 @@
+use std::{collections::HashMap, future::Future, sync::{Arc, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::database::{battlelog::response_cache::CachedResponseRow, store::BattlelogStore};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    /// Served from memory.
+    Hit,
+    /// Served from the database after a memory miss.
+    Persisted,
+    /// Fetched from Battlelog.
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn header_value(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Persisted => "HIT-DB",
+            CacheStatus::Miss => "MISS",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub body: String,
+    pub fetched_at: u32,
+    pub status: CacheStatus,
+}
+
+impl CachedResponse {
+    /// Seconds since the response was fetched from Battlelog.
+    pub fn age(&self) -> u32 {
+        now().saturating_sub(self.fetched_at)
+    }
+}
+
+/// How long the proxy endpoints keep their responses, `None` meaning forever.
+#[derive(Debug, Clone)]
+pub struct CacheTtls {
+    pub battlereport_incomplete: Duration,
+    pub playerreport: Option<Duration>,
+    pub loadout: Duration,
+}
+
+impl CacheTtls {
+    pub fn from_env() -> Self {
+        Self {
+            battlereport_incomplete: Duration::from_secs(env_u64("CACHE_TTL_BATTLEREPORT_INCOMPLETE", 60)),
+            playerreport: match env_u64("CACHE_TTL_PLAYERREPORT", 0) {
+                0 => None,
+                ttl => Some(Duration::from_secs(ttl)),
+            },
+            loadout: Duration::from_secs(env_u64("CACHE_TTL_LOADOUT", 300)),
+        }
+    }
+}
+
+struct Entry {
+    body: String,
+    fetched_at: u32,
+    expires_at: Option<u32>,
+    last_used: u64,
+}
+
+impl Entry {
+    fn is_fresh(&self) -> bool {
+        self.expires_at.map(|expires_at| expires_at > now()).unwrap_or(true)
+    }
+}
+
+/// Least recently used map of cached responses.
+struct Lru {
+    capacity: usize,
+    counter: u64,
+    entries: HashMap<String, Entry>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            counter: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&Entry> {
+        self.counter += 1;
+        let counter = self.counter;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = counter;
+                Some(entry)
+            },
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: String, mut entry: Entry) {
+        self.counter += 1;
+        entry.last_used = self.counter;
+        self.entries.insert(key, entry);
+
+        while self.entries.len() > self.capacity {
+            let oldest = self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => { self.entries.remove(&oldest); },
+                None => break,
+            }
+        }
+    }
+}
+
+/// Caches the raw JSON of the Battlelog proxy endpoints in memory and optionally in the database,
+/// so the responses survive restarts.
+pub struct ResponseCache {
+    db: Arc<dyn BattlelogStore>,
+    persist: bool,
+    pub ttls: CacheTtls,
+    /// How often the expired responses are deleted from the database.
+    pub purge_interval: Duration,
+    entries: Mutex<Lru>,
+}
+
+impl ResponseCache {
+    pub fn new(db: Arc<dyn BattlelogStore>, capacity: usize, persist: bool, ttls: CacheTtls) -> Self {
+        Self {
+            db,
+            persist,
+            ttls,
+            purge_interval: Duration::from_secs(60 * 60),
+            entries: Mutex::new(Lru::new(capacity)),
+        }
+    }
+
+    pub fn from_env(db: Arc<dyn BattlelogStore>) -> Self {
+        let persist = dotenv::var("RESPONSE_CACHE_PERSIST").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap();
+        let mut cache = ResponseCache::new(db, env_u64("RESPONSE_CACHE_CAPACITY", 1000) as usize, persist, CacheTtls::from_env());
+        cache.purge_interval = Duration::from_secs(env_u64("RESPONSE_CACHE_PURGE_INTERVAL", 60 * 60).max(1));
+        cache
+    }
+
+    /// Deletes the expired responses from the database until shutdown. A persisted response is
+    /// otherwise only replaced when the same key is fetched again.
+    pub async fn purge_expired(self: Arc<Self>, shutdown: CancellationToken) {
+        if !self.persist {
+            return;
+        }
+
+        let mut interval = tokio::time::interval(self.purge_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = shutdown.cancelled() => return,
+            }
+
+            match self.db.delete_expired_cached_responses(now()).await {
+                Ok(0) => {},
+                Ok(deleted) => debug!("Purged {} expired cached responses", deleted),
+                Err(err) => warn!("Failed to purge expired cached responses: {}", err),
+            }
+        }
+    }
+
+    /// Returns the cached response for the key, or calls `fetch` for the JSON body and how long
+    /// it may be cached. `refresh` skips the cache lookup but still stores the fetched response.
+    pub async fn get_or_fetch<F, Fut>(&self, key: &str, refresh: bool, fetch: F) -> anyhow::Result<CachedResponse>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<(String, Option<Duration>)>>,
+    {
+        if !refresh {
+            if let Some(cached) = self.lookup(key).await {
+                return Ok(cached);
+            }
+        }
+
+        let (body, ttl) = fetch().await?;
+        let fetched_at = now();
+        let expires_at = ttl.map(|ttl| fetched_at + ttl.as_secs() as u32);
+
+        self.entries.lock().unwrap().insert(key.to_string(), Entry {
+            body: body.clone(),
+            fetched_at,
+            expires_at,
+            last_used: 0,
+        });
+
+        if self.persist {
+            let row = CachedResponseRow {
+                cache_key: key.to_string(),
+                body: body.clone(),
+                fetched_at,
+                expires_at,
+            };
+            if let Err(err) = self.db.upsert_cached_response(&row).await {
+                warn!("Failed to persist cached response {}: {}", key, err);
+            }
+        }
+
+        Ok(CachedResponse {
+            body,
+            fetched_at,
+            status: CacheStatus::Miss,
+        })
+    }
+
+    async fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        if let Some(entry) = self.entries.lock().unwrap().get(key) {
+            if entry.is_fresh() {
+                return Some(CachedResponse {
+                    body: entry.body.clone(),
+                    fetched_at: entry.fetched_at,
+                    status: CacheStatus::Hit,
+                });
+            }
+        }
+
+        if !self.persist {
+            return None;
+        }
+
+        let row = match self.db.get_cached_response(key).await {
+            Ok(row) => row?,
+            Err(err) => {
+                warn!("Failed to read cached response {}: {}", key, err);
+                return None;
+            },
+        };
+
+        let entry = Entry {
+            body: row.body,
+            fetched_at: row.fetched_at,
+            expires_at: row.expires_at,
+            last_used: 0,
+        };
+        if !entry.is_fresh() {
+            return None;
+        }
+
+        let cached = CachedResponse {
+            body: entry.body.clone(),
+            fetched_at: entry.fetched_at,
+            status: CacheStatus::Persisted,
+        };
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+
+        Some(cached)
+    }
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as u32
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    dotenv::var(name)
+        .map(|var| var.parse::<u64>())
+        .unwrap_or(Ok(default))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(body: &str) -> Entry {
+        Entry {
+            body: body.to_string(),
+            fetched_at: 0,
+            expires_at: None,
+            last_used: 0,
+        }
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert("a".to_string(), entry("a"));
+        lru.insert("b".to_string(), entry("b"));
+        assert!(lru.get("a").is_some());
+
+        lru.insert("c".to_string(), entry("c"));
+        assert!(lru.get("a").is_some());
+        assert!(lru.get("b").is_none());
+        assert!(lru.get("c").is_some());
+    }
+
+    #[test]
+    fn test_entry_freshness() {
+        let mut expired = entry("a");
+        expired.expires_at = Some(now() - 1);
+        assert!(!expired.is_fresh());
+        assert!(entry("a").is_fresh());
+    }
+}
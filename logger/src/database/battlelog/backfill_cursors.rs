@@ -0,0 +1,78 @@
+use serde::Serialize;
+use sqlx::{query_as, query};
+
+use super::context::BattlelogContext;
+
+/// Progress of the report history backfill of a persona. `timestamp` is the page of
+/// `warsawbattlereportspopulatemore` requested next, `completed` is set once the history was
+/// walked back to `cutoff` or ran out.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackfillCursor {
+    pub persona_id: u64,
+    pub timestamp: u32,
+    pub cutoff: u32,
+    pub reports_ingested: u32,
+    pub reports_failed: u32,
+    pub completed: bool,
+    pub updated_at: u32,
+}
+
+impl BackfillCursor {
+    pub fn new(persona_id: u64, timestamp: u32, cutoff: u32) -> Self {
+        Self {
+            persona_id,
+            timestamp,
+            cutoff,
+            reports_ingested: 0,
+            reports_failed: 0,
+            completed: false,
+            updated_at: timestamp,
+        }
+    }
+}
+
+struct Row {
+    pub persona_id: u64,
+    pub timestamp: u32,
+    pub cutoff: u32,
+    pub reports_ingested: u32,
+    pub reports_failed: u32,
+    pub completed: i8,
+    pub updated_at: u32,
+}
+
+impl From<Row> for BackfillCursor {
+    fn from(e: Row) -> Self {
+        BackfillCursor {
+            persona_id: e.persona_id,
+            timestamp: e.timestamp,
+            cutoff: e.cutoff,
+            reports_ingested: e.reports_ingested,
+            reports_failed: e.reports_failed,
+            completed: e.completed != 0,
+            updated_at: e.updated_at,
+        }
+    }
+}
+
+impl BattlelogContext {
+    pub async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        let res =
+            query_as!(Row, "SELECT * from backfill_cursors WHERE persona_id = ?", persona_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BackfillCursor::from))
+    }
+
+    pub async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        let res = query!(r#"INSERT INTO backfill_cursors (persona_id, timestamp, cutoff, reports_ingested, reports_failed, completed, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE timestamp = VALUES(timestamp), cutoff = VALUES(cutoff), reports_ingested = VALUES(reports_ingested), reports_failed = VALUES(reports_failed), completed = VALUES(completed), updated_at = VALUES(updated_at)"#,
+            cursor.persona_id, cursor.timestamp, cursor.cutoff, cursor.reports_ingested, cursor.reports_failed, cursor.completed, cursor.updated_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
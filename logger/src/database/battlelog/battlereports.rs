@@ -88,6 +88,25 @@ impl BattlelogContext {
         Ok(res)
     }
 
+    /// Ids of the stored battlereports played in `[from, to)` on the server.
+    pub async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        struct Row {
+            pub id: u64,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT id
+                FROM battlereports
+                WHERE (? IS NULL OR created_at >= ?)
+                    AND (? IS NULL OR created_at < ?)
+                    AND (? IS NULL OR server_id = ?)
+                ORDER BY id"#, from, from, to, to, server_id, server_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: Row| e.id).collect())
+    }
+
     pub async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
         self.insert_battlereport_private(None, battlereport).await
     }
@@ -123,20 +142,59 @@ impl BattlelogContext {
         Ok(res > 0)
     }
 
+    /// Inserts the battlereport or refreshes the existing row with the newer data. The `processed`
+    /// flag only moves forward so a partial re-import can't mark a finished report as unprocessed.
     pub async fn upsert_battlereport(&self, transaction: &mut Transaction<'_, MySql>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
-        let query = query!(r#"INSERT IGNORE INTO battlereports (id, duration, winner, server_id, map, mode, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"#, 
+        let query = query!(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                duration = GREATEST(duration, VALUES(duration)),
+                winner = IF(VALUES(winner) >= 0, VALUES(winner), winner),
+                server_id = VALUES(server_id),
+                map = IF(VALUES(map) <> '', VALUES(map), map),
+                mode = IF(VALUES(mode) <> '', VALUES(mode), mode),
+                processed = GREATEST(processed, VALUES(processed))"#, 
             battlereport.id, 
             battlereport.duration, 
             battlereport.winner, 
             battlereport.server_id,
             battlereport.map,
             battlereport.mode,
-            battlereport.created_at);
+            battlereport.created_at,
+            battlereport.processed);
         
         let res = query
                 .execute(&mut *transaction)
                 .await?
-                .last_insert_id();
+                .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the battlereport or overwrites the stored columns with it, for rebuilding the row from
+    /// archived data. `processed` is only set on insert, rebuilding doesn't change whether every
+    /// playerreport of the report was ingested.
+    pub async fn replace_battlereport(&self, transaction: &mut Transaction<'_, MySql>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let query = query!(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                duration = VALUES(duration),
+                winner = VALUES(winner),
+                server_id = VALUES(server_id),
+                map = VALUES(map),
+                mode = VALUES(mode),
+                created_at = VALUES(created_at)"#,
+            battlereport.id,
+            battlereport.duration,
+            battlereport.winner,
+            battlereport.server_id,
+            battlereport.map,
+            battlereport.mode,
+            battlereport.created_at,
+            battlereport.processed);
+
+        let res = query
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
 
         Ok(res > 0)
     }
@@ -0,0 +1,140 @@
+use serde::Serialize;
+use sqlx::query_as;
+
+use super::{context::BattlelogContext, personas::PersonaSearchResult};
+
+/// Aggregated stats of the player rounds played by a clan's members, `rounds` counts every member
+/// separately so two members in the same round are two rounds.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClanStats {
+    pub clan_tag: String,
+    pub members: i64,
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub kd_ratio: f32,
+    pub spm: f32,
+    pub win_rate: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClanActivity {
+    pub day: String,
+    pub rounds: i64,
+    pub players: i64,
+}
+
+struct StatsRow {
+    pub clan_tag: String,
+    pub members: i64,
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub spm_total: i64,
+}
+
+impl From<StatsRow> for ClanStats {
+    fn from(e: StatsRow) -> Self {
+        ClanStats {
+            clan_tag: e.clan_tag,
+            members: e.members,
+            rounds: e.rounds,
+            wins: e.wins,
+            kills: e.kills,
+            deaths: e.deaths,
+            kd_ratio: e.kills as f32 / e.deaths.max(1) as f32,
+            spm: if e.rounds > 0 { e.spm_total as f32 / e.rounds as f32 } else { 0.0 },
+            win_rate: if e.rounds > 0 { e.wins as f32 / e.rounds as f32 } else { 0.0 },
+        }
+    }
+}
+
+impl BattlelogContext {
+    pub async fn get_clan_roster(&self, clan_tag: &str) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        let res =
+            query_as!(PersonaSearchResult, r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5,
+                    MAX(b.created_at) AS last_seen,
+                    COUNT(b.id) AS "rounds!: i64"
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.clan_tag = ?
+                GROUP BY p.id
+                ORDER BY last_seen DESC"#, clan_tag)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Stats of the personas currently wearing the tag, optionally limited to a single server. Only
+    /// the rounds within the first and last time the name history saw a member with the tag count.
+    pub async fn get_clan_stats(&self, clan_tag: &str, server_id: Option<i32>) -> Result<ClanStats, sqlx::Error> {
+        let res =
+            query_as!(StatsRow, r#"SELECT ? AS "clan_tag!: String",
+                    COUNT(DISTINCT p.id) AS "members!: i64",
+                    COUNT(b.id) AS "rounds!: i64",
+                    CAST(COALESCE(SUM(pr.team = b.winner), 0) AS SIGNED) AS "wins!: i64",
+                    CAST(COALESCE(SUM(pr.kills), 0) AS SIGNED) AS "kills!: i64",
+                    CAST(COALESCE(SUM(pr.deaths), 0) AS SIGNED) AS "deaths!: i64",
+                    CAST(COALESCE(SUM(pr.spm), 0) AS SIGNED) AS "spm_total!: i64"
+                FROM personas p
+                LEFT JOIN (playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id AND (? IS NULL OR b.server_id = ?))
+                    ON pr.persona_id = p.id AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND b.created_at BETWEEN h.first_seen AND h.last_seen)
+                WHERE p.clan_tag = ?"#, clan_tag, server_id, server_id, clan_tag)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(ClanStats::from(res))
+    }
+
+    /// Rounds played by the clan's members per day since `since`, while wearing the tag like in `get_clan_stats`.
+    pub async fn get_clan_activity(&self, clan_tag: &str, since: u32) -> Result<Vec<ClanActivity>, sqlx::Error> {
+        let res =
+            query_as!(ClanActivity, r#"SELECT DATE_FORMAT(FROM_UNIXTIME(b.created_at), '%Y-%m-%d') AS "day!: String",
+                    COUNT(*) AS "rounds!: i64",
+                    COUNT(DISTINCT p.id) AS "players!: i64"
+                FROM personas p
+                JOIN playerreports pr ON pr.persona_id = p.id
+                JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.clan_tag = ? AND b.created_at >= ?
+                    AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND b.created_at BETWEEN h.first_seen AND h.last_seen)
+                GROUP BY 1
+                ORDER BY 1"#, clan_tag, since)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Clans seen on the server since `since` with at least `min_rounds` member rounds, ordered by rounds.
+    /// Members are grouped by their current tag, with the rounds they played wearing it like in `get_clan_stats`.
+    pub async fn get_clan_leaderboard(&self, server_id: i32, since: u32, min_rounds: u32) -> Result<Vec<ClanStats>, sqlx::Error> {
+        let mut res: Vec<StatsRow> =
+            query_as!(StatsRow, r#"SELECT p.clan_tag AS "clan_tag!: String",
+                    COUNT(DISTINCT p.id) AS "members!: i64",
+                    COUNT(*) AS "rounds!: i64",
+                    CAST(SUM(pr.team = b.winner) AS SIGNED) AS "wins!: i64",
+                    CAST(SUM(pr.kills) AS SIGNED) AS "kills!: i64",
+                    CAST(SUM(pr.deaths) AS SIGNED) AS "deaths!: i64",
+                    CAST(SUM(pr.spm) AS SIGNED) AS "spm_total!: i64"
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN personas p ON p.id = pr.persona_id
+                WHERE b.server_id = ? AND b.created_at >= ? AND p.clan_tag IS NOT NULL AND p.clan_tag <> ''
+                    AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND b.created_at BETWEEN h.first_seen AND h.last_seen)
+                GROUP BY p.clan_tag
+                HAVING COUNT(*) >= ?
+                ORDER BY 3 DESC"#, server_id, since, min_rounds)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(ClanStats::from).collect())
+    }
+}
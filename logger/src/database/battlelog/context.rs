@@ -107,6 +107,24 @@ mod test {
         panic!()
     }
 
+    #[ignore]
+    #[tokio::test]
+    async fn test_persona_upsert_keeps_known_values() -> anyhow::Result<()> {
+        let uri = get_db_coninfo()?;
+        let db = BattlelogContext::connect(uri).await?;
+        let mut transaction = db.begin_transaction().await?;
+        db.upsert_persona(&mut transaction, &BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), Some("PLT".to_string()), Some("0123456789abcdef".to_string()), true, None), 1598211430).await?;
+        db.upsert_persona(&mut transaction, &BattlelogPersona::new(824078704, None, None, None, false, None), 1598211430).await?;
+        transaction.commit().await?;
+
+        let persona = db.get_persona_by_persona_id(824078704).await?.unwrap();
+        assert_eq!(persona.name.as_deref(), Some("Tatarek99"));
+        assert_eq!(persona.clan_tag.as_deref(), Some("PLT"));
+        assert_eq!(persona.gravatar_md5.as_deref(), Some("0123456789abcdef"));
+        assert!(persona.processed);
+        Ok(())
+    }
+
     #[ignore]
     #[tokio::test]
     async fn test_battlereport_insert() -> anyhow::Result<()> {
@@ -0,0 +1,144 @@
+use serde::Serialize;
+use sqlx::query_as;
+
+use super::context::BattlelogContext;
+
+/// Which rounds are exported, `to` is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub server_id: Option<i32>,
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    pub map: Option<String>,
+}
+
+/// A playerreport with its round, persona and server flattened into a single row.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRow {
+    pub report_id: u64,
+    pub created_at: u32,
+    pub duration: u32,
+    pub map: String,
+    pub mode: String,
+    pub winner: i8,
+    pub server_id: i32,
+    pub server_name: String,
+    pub server_guid: String,
+    pub persona_id: u64,
+    pub persona_name: Option<String>,
+    pub clan_tag: Option<String>,
+    pub team: i8,
+    pub squad_id: i8,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub kd_ratio: f32,
+    pub spm: u32,
+    pub skill: i32,
+    pub shots_hit: f32,
+    pub shots_fired: f32,
+    pub accuracy_detailed: f32,
+    pub heals: u16,
+    pub revives: u16,
+    pub kill_streak: u16,
+    pub vehicle_destroyed: u32,
+    pub total: u32,
+    pub dnf: bool,
+    pub is_commander: bool,
+    pub is_soldier: bool,
+}
+
+impl BattlelogContext {
+    /// Up to `limit` rows after the `(report_id, persona_id)` of `after`, ordered by them so the
+    /// export can be read page by page.
+    pub async fn get_export_rows(&self, filter: &ExportFilter, after: (u64, u64), limit: u32) -> Result<Vec<ExportRow>, sqlx::Error> {
+        struct Row {
+            pub report_id: u64,
+            pub created_at: u32,
+            pub duration: u32,
+            pub map: String,
+            pub mode: String,
+            pub winner: i8,
+            pub server_id: i32,
+            pub server_name: String,
+            pub server_guid: String,
+            pub persona_id: u64,
+            pub persona_name: Option<String>,
+            pub clan_tag: Option<String>,
+            pub team: i8,
+            pub squad_id: i8,
+            pub kills: u32,
+            pub deaths: u32,
+            pub assists: u32,
+            pub kd_ratio: f32,
+            pub spm: u32,
+            pub skill: i32,
+            pub shots_hit: f32,
+            pub shots_fired: f32,
+            pub accuracy_detailed: f32,
+            pub heals: u16,
+            pub revives: u16,
+            pub kill_streak: u16,
+            pub vehicle_destroyed: u32,
+            pub total: u32,
+            pub dnf: u8,
+            pub is_commander: u8,
+            pub is_soldier: u8,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT pr.report_id, b.created_at, b.duration, b.map, b.mode, b.winner,
+                    b.server_id, s.name AS server_name, s.guid AS server_guid,
+                    pr.persona_id, p.name AS persona_name, p.clan_tag,
+                    pr.team, pr.squad_id, pr.kills, pr.deaths, pr.assists, pr.kd_ratio, pr.spm, pr.skill,
+                    pr.shots_hit, pr.shots_fired, pr.accuracy_detailed, pr.heals, pr.revives, pr.kill_streak,
+                    pr.vehicle_destroyed, pr.total, pr.dnf, pr.is_commander, pr.is_soldier
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN servers s ON s.id = b.server_id
+                LEFT JOIN personas p ON p.id = pr.persona_id
+                WHERE (pr.report_id, pr.persona_id) > (?, ?)
+                    AND (? IS NULL OR b.server_id = ?)
+                    AND (? IS NULL OR b.created_at >= ?)
+                    AND (? IS NULL OR b.created_at < ?)
+                    AND (? IS NULL OR b.map = ?)
+                ORDER BY pr.report_id, pr.persona_id
+                LIMIT ?"#, after.0, after.1, filter.server_id, filter.server_id, filter.from, filter.from, filter.to, filter.to, filter.map, filter.map, limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: Row| ExportRow {
+            report_id: e.report_id,
+            created_at: e.created_at,
+            duration: e.duration,
+            map: e.map,
+            mode: e.mode,
+            winner: e.winner,
+            server_id: e.server_id,
+            server_name: e.server_name,
+            server_guid: e.server_guid,
+            persona_id: e.persona_id,
+            persona_name: e.persona_name,
+            clan_tag: e.clan_tag,
+            team: e.team,
+            squad_id: e.squad_id,
+            kills: e.kills,
+            deaths: e.deaths,
+            assists: e.assists,
+            kd_ratio: e.kd_ratio,
+            spm: e.spm,
+            skill: e.skill,
+            shots_hit: e.shots_hit,
+            shots_fired: e.shots_fired,
+            accuracy_detailed: e.accuracy_detailed,
+            heals: e.heals,
+            revives: e.revives,
+            kill_streak: e.kill_streak,
+            vehicle_destroyed: e.vehicle_destroyed,
+            total: e.total,
+            dnf: e.dnf == 1,
+            is_commander: e.is_commander == 1,
+            is_soldier: e.is_soldier == 1,
+        }).collect())
+    }
+}
@@ -0,0 +1,54 @@
+use serde::Serialize;
+use sqlx::{query_as, query};
+
+use super::context::BattlelogContext;
+
+/// Loadout as returned by Battlelog, stored as JSON whenever it differs from the previous snapshot.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadoutSnapshot {
+    pub id: u64,
+    pub persona_id: u64,
+    pub created_at: u32,
+    pub loadout: String,
+}
+
+impl LoadoutSnapshot {
+    pub fn new(persona_id: u64, created_at: u32, loadout: String) -> Self {
+        Self {
+            id: 0,
+            persona_id,
+            created_at,
+            loadout,
+        }
+    }
+}
+
+impl BattlelogContext {
+    pub async fn get_latest_loadout_snapshot(&self, persona_id: u64) -> Result<Option<LoadoutSnapshot>, sqlx::Error> {
+        let res =
+            query_as!(LoadoutSnapshot, "SELECT * from loadout_snapshots WHERE persona_id = ? ORDER BY created_at DESC, id DESC LIMIT 1", persona_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Newest snapshots first.
+    pub async fn get_loadout_snapshots(&self, persona_id: u64, limit: u32) -> Result<Vec<LoadoutSnapshot>, sqlx::Error> {
+        let res =
+            query_as!(LoadoutSnapshot, "SELECT * from loadout_snapshots WHERE persona_id = ? ORDER BY created_at DESC, id DESC LIMIT ?", persona_id, limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn insert_loadout_snapshot(&self, snapshot: &LoadoutSnapshot) -> anyhow::Result<u64> {
+        let res = query!(r#"INSERT INTO loadout_snapshots (persona_id, created_at, loadout) VALUES (?, ?, ?)"#, snapshot.persona_id, snapshot.created_at, snapshot.loadout)
+            .execute(&self.pool)
+            .await?
+            .last_insert_id();
+
+        Ok(res)
+    }
+}
@@ -5,4 +5,12 @@ pub mod playerreports;
 pub mod servers;
 pub mod game_expansions;
 pub mod persona_game_expansion;
-pub mod persona_info;
\ No newline at end of file
+pub mod persona_info;
+pub mod persona_name_history;
+pub mod clans;
+pub mod loadout_snapshots;
+pub mod response_cache;
+pub mod report_archives;
+pub mod backfill_cursors;
+pub mod exports;
+pub mod store;
@@ -20,6 +20,42 @@ impl PersonaGameExpansion {
     }
 }
 
+/// Persona's expansion with the `game_expansions` description resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonaGameExpansionDetails {
+    pub game_expansion_id: u64,
+    pub value: Option<String>,
+    pub explanation: Option<String>,
+    pub platforms: u32,
+}
+
+/// Number of personas owning an expansion on the same set of platforms.
+#[derive(Debug, Clone, Serialize)]
+pub struct GameExpansionOwnership {
+    pub game_expansion_id: u64,
+    pub explanation: Option<String>,
+    pub platforms: u32,
+    pub owners: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GameExpansionShare {
+    pub game_expansion_id: u64,
+    pub explanation: Option<String>,
+    pub owners: i64,
+    pub share: f32,
+}
+
+/// Expansion ownership among the players active on a server. The share is calculated from the
+/// `checked_players` since personas never updated from Battlelog have no expansions stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerGameExpansionShare {
+    pub server_id: i32,
+    pub active_players: i64,
+    pub checked_players: i64,
+    pub expansions: Vec<GameExpansionShare>,
+}
+
 impl BattlelogContext {
     pub async fn get_persona_game_expansion_by_id(&self, persona_id: u64, game_expansion_id: u64) -> Result<Option<PersonaGameExpansion>, sqlx::Error> {
         let res =
@@ -39,6 +75,87 @@ impl BattlelogContext {
         Ok(res)
     }
 
+    pub async fn get_persona_game_expansion_details(&self, persona_id: u64) -> Result<Vec<PersonaGameExpansionDetails>, sqlx::Error> {
+        let res: Vec<PersonaGameExpansionDetails> =
+            query_as!(PersonaGameExpansionDetails, r#"SELECT pge.game_expansion_id, ge.value, ge.explanation, pge.platforms
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                WHERE pge.persona_id = ?
+                ORDER BY pge.game_expansion_id"#, persona_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn get_game_expansion_ownership(&self) -> Result<Vec<GameExpansionOwnership>, sqlx::Error> {
+        let res: Vec<GameExpansionOwnership> =
+            query_as!(GameExpansionOwnership, r#"SELECT pge.game_expansion_id, ge.explanation, pge.platforms, COUNT(*) AS "owners!: i64"
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                GROUP BY pge.game_expansion_id, ge.explanation, pge.platforms
+                ORDER BY pge.game_expansion_id, pge.platforms"#)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Expansion ownership among the personas who played on the server since `since`.
+    pub async fn get_server_game_expansion_share(&self, server_id: i32, since: u32) -> Result<ServerGameExpansionShare, sqlx::Error> {
+        struct PlayersRow {
+            pub active_players: i64,
+            pub checked_players: i64,
+        }
+
+        struct OwnersRow {
+            pub game_expansion_id: u64,
+            pub explanation: Option<String>,
+            pub owners: i64,
+        }
+
+        let players =
+            query_as!(PlayersRow, r#"SELECT COUNT(*) AS "active_players!: i64",
+                    CAST(COALESCE(SUM(p.last_updated IS NOT NULL), 0) AS SIGNED) AS "checked_players!: i64"
+                FROM personas p
+                WHERE p.id IN (
+                    SELECT pr.persona_id FROM playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id
+                    WHERE b.server_id = ? AND b.created_at >= ?
+                )"#, server_id, since)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let mut owners: Vec<OwnersRow> =
+            query_as!(OwnersRow, r#"SELECT pge.game_expansion_id, ge.explanation, COUNT(DISTINCT pge.persona_id) AS "owners!: i64"
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                WHERE pge.persona_id IN (
+                    SELECT pr.persona_id FROM playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id
+                    WHERE b.server_id = ? AND b.created_at >= ?
+                )
+                GROUP BY pge.game_expansion_id, ge.explanation
+                ORDER BY 3 DESC"#, server_id, since)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let checked_players = players.checked_players;
+        let expansions = owners.drain(..).map(|e: OwnersRow| GameExpansionShare {
+            game_expansion_id: e.game_expansion_id,
+            explanation: e.explanation,
+            owners: e.owners,
+            share: if checked_players > 0 { e.owners as f32 / checked_players as f32 } else { 0.0 },
+        }).collect();
+
+        Ok(ServerGameExpansionShare {
+            server_id,
+            active_players: players.active_players,
+            checked_players,
+            expansions,
+        })
+    }
+
     pub async fn insert_persona_game_expansion(&self, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
         self.insert_persona_game_expansion_private(None, p).await
     }
@@ -68,7 +185,8 @@ impl BattlelogContext {
     }
 
     pub async fn upsert_persona_game_expansion(&self, transaction: &mut Transaction<'_, MySql>, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
-        let query = query!(r#"INSERT IGNORE INTO persona_game_expansions (persona_id, game_expansion_id, platforms) VALUES (?, ?, ?)"#, p.persona_id, p.game_expansion_id, p.platforms);
+        let query = query!(r#"INSERT INTO persona_game_expansions (persona_id, game_expansion_id, platforms) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE platforms = VALUES(platforms)"#, p.persona_id, p.game_expansion_id, p.platforms);
         let res = query
             .execute(&mut *transaction)
             .await?
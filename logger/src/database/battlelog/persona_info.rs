@@ -36,6 +36,15 @@ impl BattlelogContext {
         Ok(res)
     }
 
+    pub async fn get_persona_info_by_id_with_transaction(&self, transaction: &mut Transaction<'_, MySql>, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        let res =
+            query_as!(PersonaInfo, "SELECT * from persona_infos WHERE persona_id = ?", persona_id)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        Ok(res)
+    }
+
     pub async fn get_persona_infos(&self) -> Result<Vec<PersonaInfo>, sqlx::Error> {
         let mut res: Vec<PersonaInfo> =
             query_as!(PersonaInfo, "SELECT * from persona_infos")
@@ -74,7 +83,13 @@ impl BattlelogContext {
     }
 
     pub async fn upsert_persona_info(&self, transaction: &mut Transaction<'_, MySql>, p: &PersonaInfo) -> anyhow::Result<u64> {
-        let query = query!(r#"INSERT IGNORE INTO persona_infos (persona_id, locality, location, presentation, login_counter, last_login) VALUES (?, ?, ?, ?, ?, ?)"#, p.persona_id, p.locality, p.location, p.presentation, p.login_counter, p.last_login);
+        let query = query!(r#"INSERT INTO persona_infos (persona_id, locality, location, presentation, login_counter, last_login) VALUES (?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                locality = VALUES(locality),
+                location = VALUES(location),
+                presentation = VALUES(presentation),
+                login_counter = VALUES(login_counter),
+                last_login = VALUES(last_login)"#, p.persona_id, p.locality, p.location, p.presentation, p.login_counter, p.last_login);
         let res = query
             .execute(&mut *transaction)
             .await?
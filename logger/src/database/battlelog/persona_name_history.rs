@@ -0,0 +1,90 @@
+use serde::Serialize;
+use sqlx::{query_as, query, MySql, Transaction};
+
+use super::{context::BattlelogContext, personas::BattlelogPersona};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonaNameHistory {
+    pub persona_id: u64,
+    pub name: String,
+    pub clan_tag: Option<String>,
+    pub first_seen: u32,
+    pub last_seen: u32,
+}
+
+impl PersonaNameHistory {
+    pub fn new(persona_id: u64, name: String, clan_tag: Option<String>, first_seen: u32, last_seen: u32) -> Self {
+        Self {
+            persona_id,
+            name,
+            clan_tag,
+            first_seen,
+            last_seen,
+        }
+    }
+
+    /// Name history entry for a persona seen at `seen_at`, `None` if the persona has no known name.
+    pub fn from_persona(persona: &BattlelogPersona, seen_at: u32) -> Option<Self> {
+        persona.name.as_ref().map(|name| PersonaNameHistory::new(persona.id, name.to_string(), persona.clan_tag.clone(), seen_at, seen_at))
+    }
+}
+
+struct Row {
+    pub persona_id: u64,
+    pub name: String,
+    pub clan_tag: String,
+    pub first_seen: u32,
+    pub last_seen: u32,
+}
+
+impl From<Row> for PersonaNameHistory {
+    fn from(e: Row) -> Self {
+        PersonaNameHistory {
+            persona_id: e.persona_id,
+            name: e.name,
+            // Missing clan tag is stored as an empty string to keep it in the primary key
+            clan_tag: if e.clan_tag.is_empty() { None } else { Some(e.clan_tag) },
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        }
+    }
+}
+
+impl BattlelogContext {
+    pub async fn get_persona_name_history(&self, persona_id: u64) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as!(Row, "SELECT * from persona_name_history WHERE persona_id = ? ORDER BY last_seen DESC", persona_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaNameHistory::from).collect())
+    }
+
+    pub async fn get_persona_name_history_by_name(&self, name: &str) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as!(Row, "SELECT * from persona_name_history WHERE name = ? ORDER BY last_seen DESC", name)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaNameHistory::from).collect())
+    }
+
+    /// Records the name and clan tag, widening the first/last seen range if it was already known.
+    pub async fn upsert_persona_name_history(&self, transaction: &mut Transaction<'_, MySql>, p: &PersonaNameHistory) -> anyhow::Result<u64> {
+        let query = query!(r#"INSERT INTO persona_name_history (persona_id, name, clan_tag, first_seen, last_seen) VALUES (?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                first_seen = LEAST(first_seen, VALUES(first_seen)),
+                last_seen = GREATEST(last_seen, VALUES(last_seen))"#, 
+            p.persona_id, 
+            p.name, 
+            p.clan_tag.as_deref().unwrap_or(""), 
+            p.first_seen, 
+            p.last_seen);
+        let res = query
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
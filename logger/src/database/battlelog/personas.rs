@@ -58,6 +58,26 @@ impl BattlelogPersona {
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct PersonaSearchResult {
+    pub id: u64,
+    pub name: Option<String>,
+    pub clan_tag: Option<String>,
+    pub gravatar_md5: Option<String>,
+    pub last_seen: Option<u32>,
+    pub rounds: i64,
+}
+
+/// Turns user input into a case-insensitive `LIKE` prefix pattern.
+pub(crate) fn like_prefix(value: &str) -> String {
+    let escaped = value
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}%", escaped)
+}
+
 impl BattlelogContext {
     pub async fn get_persona_by_persona_id(&self, persona: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
         struct Row {
@@ -70,7 +90,7 @@ impl BattlelogContext {
         }
 
         let res =
-            query_as!(Row, "SELECT * from personas WHERE id = ?", persona)
+            query_as!(Row, "SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE id = ?", persona)
             .fetch_optional(&self.pool)
             .await?;
 
@@ -86,6 +106,33 @@ impl BattlelogContext {
         Ok(res)
     }
 
+    pub async fn get_persona_by_persona_id_with_transaction(&self, transaction: &mut Transaction<'_, MySql>, persona: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        struct Row {
+            pub id: u64,
+            pub name: Option<String>,
+            pub clan_tag: Option<String>,
+            pub gravatar_md5: Option<String>,
+            pub processed: u8,
+            pub last_updated: Option<u32>,
+        }
+
+        let res =
+            query_as!(Row, "SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE id = ?", persona)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        let res = res.map(|e: Row| BattlelogPersona {
+            id: e.id,
+            name: e.name,
+            clan_tag: e.clan_tag,
+            gravatar_md5: e.gravatar_md5,
+            processed: e.processed == 1,
+            last_updated: e.last_updated,
+        });
+
+        Ok(res)
+    }
+
     pub async fn get_persona_by_persona_id_str(&self, persona: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
         pub struct Row {
             pub id: u64,
@@ -97,7 +144,35 @@ impl BattlelogContext {
         }
 
         let res =
-            query_as!(Row, "SELECT * from personas WHERE id = ?", persona)
+            query_as!(Row, "SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE id = ?", persona)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let res = res.map(|e: Row| BattlelogPersona {
+            id: e.id,
+            name: e.name,
+            clan_tag: e.clan_tag,
+            gravatar_md5: e.gravatar_md5,
+            processed: e.processed == 1,
+            last_updated: e.last_updated,
+        });
+
+        Ok(res)
+    }
+
+    /// Persona currently using the name, the most recently updated one if the name has changed hands.
+    pub async fn get_persona_by_name(&self, name: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        pub struct Row {
+            pub id: u64,
+            pub name: Option<String>,
+            pub clan_tag: Option<String>,
+            pub gravatar_md5: Option<String>,
+            pub processed: u8,
+            pub last_updated: Option<u32>,
+        }
+
+        let res =
+            query_as!(Row, "SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE name = ? ORDER BY last_updated DESC LIMIT 1", name)
             .fetch_optional(&self.pool)
             .await?;
 
@@ -124,7 +199,75 @@ impl BattlelogContext {
         }
 
         let mut res: Vec<Row> =
-            query_as!(Row, "SELECT * from personas WHERE last_updated IS NULL")
+            query_as!(Row, "SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE last_updated IS NULL")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let res: Vec<BattlelogPersona> = res.drain(..).map(|e: Row| BattlelogPersona {
+            id: e.id,
+            name: e.name,
+            clan_tag: e.clan_tag,
+            gravatar_md5: e.gravatar_md5,
+            processed: e.processed == 1,
+            last_updated: e.last_updated,
+        }).collect();
+
+        Ok(res)
+    }
+
+    /// Searches personas by a prefix of their current or any historical name and clan tag. Both filters
+    /// are case-insensitive and optional, leaving both out matches every persona.
+    pub async fn search_personas(&self, name: Option<&str>, clan_tag: Option<&str>, limit: u32) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        let name_pattern = like_prefix(name.unwrap_or(""));
+        let clan_pattern = clan_tag.map(like_prefix);
+
+        let res =
+            query_as!(PersonaSearchResult, r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5,
+                    MAX(b.created_at) AS last_seen,
+                    COUNT(b.id) AS "rounds!: i64"
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.id IN (
+                    SELECT persona_id FROM persona_name_history
+                        WHERE LOWER(name) LIKE ? AND (? IS NULL OR LOWER(clan_tag) LIKE ?)
+                    UNION
+                    SELECT id FROM personas
+                        WHERE LOWER(COALESCE(name, '')) LIKE ? AND (? IS NULL OR LOWER(clan_tag) LIKE ?)
+                )
+                GROUP BY p.id
+                ORDER BY last_seen DESC
+                LIMIT ?"#,
+                name_pattern, clan_pattern, clan_pattern,
+                name_pattern, clan_pattern, clan_pattern,
+                limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Personas not updated since `stale_before`, the ones seen on our servers since `active_since`
+    /// first and then the ones with the oldest data.
+    pub async fn get_personas_for_refresh(&self, active_since: u32, stale_before: u32, limit: u32) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        pub struct Row {
+            pub id: u64,
+            pub name: Option<String>,
+            pub clan_tag: Option<String>,
+            pub gravatar_md5: Option<String>,
+            pub processed: u8,
+            pub last_updated: Option<u32>,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5, p.processed, p.last_updated
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.last_updated IS NULL OR p.last_updated < ?
+                GROUP BY p.id
+                ORDER BY COALESCE(MAX(b.created_at), 0) >= ? DESC, p.last_updated IS NOT NULL, p.last_updated
+                LIMIT ?"#, stale_before, active_since, limit)
             .fetch_all(&self.pool)
             .await?;
 
@@ -168,12 +311,24 @@ impl BattlelogContext {
         Ok(res)
     }
 
-    pub async fn upsert_persona(&self, transaction: &mut Transaction<'_, MySql>, persona: &BattlelogPersona) -> anyhow::Result<u64> {
-        let query = query!(r#"INSERT IGNORE INTO personas (id, name, clan_tag, gravatar_md5, processed) VALUES (?, ?, ?, ?, ?)"#, persona.id, persona.name, persona.clan_tag, persona.gravatar_md5, persona.processed);
+    /// Inserts the persona or merges it into the existing row. A known name or gravatar is never
+    /// overwritten with NULL, while the clan tag follows the most recently seen processed record
+    /// since leaving a clan is reported as a missing tag. `clan_tag_seen_at` keeps an old report
+    /// from bringing back a stale tag.
+    pub async fn upsert_persona(&self, transaction: &mut Transaction<'_, MySql>, persona: &BattlelogPersona, seen_at: u32) -> anyhow::Result<u64> {
+        let clan_tag_seen_at = if persona.processed { Some(seen_at) } else { None };
+        // The clan tag is assigned before clan_tag_seen_at, which still holds the old time then
+        let query = query!(r#"INSERT INTO personas (id, name, clan_tag, gravatar_md5, processed, clan_tag_seen_at) VALUES (?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                name = COALESCE(VALUES(name), name),
+                clan_tag = IF(VALUES(clan_tag_seen_at) >= COALESCE(clan_tag_seen_at, 0), VALUES(clan_tag), clan_tag),
+                clan_tag_seen_at = IF(VALUES(clan_tag_seen_at) >= COALESCE(clan_tag_seen_at, 0), VALUES(clan_tag_seen_at), clan_tag_seen_at),
+                gravatar_md5 = COALESCE(VALUES(gravatar_md5), gravatar_md5),
+                processed = GREATEST(processed, VALUES(processed))"#, persona.id, persona.name, persona.clan_tag, persona.gravatar_md5, persona.processed, clan_tag_seen_at);
         let res = query
             .execute(&mut *transaction)
             .await?
-            .last_insert_id();
+            .rows_affected();
 
         Ok(res)
     }
@@ -211,4 +366,25 @@ impl BattlelogContext {
 
         Ok(res > 0)
     }
-}
\ No newline at end of file
+
+    pub async fn update_persona_last_updated(&self, transaction: &mut Transaction<'_, MySql>, persona_id: u64, last_updated: u32) -> anyhow::Result<bool> {
+        let res = query!(r#"UPDATE personas SET last_updated = ? WHERE id = ?"#, last_updated, persona_id)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::like_prefix;
+
+    #[test]
+    fn test_like_prefix_escapes_wildcards() {
+        assert_eq!(like_prefix("xFile"), "xfile%");
+        assert_eq!(like_prefix("100%_Pro"), "100\\%\\_pro%");
+        assert_eq!(like_prefix(""), "%");
+    }
+}
@@ -180,57 +180,84 @@ impl BattlelogPlayerreport {
     }
 }
 
-impl BattlelogContext {
-    pub async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
-        pub struct Row {
-            pub report_id: u64,
-            pub persona_id: u64,
-            pub kills: u32,
-            pub deaths: u32,
-            pub shots_hit: f32,
-            pub shots_fired: f32,
-            pub vehicle_destroyed: u32,
-            pub assists: u32,
-            pub spm: u32,
-            pub kd_ratio: f32,
-            pub skill: i32,
-            pub vehicle_assists: u32,
-            pub accuracy: u32,
-            pub sc_unlock: u32,
-            pub sc_bomber: u32,
-            pub sc_vehiclesh: u32,
-            pub sc_vehicleajet: u32,
-            pub sc_engineer: u32,
-            pub sc_commander: u32,
-            pub sc_assault: u32,
-            pub vehicle: u32,
-            pub sc_vehicleaa: u32,
-            pub sc_award: u32,
-            pub sc_vehicleifv: u32,
-            pub sc_recon: u32,
-            pub sc_vehicleah: u32,
-            pub sc_support: u32,
-            pub sc_vehiclesjet: u32,
-            pub total: u32,
-            pub sc_vehiclembt: u32,
-            pub sc_vehicleaboat: u32,
-            pub heals: u16,
-            pub revives: u16,
-            pub team: i8,
-            pub kill_streak: u16,
-            pub squad_id: i8,
-            pub accuracy_detailed: f32,
-            pub dnf: u8,
-            pub is_commander: u8,
-            pub is_soldier: u8,
-        }
+/// Round on our servers from a persona's point of view.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonaRound {
+    pub report_id: u64,
+    pub server_id: i32,
+    pub server_name: String,
+    pub map: String,
+    pub mode: String,
+    pub created_at: u32,
+    pub duration: u32,
+    pub team: i8,
+    pub winner: i8,
+    pub kills: u32,
+    pub deaths: u32,
+    pub spm: u32,
+    pub total: u32,
+    pub dnf: bool,
+}
 
-        let res =
-            query_as!(Row, "SELECT * from playerreports WHERE report_id = ? AND persona_id = ?", report_id, persona_id)
-            .fetch_optional(&self.pool)
-            .await?;
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonaStats {
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub kd_ratio: f32,
+    pub spm: f32,
+    pub seconds_played: i64,
+    pub first_seen: Option<u32>,
+    pub last_seen: Option<u32>,
+}
 
-        let res = res.map(|e: Row| BattlelogPlayerreport {
+struct PlayerreportRow {
+    pub report_id: u64,
+    pub persona_id: u64,
+    pub kills: u32,
+    pub deaths: u32,
+    pub shots_hit: f32,
+    pub shots_fired: f32,
+    pub vehicle_destroyed: u32,
+    pub assists: u32,
+    pub spm: u32,
+    pub kd_ratio: f32,
+    pub skill: i32,
+    pub vehicle_assists: u32,
+    pub accuracy: u32,
+    pub sc_unlock: u32,
+    pub sc_bomber: u32,
+    pub sc_vehiclesh: u32,
+    pub sc_vehicleajet: u32,
+    pub sc_engineer: u32,
+    pub sc_commander: u32,
+    pub sc_assault: u32,
+    pub vehicle: u32,
+    pub sc_vehicleaa: u32,
+    pub sc_award: u32,
+    pub sc_vehicleifv: u32,
+    pub sc_recon: u32,
+    pub sc_vehicleah: u32,
+    pub sc_support: u32,
+    pub sc_vehiclesjet: u32,
+    pub total: u32,
+    pub sc_vehiclembt: u32,
+    pub sc_vehicleaboat: u32,
+    pub heals: u16,
+    pub revives: u16,
+    pub team: i8,
+    pub kill_streak: u16,
+    pub squad_id: i8,
+    pub accuracy_detailed: f32,
+    pub dnf: u8,
+    pub is_commander: u8,
+    pub is_soldier: u8,
+}
+
+impl From<PlayerreportRow> for BattlelogPlayerreport {
+    fn from(e: PlayerreportRow) -> Self {
+        BattlelogPlayerreport {
             report_id: e.report_id,
             persona_id: e.persona_id,
             kills: e.kills,
@@ -271,11 +298,137 @@ impl BattlelogContext {
             dnf: e.dnf == 1,
             is_commander: e.is_commander == 1,
             is_soldier: e.is_soldier == 1,
-        });
+        }
+    }
+}
+
+impl BattlelogContext {
+    pub async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        let res =
+            query_as!(PlayerreportRow, "SELECT * from playerreports WHERE report_id = ? AND persona_id = ?", report_id, persona_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogPlayerreport::from))
+    }
+
+    pub async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        let mut res =
+            query_as!(PlayerreportRow, "SELECT * from playerreports WHERE report_id = ? ORDER BY persona_id", report_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(BattlelogPlayerreport::from).collect())
+    }
+
+    pub async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        pub struct Row {
+            pub report_id: u64,
+            pub server_id: i32,
+            pub server_name: String,
+            pub map: String,
+            pub mode: String,
+            pub created_at: u32,
+            pub duration: u32,
+            pub team: i8,
+            pub winner: i8,
+            pub kills: u32,
+            pub deaths: u32,
+            pub spm: u32,
+            pub total: u32,
+            pub dnf: u8,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT pr.report_id, b.server_id, s.name AS server_name, b.map, b.mode, b.created_at, b.duration, 
+                    pr.team, b.winner, pr.kills, pr.deaths, pr.spm, pr.total, pr.dnf
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN servers s ON s.id = b.server_id
+                WHERE pr.persona_id = ?
+                ORDER BY b.created_at DESC
+                LIMIT ?"#, persona_id, limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let res = res.drain(..).map(|e: Row| PersonaRound {
+            report_id: e.report_id,
+            server_id: e.server_id,
+            server_name: e.server_name,
+            map: e.map,
+            mode: e.mode,
+            created_at: e.created_at,
+            duration: e.duration,
+            team: e.team,
+            winner: e.winner,
+            kills: e.kills,
+            deaths: e.deaths,
+            spm: e.spm,
+            total: e.total,
+            dnf: e.dnf == 1,
+        }).collect();
 
         Ok(res)
     }
 
+    pub async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        pub struct Row {
+            pub rounds: i64,
+            pub wins: i64,
+            pub kills: i64,
+            pub deaths: i64,
+            pub spm_total: i64,
+            pub seconds_played: i64,
+            pub first_seen: Option<u32>,
+            pub last_seen: Option<u32>,
+        }
+
+        let e =
+            query_as!(Row, r#"SELECT COUNT(*) AS "rounds!: i64",
+                    CAST(COALESCE(SUM(pr.team = b.winner), 0) AS SIGNED) AS "wins!: i64",
+                    CAST(COALESCE(SUM(pr.kills), 0) AS SIGNED) AS "kills!: i64",
+                    CAST(COALESCE(SUM(pr.deaths), 0) AS SIGNED) AS "deaths!: i64",
+                    CAST(COALESCE(SUM(pr.spm), 0) AS SIGNED) AS "spm_total!: i64",
+                    CAST(COALESCE(SUM(b.duration), 0) AS SIGNED) AS "seconds_played!: i64",
+                    MIN(b.created_at) AS first_seen,
+                    MAX(b.created_at) AS last_seen
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                WHERE pr.persona_id = ?"#, persona_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(PersonaStats {
+            rounds: e.rounds,
+            wins: e.wins,
+            kills: e.kills,
+            deaths: e.deaths,
+            kd_ratio: e.kills as f32 / e.deaths.max(1) as f32,
+            spm: if e.rounds > 0 { e.spm_total as f32 / e.rounds as f32 } else { 0.0 },
+            seconds_played: e.seconds_played,
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        })
+    }
+
+    /// Personas who played in any of the stored rounds of the persona.
+    pub async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        struct Row {
+            pub persona_id: u64,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT DISTINCT pr.persona_id
+                FROM playerreports own
+                JOIN playerreports pr ON pr.report_id = own.report_id
+                WHERE own.persona_id = ? AND pr.persona_id <> ?
+                ORDER BY pr.persona_id"#, persona_id, persona_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: Row| e.persona_id).collect())
+    }
+
     pub async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
         self.insert_playerreport_private(None, playerreport).await
     }
@@ -345,9 +498,11 @@ impl BattlelogContext {
         Ok(res > 0)
     }
 
+    /// Inserts the playerreport or replaces the stored stats with the latest fetched ones.
     pub async fn upsert_playerreport(&self, transaction: &mut Transaction<'_, MySql>, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
-        let query = query!(r#"INSERT IGNORE INTO playerreports (report_id, persona_id, kills, deaths, shots_hit, shots_fired, vehicle_destroyed, assists, spm, kd_ratio, skill, vehicle_assists, accuracy, sc_unlock, sc_bomber, sc_vehiclesh, sc_vehicleajet, sc_engineer, sc_commander, sc_assault, vehicle, sc_vehicleaa, sc_award, sc_vehicleifv, sc_recon, sc_vehicleah, sc_support, sc_vehiclesjet, total, sc_vehiclembt, sc_vehicleaboat, heals, revives, team, kill_streak, squad_id, accuracy_detailed, dnf, is_commander, is_soldier) 
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#, 
+        let query = query!(r#"INSERT INTO playerreports (report_id, persona_id, kills, deaths, shots_hit, shots_fired, vehicle_destroyed, assists, spm, kd_ratio, skill, vehicle_assists, accuracy, sc_unlock, sc_bomber, sc_vehiclesh, sc_vehicleajet, sc_engineer, sc_commander, sc_assault, vehicle, sc_vehicleaa, sc_award, sc_vehicleifv, sc_recon, sc_vehicleah, sc_support, sc_vehiclesjet, total, sc_vehiclembt, sc_vehicleaboat, heals, revives, team, kill_streak, squad_id, accuracy_detailed, dnf, is_commander, is_soldier) 
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE kills = VALUES(kills), deaths = VALUES(deaths), shots_hit = VALUES(shots_hit), shots_fired = VALUES(shots_fired), vehicle_destroyed = VALUES(vehicle_destroyed), assists = VALUES(assists), spm = VALUES(spm), kd_ratio = VALUES(kd_ratio), skill = VALUES(skill), vehicle_assists = VALUES(vehicle_assists), accuracy = VALUES(accuracy), sc_unlock = VALUES(sc_unlock), sc_bomber = VALUES(sc_bomber), sc_vehiclesh = VALUES(sc_vehiclesh), sc_vehicleajet = VALUES(sc_vehicleajet), sc_engineer = VALUES(sc_engineer), sc_commander = VALUES(sc_commander), sc_assault = VALUES(sc_assault), vehicle = VALUES(vehicle), sc_vehicleaa = VALUES(sc_vehicleaa), sc_award = VALUES(sc_award), sc_vehicleifv = VALUES(sc_vehicleifv), sc_recon = VALUES(sc_recon), sc_vehicleah = VALUES(sc_vehicleah), sc_support = VALUES(sc_support), sc_vehiclesjet = VALUES(sc_vehiclesjet), total = VALUES(total), sc_vehiclembt = VALUES(sc_vehiclembt), sc_vehicleaboat = VALUES(sc_vehicleaboat), heals = VALUES(heals), revives = VALUES(revives), team = VALUES(team), kill_streak = VALUES(kill_streak), squad_id = VALUES(squad_id), accuracy_detailed = VALUES(accuracy_detailed), dnf = VALUES(dnf), is_commander = VALUES(is_commander), is_soldier = VALUES(is_soldier)"#, 
             playerreport.report_id,
             playerreport.persona_id,
             playerreport.kills,
@@ -391,7 +546,7 @@ impl BattlelogContext {
         let res = query
             .execute(&mut *transaction)
             .await?
-            .last_insert_id();
+            .rows_affected();
 
         Ok(res > 0)
     }
@@ -0,0 +1,83 @@
+use sqlx::{query_as, query, MySql, Transaction};
+
+use super::context::BattlelogContext;
+
+/// Gzipped battlereport response body, byte for byte as Battlelog sent it.
+#[derive(Debug, Clone)]
+pub struct BattlereportArchive {
+    pub report_id: u64,
+    pub data: Vec<u8>,
+    pub archived_at: u32,
+}
+
+/// Gzipped playerreport response body, byte for byte as Battlelog sent it.
+#[derive(Debug, Clone)]
+pub struct PlayerreportArchive {
+    pub report_id: u64,
+    pub persona_id: u64,
+    pub data: Vec<u8>,
+    pub archived_at: u32,
+}
+
+impl BattlelogContext {
+    pub async fn get_battlereport_archive(&self, report_id: u64) -> Result<Option<BattlereportArchive>, sqlx::Error> {
+        let res =
+            query_as!(BattlereportArchive, "SELECT * from battlereport_archives WHERE report_id = ?", report_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn get_playerreport_archives(&self, report_id: u64) -> Result<Vec<PlayerreportArchive>, sqlx::Error> {
+        let res =
+            query_as!(PlayerreportArchive, "SELECT * from playerreport_archives WHERE report_id = ?", report_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Ids of the archived battlereports, filtered by the stored battlereport row. Archives without
+    /// a battlereport row are only included when no filter is given.
+    pub async fn get_battlereport_archive_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        struct Row {
+            pub report_id: u64,
+        }
+
+        let mut res: Vec<Row> =
+            query_as!(Row, r#"SELECT a.report_id
+                FROM battlereport_archives a
+                LEFT JOIN battlereports b ON b.id = a.report_id
+                WHERE (? IS NULL OR b.created_at >= ?)
+                    AND (? IS NULL OR b.created_at < ?)
+                    AND (? IS NULL OR b.server_id = ?)
+                ORDER BY a.report_id"#, from, from, to, to, server_id, server_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: Row| e.report_id).collect())
+    }
+
+    pub async fn upsert_battlereport_archive(&self, transaction: &mut Transaction<'_, MySql>, archive: &BattlereportArchive) -> anyhow::Result<bool> {
+        let res = query!(r#"INSERT INTO battlereport_archives (report_id, data, archived_at) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE data = VALUES(data), archived_at = VALUES(archived_at)"#,
+            archive.report_id, archive.data, archive.archived_at)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn upsert_playerreport_archive(&self, transaction: &mut Transaction<'_, MySql>, archive: &PlayerreportArchive) -> anyhow::Result<bool> {
+        let res = query!(r#"INSERT INTO playerreport_archives (report_id, persona_id, data, archived_at) VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE data = VALUES(data), archived_at = VALUES(archived_at)"#,
+            archive.report_id, archive.persona_id, archive.data, archive.archived_at)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
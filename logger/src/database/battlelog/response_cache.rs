@@ -0,0 +1,44 @@
+use serde::Serialize;
+use sqlx::{query_as, query};
+
+use super::context::BattlelogContext;
+
+/// Raw JSON response of a Battlelog proxy endpoint, `expires_at` is `None` for responses that never change.
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedResponseRow {
+    pub cache_key: String,
+    pub body: String,
+    pub fetched_at: u32,
+    pub expires_at: Option<u32>,
+}
+
+impl BattlelogContext {
+    pub async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        let res =
+            query_as!(CachedResponseRow, "SELECT * from response_cache WHERE cache_key = ?", cache_key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        let res = query!(r#"INSERT INTO response_cache (cache_key, body, fetched_at, expires_at) VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE body = VALUES(body), fetched_at = VALUES(fetched_at), expires_at = VALUES(expires_at)"#,
+            row.cache_key, row.body, row.fetched_at, row.expires_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        let res = query!(r#"DELETE FROM response_cache WHERE expires_at IS NOT NULL AND expires_at < ?"#, now)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,278 @@
+use async_trait::async_trait;
+use sqlx::{Acquire, MySql, Transaction};
+
+use crate::database::store::{BattlelogStore, StoreTransaction};
+
+use super::{
+    backfill_cursors::BackfillCursor,
+    battlereports::BattlelogBattlereport,
+    clans::{ClanActivity, ClanStats},
+    exports::{ExportFilter, ExportRow},
+    context::BattlelogContext,
+    loadout_snapshots::LoadoutSnapshot,
+    persona_game_expansion::{GameExpansionOwnership, PersonaGameExpansion, PersonaGameExpansionDetails, ServerGameExpansionShare},
+    persona_info::PersonaInfo,
+    persona_name_history::PersonaNameHistory,
+    personas::{BattlelogPersona, PersonaSearchResult},
+    playerreports::{BattlelogPlayerreport, PersonaRound, PersonaStats},
+    report_archives::{BattlereportArchive, PlayerreportArchive},
+    response_cache::CachedResponseRow,
+    servers::BattlelogServer,
+};
+
+#[async_trait]
+impl BattlelogStore for BattlelogContext {
+    async fn begin(&self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let transaction = self.begin_transaction().await?;
+        Ok(Box::new(MySqlTransaction { db: self, transaction }))
+    }
+
+    async fn close(&self) {
+        BattlelogContext::close(self).await
+    }
+
+    async fn get_persona_by_persona_id(&self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        BattlelogContext::get_persona_by_persona_id(self, persona_id).await
+    }
+
+    async fn get_persona_by_name(&self, name: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        BattlelogContext::get_persona_by_name(self, name).await
+    }
+
+    async fn get_personas_without_update(&self) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        BattlelogContext::get_personas_without_update(self).await
+    }
+
+    async fn search_personas(&self, name: Option<&str>, clan_tag: Option<&str>, limit: u32) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        BattlelogContext::search_personas(self, name, clan_tag, limit).await
+    }
+
+    async fn get_personas_for_refresh(&self, active_since: u32, stale_before: u32, limit: u32) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        BattlelogContext::get_personas_for_refresh(self, active_since, stale_before, limit).await
+    }
+
+    async fn insert_persona(&self, persona: &BattlelogPersona) -> anyhow::Result<u64> {
+        BattlelogContext::insert_persona(self, persona).await
+    }
+
+    async fn get_battlereport_by_report_id(&self, report_id: u64) -> Result<Option<BattlelogBattlereport>, sqlx::Error> {
+        BattlelogContext::get_battlereport_by_report_id(self, report_id).await
+    }
+
+    async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        BattlelogContext::get_battlereport_ids(self, from, to, server_id).await
+    }
+
+    async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        BattlelogContext::insert_battlereport(self, battlereport).await
+    }
+
+    async fn update_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        BattlelogContext::update_battlereport(self, battlereport).await
+    }
+
+    async fn get_server_by_server_id(&self, server_id: i32) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        BattlelogContext::get_server_by_server_id(self, server_id).await
+    }
+
+    async fn get_server_by_server_guid(&self, guid: &str) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        BattlelogContext::get_server_by_server_guid(self, guid).await
+    }
+
+    async fn insert_server(&self, server: &BattlelogServer) -> anyhow::Result<u64> {
+        BattlelogContext::insert_server(self, server).await
+    }
+
+    async fn update_server(&self, server: &BattlelogServer) -> anyhow::Result<bool> {
+        BattlelogContext::update_server(self, server).await
+    }
+
+    async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        BattlelogContext::get_playerreport_by_report_id_and_persona_id(self, report_id, persona_id).await
+    }
+
+    async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        BattlelogContext::get_playerreports_by_report_id(self, report_id).await
+    }
+
+    async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        BattlelogContext::get_persona_recent_rounds(self, persona_id, limit).await
+    }
+
+    async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        BattlelogContext::get_persona_stats(self, persona_id).await
+    }
+
+    async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        BattlelogContext::get_co_player_persona_ids(self, persona_id).await
+    }
+
+    async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        BattlelogContext::insert_playerreport(self, playerreport).await
+    }
+
+    async fn get_persona_name_history(&self, persona_id: u64) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        BattlelogContext::get_persona_name_history(self, persona_id).await
+    }
+
+    async fn get_persona_name_history_by_name(&self, name: &str) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        BattlelogContext::get_persona_name_history_by_name(self, name).await
+    }
+
+    async fn get_persona_info_by_id(&self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        BattlelogContext::get_persona_info_by_id(self, persona_id).await
+    }
+
+    async fn get_persona_game_expansion_details(&self, persona_id: u64) -> Result<Vec<PersonaGameExpansionDetails>, sqlx::Error> {
+        BattlelogContext::get_persona_game_expansion_details(self, persona_id).await
+    }
+
+    async fn get_game_expansion_ownership(&self) -> Result<Vec<GameExpansionOwnership>, sqlx::Error> {
+        BattlelogContext::get_game_expansion_ownership(self).await
+    }
+
+    async fn get_server_game_expansion_share(&self, server_id: i32, since: u32) -> Result<ServerGameExpansionShare, sqlx::Error> {
+        BattlelogContext::get_server_game_expansion_share(self, server_id, since).await
+    }
+
+    async fn get_clan_roster(&self, clan_tag: &str) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        BattlelogContext::get_clan_roster(self, clan_tag).await
+    }
+
+    async fn get_clan_stats(&self, clan_tag: &str, server_id: Option<i32>) -> Result<ClanStats, sqlx::Error> {
+        BattlelogContext::get_clan_stats(self, clan_tag, server_id).await
+    }
+
+    async fn get_clan_activity(&self, clan_tag: &str, since: u32) -> Result<Vec<ClanActivity>, sqlx::Error> {
+        BattlelogContext::get_clan_activity(self, clan_tag, since).await
+    }
+
+    async fn get_clan_leaderboard(&self, server_id: i32, since: u32, min_rounds: u32) -> Result<Vec<ClanStats>, sqlx::Error> {
+        BattlelogContext::get_clan_leaderboard(self, server_id, since, min_rounds).await
+    }
+
+    async fn get_latest_loadout_snapshot(&self, persona_id: u64) -> Result<Option<LoadoutSnapshot>, sqlx::Error> {
+        BattlelogContext::get_latest_loadout_snapshot(self, persona_id).await
+    }
+
+    async fn get_loadout_snapshots(&self, persona_id: u64, limit: u32) -> Result<Vec<LoadoutSnapshot>, sqlx::Error> {
+        BattlelogContext::get_loadout_snapshots(self, persona_id, limit).await
+    }
+
+    async fn insert_loadout_snapshot(&self, snapshot: &LoadoutSnapshot) -> anyhow::Result<u64> {
+        BattlelogContext::insert_loadout_snapshot(self, snapshot).await
+    }
+
+    async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        BattlelogContext::get_cached_response(self, cache_key).await
+    }
+
+    async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        BattlelogContext::upsert_cached_response(self, row).await
+    }
+
+    async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        BattlelogContext::delete_expired_cached_responses(self, now).await
+    }
+
+    async fn get_battlereport_archive(&self, report_id: u64) -> Result<Option<BattlereportArchive>, sqlx::Error> {
+        BattlelogContext::get_battlereport_archive(self, report_id).await
+    }
+
+    async fn get_playerreport_archives(&self, report_id: u64) -> Result<Vec<PlayerreportArchive>, sqlx::Error> {
+        BattlelogContext::get_playerreport_archives(self, report_id).await
+    }
+
+    async fn get_battlereport_archive_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        BattlelogContext::get_battlereport_archive_ids(self, from, to, server_id).await
+    }
+
+    async fn get_export_rows(&self, filter: &ExportFilter, after: (u64, u64), limit: u32) -> Result<Vec<ExportRow>, sqlx::Error> {
+        BattlelogContext::get_export_rows(self, filter, after, limit).await
+    }
+
+    async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        BattlelogContext::get_backfill_cursor(self, persona_id).await
+    }
+
+    async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        BattlelogContext::upsert_backfill_cursor(self, cursor).await
+    }
+}
+
+pub struct MySqlTransaction<'c> {
+    db: &'c BattlelogContext,
+    transaction: Transaction<'c, MySql>,
+}
+
+#[async_trait]
+impl<'c> StoreTransaction for MySqlTransaction<'c> {
+    async fn savepoint(&mut self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let transaction = self.transaction.begin().await?;
+        Ok(Box::new(MySqlTransaction { db: self.db, transaction }))
+    }
+
+    async fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.transaction.commit().await?)
+    }
+
+    async fn rollback(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.transaction.rollback().await?)
+    }
+
+    async fn get_persona_by_persona_id(&mut self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        self.db.get_persona_by_persona_id_with_transaction(&mut self.transaction, persona_id).await
+    }
+
+    async fn get_persona_info_by_id(&mut self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        self.db.get_persona_info_by_id_with_transaction(&mut self.transaction, persona_id).await
+    }
+
+    async fn upsert_persona(&mut self, persona: &BattlelogPersona, seen_at: u32) -> anyhow::Result<u64> {
+        self.db.upsert_persona(&mut self.transaction, persona, seen_at).await
+    }
+
+    async fn update_persona(&mut self, persona: &BattlelogPersona) -> anyhow::Result<bool> {
+        self.db.update_persona_with_transaction(&mut self.transaction, persona).await
+    }
+
+    async fn update_persona_last_updated(&mut self, persona_id: u64, last_updated: u32) -> anyhow::Result<bool> {
+        self.db.update_persona_last_updated(&mut self.transaction, persona_id, last_updated).await
+    }
+
+    async fn upsert_persona_name_history(&mut self, p: &PersonaNameHistory) -> anyhow::Result<u64> {
+        self.db.upsert_persona_name_history(&mut self.transaction, p).await
+    }
+
+    async fn upsert_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<u64> {
+        self.db.upsert_persona_info(&mut self.transaction, p).await
+    }
+
+    async fn update_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<bool> {
+        self.db.update_persona_info_with_transaction(&mut self.transaction, p).await
+    }
+
+    async fn upsert_persona_game_expansion(&mut self, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
+        self.db.upsert_persona_game_expansion(&mut self.transaction, p).await
+    }
+
+    async fn upsert_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        self.db.upsert_battlereport(&mut self.transaction, battlereport).await
+    }
+
+    async fn replace_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        self.db.replace_battlereport(&mut self.transaction, battlereport).await
+    }
+
+    async fn upsert_playerreport(&mut self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        self.db.upsert_playerreport(&mut self.transaction, playerreport).await
+    }
+
+    async fn upsert_battlereport_archive(&mut self, archive: &BattlereportArchive) -> anyhow::Result<bool> {
+        self.db.upsert_battlereport_archive(&mut self.transaction, archive).await
+    }
+
+    async fn upsert_playerreport_archive(&mut self, archive: &PlayerreportArchive) -> anyhow::Result<bool> {
+        self.db.upsert_playerreport_archive(&mut self.transaction, archive).await
+    }
+}
@@ -0,0 +1,295 @@
+use std::future::Future;
+
+use async_trait::async_trait;
+
+use crate::metrics::DB_QUERY_DURATION;
+
+use super::battlelog::{
+    backfill_cursors::BackfillCursor,
+    battlereports::BattlelogBattlereport,
+    clans::{ClanActivity, ClanStats},
+    exports::{ExportFilter, ExportRow},
+    loadout_snapshots::LoadoutSnapshot,
+    persona_game_expansion::{GameExpansionOwnership, PersonaGameExpansion, PersonaGameExpansionDetails, ServerGameExpansionShare},
+    persona_info::PersonaInfo,
+    persona_name_history::PersonaNameHistory,
+    personas::{BattlelogPersona, PersonaSearchResult},
+    playerreports::{BattlelogPlayerreport, PersonaRound, PersonaStats},
+    report_archives::{BattlereportArchive, PlayerreportArchive},
+    response_cache::CachedResponseRow,
+    servers::BattlelogServer,
+};
+use super::store::{BattlelogStore, StoreTransaction};
+
+/// Wraps a store to observe the latency of every call in `DB_QUERY_DURATION`, labelled by the
+/// store method. The transactions it begins are wrapped the same way.
+pub struct MeteredStore<S> {
+    inner: S,
+}
+
+impl<S: BattlelogStore> MeteredStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+struct MeteredTransaction<'a> {
+    inner: Box<dyn StoreTransaction + 'a>,
+}
+
+async fn timed<T>(query: &'static str, future: impl Future<Output = T>) -> T {
+    let _timer = DB_QUERY_DURATION.with_label_values(&[query]).start_timer();
+    future.await
+}
+
+#[async_trait]
+impl<S: BattlelogStore> BattlelogStore for MeteredStore<S> {
+    async fn begin(&self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let inner = timed("begin", self.inner.begin()).await?;
+        Ok(Box::new(MeteredTransaction { inner }))
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    async fn get_persona_by_persona_id(&self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        timed("get_persona_by_persona_id", self.inner.get_persona_by_persona_id(persona_id)).await
+    }
+
+    async fn get_persona_by_name(&self, name: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        timed("get_persona_by_name", self.inner.get_persona_by_name(name)).await
+    }
+
+    async fn get_personas_without_update(&self) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        timed("get_personas_without_update", self.inner.get_personas_without_update()).await
+    }
+
+    async fn search_personas(&self, name: Option<&str>, clan_tag: Option<&str>, limit: u32) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        timed("search_personas", self.inner.search_personas(name, clan_tag, limit)).await
+    }
+
+    async fn get_personas_for_refresh(&self, active_since: u32, stale_before: u32, limit: u32) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        timed("get_personas_for_refresh", self.inner.get_personas_for_refresh(active_since, stale_before, limit)).await
+    }
+
+    async fn insert_persona(&self, persona: &BattlelogPersona) -> anyhow::Result<u64> {
+        timed("insert_persona", self.inner.insert_persona(persona)).await
+    }
+
+    async fn get_battlereport_by_report_id(&self, report_id: u64) -> Result<Option<BattlelogBattlereport>, sqlx::Error> {
+        timed("get_battlereport_by_report_id", self.inner.get_battlereport_by_report_id(report_id)).await
+    }
+
+    async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        timed("get_battlereport_ids", self.inner.get_battlereport_ids(from, to, server_id)).await
+    }
+
+    async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        timed("insert_battlereport", self.inner.insert_battlereport(battlereport)).await
+    }
+
+    async fn update_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        timed("update_battlereport", self.inner.update_battlereport(battlereport)).await
+    }
+
+    async fn get_server_by_server_id(&self, server_id: i32) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        timed("get_server_by_server_id", self.inner.get_server_by_server_id(server_id)).await
+    }
+
+    async fn get_server_by_server_guid(&self, guid: &str) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        timed("get_server_by_server_guid", self.inner.get_server_by_server_guid(guid)).await
+    }
+
+    async fn insert_server(&self, server: &BattlelogServer) -> anyhow::Result<u64> {
+        timed("insert_server", self.inner.insert_server(server)).await
+    }
+
+    async fn update_server(&self, server: &BattlelogServer) -> anyhow::Result<bool> {
+        timed("update_server", self.inner.update_server(server)).await
+    }
+
+    async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        timed("get_playerreport_by_report_id_and_persona_id", self.inner.get_playerreport_by_report_id_and_persona_id(report_id, persona_id)).await
+    }
+
+    async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        timed("get_playerreports_by_report_id", self.inner.get_playerreports_by_report_id(report_id)).await
+    }
+
+    async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        timed("get_persona_recent_rounds", self.inner.get_persona_recent_rounds(persona_id, limit)).await
+    }
+
+    async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        timed("get_persona_stats", self.inner.get_persona_stats(persona_id)).await
+    }
+
+    async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        timed("get_co_player_persona_ids", self.inner.get_co_player_persona_ids(persona_id)).await
+    }
+
+    async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        timed("insert_playerreport", self.inner.insert_playerreport(playerreport)).await
+    }
+
+    async fn get_persona_name_history(&self, persona_id: u64) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        timed("get_persona_name_history", self.inner.get_persona_name_history(persona_id)).await
+    }
+
+    async fn get_persona_name_history_by_name(&self, name: &str) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        timed("get_persona_name_history_by_name", self.inner.get_persona_name_history_by_name(name)).await
+    }
+
+    async fn get_persona_info_by_id(&self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        timed("get_persona_info_by_id", self.inner.get_persona_info_by_id(persona_id)).await
+    }
+
+    async fn get_persona_game_expansion_details(&self, persona_id: u64) -> Result<Vec<PersonaGameExpansionDetails>, sqlx::Error> {
+        timed("get_persona_game_expansion_details", self.inner.get_persona_game_expansion_details(persona_id)).await
+    }
+
+    async fn get_game_expansion_ownership(&self) -> Result<Vec<GameExpansionOwnership>, sqlx::Error> {
+        timed("get_game_expansion_ownership", self.inner.get_game_expansion_ownership()).await
+    }
+
+    async fn get_server_game_expansion_share(&self, server_id: i32, since: u32) -> Result<ServerGameExpansionShare, sqlx::Error> {
+        timed("get_server_game_expansion_share", self.inner.get_server_game_expansion_share(server_id, since)).await
+    }
+
+    async fn get_clan_roster(&self, clan_tag: &str) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        timed("get_clan_roster", self.inner.get_clan_roster(clan_tag)).await
+    }
+
+    async fn get_clan_stats(&self, clan_tag: &str, server_id: Option<i32>) -> Result<ClanStats, sqlx::Error> {
+        timed("get_clan_stats", self.inner.get_clan_stats(clan_tag, server_id)).await
+    }
+
+    async fn get_clan_activity(&self, clan_tag: &str, since: u32) -> Result<Vec<ClanActivity>, sqlx::Error> {
+        timed("get_clan_activity", self.inner.get_clan_activity(clan_tag, since)).await
+    }
+
+    async fn get_clan_leaderboard(&self, server_id: i32, since: u32, min_rounds: u32) -> Result<Vec<ClanStats>, sqlx::Error> {
+        timed("get_clan_leaderboard", self.inner.get_clan_leaderboard(server_id, since, min_rounds)).await
+    }
+
+    async fn get_latest_loadout_snapshot(&self, persona_id: u64) -> Result<Option<LoadoutSnapshot>, sqlx::Error> {
+        timed("get_latest_loadout_snapshot", self.inner.get_latest_loadout_snapshot(persona_id)).await
+    }
+
+    async fn get_loadout_snapshots(&self, persona_id: u64, limit: u32) -> Result<Vec<LoadoutSnapshot>, sqlx::Error> {
+        timed("get_loadout_snapshots", self.inner.get_loadout_snapshots(persona_id, limit)).await
+    }
+
+    async fn insert_loadout_snapshot(&self, snapshot: &LoadoutSnapshot) -> anyhow::Result<u64> {
+        timed("insert_loadout_snapshot", self.inner.insert_loadout_snapshot(snapshot)).await
+    }
+
+    async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        timed("get_cached_response", self.inner.get_cached_response(cache_key)).await
+    }
+
+    async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        timed("upsert_cached_response", self.inner.upsert_cached_response(row)).await
+    }
+
+    async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        timed("delete_expired_cached_responses", self.inner.delete_expired_cached_responses(now)).await
+    }
+
+    async fn get_battlereport_archive(&self, report_id: u64) -> Result<Option<BattlereportArchive>, sqlx::Error> {
+        timed("get_battlereport_archive", self.inner.get_battlereport_archive(report_id)).await
+    }
+
+    async fn get_playerreport_archives(&self, report_id: u64) -> Result<Vec<PlayerreportArchive>, sqlx::Error> {
+        timed("get_playerreport_archives", self.inner.get_playerreport_archives(report_id)).await
+    }
+
+    async fn get_battlereport_archive_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        timed("get_battlereport_archive_ids", self.inner.get_battlereport_archive_ids(from, to, server_id)).await
+    }
+
+    async fn get_export_rows(&self, filter: &ExportFilter, after: (u64, u64), limit: u32) -> Result<Vec<ExportRow>, sqlx::Error> {
+        timed("get_export_rows", self.inner.get_export_rows(filter, after, limit)).await
+    }
+
+    async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        timed("get_backfill_cursor", self.inner.get_backfill_cursor(persona_id)).await
+    }
+
+    async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        timed("upsert_backfill_cursor", self.inner.upsert_backfill_cursor(cursor)).await
+    }
+}
+
+#[async_trait]
+impl StoreTransaction for MeteredTransaction<'_> {
+    async fn savepoint(&mut self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let inner = timed("savepoint", self.inner.savepoint()).await?;
+        Ok(Box::new(MeteredTransaction { inner }))
+    }
+
+    async fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        timed("commit", self.inner.commit()).await
+    }
+
+    async fn rollback(self: Box<Self>) -> anyhow::Result<()> {
+        timed("rollback", self.inner.rollback()).await
+    }
+
+    async fn get_persona_by_persona_id(&mut self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        timed("get_persona_by_persona_id", self.inner.get_persona_by_persona_id(persona_id)).await
+    }
+
+    async fn get_persona_info_by_id(&mut self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        timed("get_persona_info_by_id", self.inner.get_persona_info_by_id(persona_id)).await
+    }
+
+    async fn upsert_persona(&mut self, persona: &BattlelogPersona, seen_at: u32) -> anyhow::Result<u64> {
+        timed("upsert_persona", self.inner.upsert_persona(persona, seen_at)).await
+    }
+
+    async fn update_persona(&mut self, persona: &BattlelogPersona) -> anyhow::Result<bool> {
+        timed("update_persona", self.inner.update_persona(persona)).await
+    }
+
+    async fn update_persona_last_updated(&mut self, persona_id: u64, last_updated: u32) -> anyhow::Result<bool> {
+        timed("update_persona_last_updated", self.inner.update_persona_last_updated(persona_id, last_updated)).await
+    }
+
+    async fn upsert_persona_name_history(&mut self, p: &PersonaNameHistory) -> anyhow::Result<u64> {
+        timed("upsert_persona_name_history", self.inner.upsert_persona_name_history(p)).await
+    }
+
+    async fn upsert_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<u64> {
+        timed("upsert_persona_info", self.inner.upsert_persona_info(p)).await
+    }
+
+    async fn update_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<bool> {
+        timed("update_persona_info", self.inner.update_persona_info(p)).await
+    }
+
+    async fn upsert_persona_game_expansion(&mut self, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
+        timed("upsert_persona_game_expansion", self.inner.upsert_persona_game_expansion(p)).await
+    }
+
+    async fn upsert_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        timed("upsert_battlereport", self.inner.upsert_battlereport(battlereport)).await
+    }
+
+    async fn replace_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        timed("replace_battlereport", self.inner.replace_battlereport(battlereport)).await
+    }
+
+    async fn upsert_playerreport(&mut self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        timed("upsert_playerreport", self.inner.upsert_playerreport(playerreport)).await
+    }
+
+    async fn upsert_battlereport_archive(&mut self, archive: &BattlereportArchive) -> anyhow::Result<bool> {
+        timed("upsert_battlereport_archive", self.inner.upsert_battlereport_archive(archive)).await
+    }
+
+    async fn upsert_playerreport_archive(&mut self, archive: &PlayerreportArchive) -> anyhow::Result<bool> {
+        timed("upsert_playerreport_archive", self.inner.upsert_playerreport_archive(archive)).await
+    }
+}
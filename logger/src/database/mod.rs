@@ -1 +1,5 @@
-pub mod battlelog;
\ No newline at end of file
+pub mod battlelog;
+pub mod metered;
+pub mod postgres;
+pub mod sqlite;
+pub mod store;
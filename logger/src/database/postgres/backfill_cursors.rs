@@ -0,0 +1,59 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::backfill_cursors::BackfillCursor;
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub persona_id: i64,
+    pub timestamp: i64,
+    pub cutoff: i64,
+    pub reports_ingested: i64,
+    pub reports_failed: i64,
+    pub completed: bool,
+    pub updated_at: i64,
+}
+
+impl From<Row> for BackfillCursor {
+    fn from(e: Row) -> Self {
+        BackfillCursor {
+            persona_id: e.persona_id as u64,
+            timestamp: e.timestamp as u32,
+            cutoff: e.cutoff as u32,
+            reports_ingested: e.reports_ingested as u32,
+            reports_failed: e.reports_failed as u32,
+            completed: e.completed,
+            updated_at: e.updated_at as u32,
+        }
+    }
+}
+
+impl PostgresContext {
+    pub async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from backfill_cursors WHERE persona_id = $1")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BackfillCursor::from))
+    }
+
+    pub async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO backfill_cursors (persona_id, timestamp, cutoff, reports_ingested, reports_failed, completed, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (persona_id) DO UPDATE SET timestamp = EXCLUDED.timestamp, cutoff = EXCLUDED.cutoff, reports_ingested = EXCLUDED.reports_ingested, reports_failed = EXCLUDED.reports_failed, completed = EXCLUDED.completed, updated_at = EXCLUDED.updated_at"#)
+            .bind(cursor.persona_id as i64)
+            .bind(cursor.timestamp as i64)
+            .bind(cursor.cutoff as i64)
+            .bind(cursor.reports_ingested as i64)
+            .bind(cursor.reports_failed as i64)
+            .bind(cursor.completed)
+            .bind(cursor.updated_at as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
@@ -0,0 +1,145 @@
+use sqlx::{query, query_as, FromRow, Postgres, Transaction};
+
+use crate::database::battlelog::battlereports::BattlelogBattlereport;
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub id: i64,
+    pub duration: i64,
+    pub winner: i16,
+    pub server_id: i32,
+    pub map: String,
+    pub mode: String,
+    pub created_at: i64,
+    pub processed: i16,
+}
+
+impl From<Row> for BattlelogBattlereport {
+    fn from(e: Row) -> Self {
+        BattlelogBattlereport {
+            id: e.id as u64,
+            duration: e.duration as u32,
+            winner: e.winner as i8,
+            server_id: e.server_id,
+            map: e.map,
+            mode: e.mode,
+            created_at: e.created_at as u32,
+            processed: e.processed as u8,
+        }
+    }
+}
+
+impl PostgresContext {
+    pub async fn get_battlereport_by_report_id(&self, report_id: u64) -> Result<Option<BattlelogBattlereport>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from battlereports WHERE id = $1")
+            .bind(report_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogBattlereport::from))
+    }
+
+    /// Ids of the stored battlereports, see `BattlelogContext::get_battlereport_ids`.
+    pub async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        let res: Vec<(i64,)> =
+            query_as(r#"SELECT id
+                FROM battlereports
+                WHERE ($1::BIGINT IS NULL OR created_at >= $1)
+                    AND ($2::BIGINT IS NULL OR created_at < $2)
+                    AND ($3::INTEGER IS NULL OR server_id = $3)
+                ORDER BY id"#)
+            .bind(from.map(|v| v as i64))
+            .bind(to.map(|v| v as i64))
+            .bind(server_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|e| e.0 as u64).collect())
+    }
+
+    pub async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration as i64)
+            .bind(battlereport.winner as i16)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the battlereport or refreshes the existing row, see `BattlelogContext::upsert_battlereport`.
+    pub async fn upsert_battlereport(&self, transaction: &mut Transaction<'_, Postgres>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE SET
+                duration = GREATEST(battlereports.duration, EXCLUDED.duration),
+                winner = CASE WHEN EXCLUDED.winner >= 0 THEN EXCLUDED.winner ELSE battlereports.winner END,
+                server_id = EXCLUDED.server_id,
+                map = CASE WHEN EXCLUDED.map <> '' THEN EXCLUDED.map ELSE battlereports.map END,
+                mode = CASE WHEN EXCLUDED.mode <> '' THEN EXCLUDED.mode ELSE battlereports.mode END,
+                processed = GREATEST(battlereports.processed, EXCLUDED.processed)"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration as i64)
+            .bind(battlereport.winner as i16)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at as i64)
+            .bind(battlereport.processed as i16)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the battlereport or overwrites the stored columns, see `BattlelogContext::replace_battlereport`.
+    pub async fn replace_battlereport(&self, transaction: &mut Transaction<'_, Postgres>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE SET
+                duration = EXCLUDED.duration,
+                winner = EXCLUDED.winner,
+                server_id = EXCLUDED.server_id,
+                map = EXCLUDED.map,
+                mode = EXCLUDED.mode,
+                created_at = EXCLUDED.created_at"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration as i64)
+            .bind(battlereport.winner as i16)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at as i64)
+            .bind(battlereport.processed as i16)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn update_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE battlereports SET duration = $1, winner = $2, server_id = $3, map = $4, mode = $5, created_at = $6, processed = $7 WHERE id = $8"#)
+            .bind(battlereport.duration as i64)
+            .bind(battlereport.winner as i16)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at as i64)
+            .bind(battlereport.processed as i16)
+            .bind(battlereport.id as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
@@ -0,0 +1,32 @@
+use std::str::FromStr;
+
+use sqlx::{PgPool, Postgres, Error, Transaction, postgres::{PgConnectOptions, PgPoolOptions}, ConnectOptions};
+
+/// PostgreSQL counterpart of `BattlelogContext`. Postgres has no unsigned integers, so the ids are
+/// stored as `BIGINT` and the unsigned 32-bit values as `BIGINT` to keep their full range.
+#[derive(Clone, Debug)]
+pub struct PostgresContext {
+    pub pool: PgPool,
+}
+
+impl PostgresContext {
+    /// Connects and applies the pending migrations from `migrations_postgres`.
+    pub async fn connect(url: impl AsRef<str>) -> Result<Self, sqlx::Error> {
+        let mut connection_options = PgConnectOptions::from_str(url.as_ref())?;
+        connection_options
+            .disable_statement_logging();
+        let pool = PgPoolOptions::new().connect_with(connection_options).await?;
+
+        sqlx::migrate!("./migrations_postgres").run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub async fn begin_transaction(&self) -> Result<Transaction<'_, Postgres>, Error> {
+        self.pool.begin().await
+    }
+}
@@ -0,0 +1,113 @@
+use sqlx::{query_as, FromRow};
+
+use crate::database::battlelog::exports::{ExportFilter, ExportRow};
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub report_id: i64,
+    pub created_at: i64,
+    pub duration: i64,
+    pub map: String,
+    pub mode: String,
+    pub winner: i16,
+    pub server_id: i32,
+    pub server_name: String,
+    pub server_guid: String,
+    pub persona_id: i64,
+    pub persona_name: Option<String>,
+    pub clan_tag: Option<String>,
+    pub team: i16,
+    pub squad_id: i16,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    pub kd_ratio: f32,
+    pub spm: i64,
+    pub skill: i32,
+    pub shots_hit: f32,
+    pub shots_fired: f32,
+    pub accuracy_detailed: f32,
+    pub heals: i32,
+    pub revives: i32,
+    pub kill_streak: i32,
+    pub vehicle_destroyed: i64,
+    pub total: i64,
+    pub dnf: bool,
+    pub is_commander: bool,
+    pub is_soldier: bool,
+}
+
+impl From<Row> for ExportRow {
+    fn from(e: Row) -> Self {
+        ExportRow {
+            report_id: e.report_id as u64,
+            created_at: e.created_at as u32,
+            duration: e.duration as u32,
+            map: e.map,
+            mode: e.mode,
+            winner: e.winner as i8,
+            server_id: e.server_id,
+            server_name: e.server_name,
+            server_guid: e.server_guid,
+            persona_id: e.persona_id as u64,
+            persona_name: e.persona_name,
+            clan_tag: e.clan_tag,
+            team: e.team as i8,
+            squad_id: e.squad_id as i8,
+            kills: e.kills as u32,
+            deaths: e.deaths as u32,
+            assists: e.assists as u32,
+            kd_ratio: e.kd_ratio,
+            spm: e.spm as u32,
+            skill: e.skill,
+            shots_hit: e.shots_hit,
+            shots_fired: e.shots_fired,
+            accuracy_detailed: e.accuracy_detailed,
+            heals: e.heals as u16,
+            revives: e.revives as u16,
+            kill_streak: e.kill_streak as u16,
+            vehicle_destroyed: e.vehicle_destroyed as u32,
+            total: e.total as u32,
+            dnf: e.dnf,
+            is_commander: e.is_commander,
+            is_soldier: e.is_soldier,
+        }
+    }
+}
+
+impl PostgresContext {
+    /// Export rows after `after`, see `BattlelogContext::get_export_rows`.
+    pub async fn get_export_rows(&self, filter: &ExportFilter, after: (u64, u64), limit: u32) -> Result<Vec<ExportRow>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as(r#"SELECT pr.report_id, b.created_at, b.duration, b.map, b.mode, b.winner,
+                    b.server_id, s.name AS server_name, s.guid AS server_guid,
+                    pr.persona_id, p.name AS persona_name, p.clan_tag,
+                    pr.team, pr.squad_id, pr.kills, pr.deaths, pr.assists, pr.kd_ratio, pr.spm, pr.skill,
+                    pr.shots_hit, pr.shots_fired, pr.accuracy_detailed, pr.heals, pr.revives, pr.kill_streak,
+                    pr.vehicle_destroyed, pr.total, pr.dnf, pr.is_commander, pr.is_soldier
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN servers s ON s.id = b.server_id
+                LEFT JOIN personas p ON p.id = pr.persona_id
+                WHERE (pr.report_id, pr.persona_id) > ($1, $2)
+                    AND ($3::INTEGER IS NULL OR b.server_id = $3)
+                    AND ($4::BIGINT IS NULL OR b.created_at >= $4)
+                    AND ($5::BIGINT IS NULL OR b.created_at < $5)
+                    AND ($6::TEXT IS NULL OR b.map = $6)
+                ORDER BY pr.report_id, pr.persona_id
+                LIMIT $7"#)
+            .bind(after.0 as i64)
+            .bind(after.1 as i64)
+            .bind(filter.server_id)
+            .bind(filter.from.map(|v| v as i64))
+            .bind(filter.to.map(|v| v as i64))
+            .bind(filter.map.as_deref())
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(ExportRow::from).collect())
+    }
+}
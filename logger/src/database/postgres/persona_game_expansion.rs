@@ -0,0 +1,134 @@
+use sqlx::{query, query_as, FromRow, Postgres, Transaction};
+
+use crate::database::battlelog::persona_game_expansion::{GameExpansionOwnership, GameExpansionShare, PersonaGameExpansion, PersonaGameExpansionDetails, ServerGameExpansionShare};
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct DetailsRow {
+    pub game_expansion_id: i64,
+    pub value: Option<String>,
+    pub explanation: Option<String>,
+    pub platforms: i64,
+}
+
+#[derive(FromRow)]
+struct OwnershipRow {
+    pub game_expansion_id: i64,
+    pub explanation: Option<String>,
+    pub platforms: i64,
+    pub owners: i64,
+}
+
+#[derive(FromRow)]
+struct PlayersRow {
+    pub active_players: i64,
+    pub checked_players: i64,
+}
+
+#[derive(FromRow)]
+struct OwnersRow {
+    pub game_expansion_id: i64,
+    pub explanation: Option<String>,
+    pub owners: i64,
+}
+
+impl PostgresContext {
+    pub async fn get_persona_game_expansion_details(&self, persona_id: u64) -> Result<Vec<PersonaGameExpansionDetails>, sqlx::Error> {
+        let mut res: Vec<DetailsRow> =
+            query_as(r#"SELECT pge.game_expansion_id, ge.value, ge.explanation, pge.platforms
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                WHERE pge.persona_id = $1
+                ORDER BY pge.game_expansion_id"#)
+            .bind(persona_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: DetailsRow| PersonaGameExpansionDetails {
+            game_expansion_id: e.game_expansion_id as u64,
+            value: e.value,
+            explanation: e.explanation,
+            platforms: e.platforms as u32,
+        }).collect())
+    }
+
+    pub async fn get_game_expansion_ownership(&self) -> Result<Vec<GameExpansionOwnership>, sqlx::Error> {
+        let mut res: Vec<OwnershipRow> =
+            query_as(r#"SELECT pge.game_expansion_id, ge.explanation, pge.platforms, COUNT(*) AS owners
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                GROUP BY pge.game_expansion_id, ge.explanation, pge.platforms
+                ORDER BY pge.game_expansion_id, pge.platforms"#)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: OwnershipRow| GameExpansionOwnership {
+            game_expansion_id: e.game_expansion_id as u64,
+            explanation: e.explanation,
+            platforms: e.platforms as u32,
+            owners: e.owners,
+        }).collect())
+    }
+
+    /// Expansion ownership among the personas who played on the server since `since`.
+    pub async fn get_server_game_expansion_share(&self, server_id: i32, since: u32) -> Result<ServerGameExpansionShare, sqlx::Error> {
+        let players: PlayersRow =
+            query_as(r#"SELECT COUNT(*) AS active_players,
+                    COUNT(p.last_updated) AS checked_players
+                FROM personas p
+                WHERE p.id IN (
+                    SELECT pr.persona_id FROM playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id
+                    WHERE b.server_id = $1 AND b.created_at >= $2
+                )"#)
+            .bind(server_id)
+            .bind(since as i64)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let mut owners: Vec<OwnersRow> =
+            query_as(r#"SELECT pge.game_expansion_id, ge.explanation, COUNT(DISTINCT pge.persona_id) AS owners
+                FROM persona_game_expansions pge
+                LEFT JOIN game_expansions ge ON ge.id = pge.game_expansion_id
+                WHERE pge.persona_id IN (
+                    SELECT pr.persona_id FROM playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id
+                    WHERE b.server_id = $1 AND b.created_at >= $2
+                )
+                GROUP BY pge.game_expansion_id, ge.explanation
+                ORDER BY 3 DESC"#)
+            .bind(server_id)
+            .bind(since as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let checked_players = players.checked_players;
+        let expansions = owners.drain(..).map(|e: OwnersRow| GameExpansionShare {
+            game_expansion_id: e.game_expansion_id as u64,
+            explanation: e.explanation,
+            owners: e.owners,
+            share: if checked_players > 0 { e.owners as f32 / checked_players as f32 } else { 0.0 },
+        }).collect();
+
+        Ok(ServerGameExpansionShare {
+            server_id,
+            active_players: players.active_players,
+            checked_players,
+            expansions,
+        })
+    }
+
+    pub async fn upsert_persona_game_expansion(&self, transaction: &mut Transaction<'_, Postgres>, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO persona_game_expansions (persona_id, game_expansion_id, platforms) VALUES ($1, $2, $3)
+            ON CONFLICT (persona_id, game_expansion_id) DO UPDATE SET platforms = EXCLUDED.platforms"#)
+            .bind(p.persona_id as i64)
+            .bind(p.game_expansion_id as i64)
+            .bind(p.platforms as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,87 @@
+use sqlx::{query, query_as, FromRow, Postgres, Transaction};
+
+use crate::database::battlelog::persona_info::PersonaInfo;
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub persona_id: i64,
+    pub locality: Option<String>,
+    pub location: Option<String>,
+    pub presentation: Option<String>,
+    pub login_counter: Option<i64>,
+    pub last_login: Option<i64>,
+}
+
+impl From<Row> for PersonaInfo {
+    fn from(e: Row) -> Self {
+        PersonaInfo {
+            persona_id: e.persona_id as u64,
+            locality: e.locality,
+            location: e.location,
+            presentation: e.presentation,
+            login_counter: e.login_counter.map(|v| v as u32),
+            last_login: e.last_login.map(|v| v as u32),
+        }
+    }
+}
+
+impl PostgresContext {
+    pub async fn get_persona_info_by_id(&self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from persona_infos WHERE persona_id = $1")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(PersonaInfo::from))
+    }
+
+    pub async fn get_persona_info_by_id_with_transaction(&self, transaction: &mut Transaction<'_, Postgres>, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from persona_infos WHERE persona_id = $1")
+            .bind(persona_id as i64)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        Ok(res.map(PersonaInfo::from))
+    }
+
+    /// Inserts the persona info or replaces the one the persona already has.
+    pub async fn upsert_persona_info(&self, transaction: &mut Transaction<'_, Postgres>, p: &PersonaInfo) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO persona_infos (persona_id, locality, location, presentation, login_counter, last_login) VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (persona_id) DO UPDATE SET
+                locality = EXCLUDED.locality,
+                location = EXCLUDED.location,
+                presentation = EXCLUDED.presentation,
+                login_counter = EXCLUDED.login_counter,
+                last_login = EXCLUDED.last_login"#)
+            .bind(p.persona_id as i64)
+            .bind(&p.locality)
+            .bind(&p.location)
+            .bind(&p.presentation)
+            .bind(p.login_counter.map(|v| v as i64))
+            .bind(p.last_login.map(|v| v as i64))
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+
+    pub async fn update_persona_info(&self, transaction: &mut Transaction<'_, Postgres>, p: &PersonaInfo) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE persona_infos SET locality = $1, location = $2, presentation = $3, login_counter = $4, last_login = $5 WHERE persona_id = $6"#)
+            .bind(&p.locality)
+            .bind(&p.location)
+            .bind(&p.presentation)
+            .bind(p.login_counter.map(|v| v as i64))
+            .bind(p.last_login.map(|v| v as i64))
+            .bind(p.persona_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
@@ -0,0 +1,207 @@
+use sqlx::{query, query_as, FromRow, Postgres, Transaction};
+
+use crate::database::battlelog::personas::{like_prefix, BattlelogPersona, PersonaSearchResult};
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub id: i64,
+    pub name: Option<String>,
+    pub clan_tag: Option<String>,
+    pub gravatar_md5: Option<String>,
+    pub processed: bool,
+    pub last_updated: Option<i64>,
+}
+
+impl From<Row> for BattlelogPersona {
+    fn from(e: Row) -> Self {
+        BattlelogPersona {
+            id: e.id as u64,
+            name: e.name,
+            clan_tag: e.clan_tag,
+            gravatar_md5: e.gravatar_md5,
+            processed: e.processed,
+            last_updated: e.last_updated.map(|v| v as u32),
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub(super) struct SearchRow {
+    pub id: i64,
+    pub name: Option<String>,
+    pub clan_tag: Option<String>,
+    pub gravatar_md5: Option<String>,
+    pub last_seen: Option<i64>,
+    pub rounds: i64,
+}
+
+impl From<SearchRow> for PersonaSearchResult {
+    fn from(e: SearchRow) -> Self {
+        PersonaSearchResult {
+            id: e.id as u64,
+            name: e.name,
+            clan_tag: e.clan_tag,
+            gravatar_md5: e.gravatar_md5,
+            last_seen: e.last_seen.map(|v| v as u32),
+            rounds: e.rounds,
+        }
+    }
+}
+
+impl PostgresContext {
+    pub async fn get_persona_by_persona_id(&self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE id = $1")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogPersona::from))
+    }
+
+    pub async fn get_persona_by_persona_id_with_transaction(&self, transaction: &mut Transaction<'_, Postgres>, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE id = $1")
+            .bind(persona_id as i64)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        Ok(res.map(BattlelogPersona::from))
+    }
+
+    /// Persona currently using the name, the most recently updated one if the name has changed hands.
+    pub async fn get_persona_by_name(&self, name: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE name = $1 ORDER BY last_updated DESC NULLS LAST LIMIT 1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogPersona::from))
+    }
+
+    pub async fn get_personas_without_update(&self) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT id, name, clan_tag, gravatar_md5, processed, last_updated FROM personas WHERE last_updated IS NULL")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(BattlelogPersona::from).collect())
+    }
+
+    /// Searches personas by a prefix of their current or any historical name and clan tag.
+    pub async fn search_personas(&self, name: Option<&str>, clan_tag: Option<&str>, limit: u32) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        let name_pattern = like_prefix(name.unwrap_or(""));
+        let clan_pattern = clan_tag.map(like_prefix);
+
+        let mut res: Vec<SearchRow> =
+            query_as(r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5,
+                    MAX(b.created_at) AS last_seen,
+                    COUNT(b.id) AS rounds
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.id IN (
+                    SELECT persona_id FROM persona_name_history
+                        WHERE LOWER(name) LIKE $1 AND ($2::TEXT IS NULL OR LOWER(clan_tag) LIKE $2)
+                    UNION
+                    SELECT id FROM personas
+                        WHERE LOWER(COALESCE(name, '')) LIKE $1 AND ($2::TEXT IS NULL OR LOWER(clan_tag) LIKE $2)
+                )
+                GROUP BY p.id
+                ORDER BY last_seen DESC NULLS LAST
+                LIMIT $3"#)
+            .bind(name_pattern)
+            .bind(clan_pattern)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaSearchResult::from).collect())
+    }
+
+    /// Personas not updated since `stale_before`, the ones seen on our servers since `active_since`
+    /// first and then the ones with the oldest data.
+    pub async fn get_personas_for_refresh(&self, active_since: u32, stale_before: u32, limit: u32) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as(r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5, p.processed, p.last_updated
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.last_updated IS NULL OR p.last_updated < $1
+                GROUP BY p.id
+                ORDER BY COALESCE(MAX(b.created_at), 0) >= $2 DESC, p.last_updated IS NOT NULL, p.last_updated
+                LIMIT $3"#)
+            .bind(stale_before as i64)
+            .bind(active_since as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(BattlelogPersona::from).collect())
+    }
+
+    pub async fn insert_persona(&self, persona: &BattlelogPersona) -> anyhow::Result<u64> {
+        let res: (i64,) = query_as(r#"INSERT INTO personas (id, name, clan_tag, gravatar_md5, processed) VALUES ($1, $2, $3, $4, $5) RETURNING id"#)
+            .bind(persona.id as i64)
+            .bind(&persona.name)
+            .bind(&persona.clan_tag)
+            .bind(&persona.gravatar_md5)
+            .bind(persona.processed)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(res.0 as u64)
+    }
+
+    /// Inserts the persona or merges it into the existing row, see `BattlelogContext::upsert_persona`.
+    pub async fn upsert_persona(&self, transaction: &mut Transaction<'_, Postgres>, persona: &BattlelogPersona, seen_at: u32) -> anyhow::Result<u64> {
+        let clan_tag_seen_at = if persona.processed { Some(seen_at as i64) } else { None };
+        let res = query(r#"INSERT INTO personas (id, name, clan_tag, gravatar_md5, processed, clan_tag_seen_at) VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET
+                name = COALESCE(EXCLUDED.name, personas.name),
+                clan_tag = CASE WHEN EXCLUDED.clan_tag_seen_at >= COALESCE(personas.clan_tag_seen_at, 0) THEN EXCLUDED.clan_tag ELSE personas.clan_tag END,
+                clan_tag_seen_at = CASE WHEN EXCLUDED.clan_tag_seen_at >= COALESCE(personas.clan_tag_seen_at, 0) THEN EXCLUDED.clan_tag_seen_at ELSE personas.clan_tag_seen_at END,
+                gravatar_md5 = COALESCE(EXCLUDED.gravatar_md5, personas.gravatar_md5),
+                processed = personas.processed OR EXCLUDED.processed"#)
+            .bind(persona.id as i64)
+            .bind(&persona.name)
+            .bind(&persona.clan_tag)
+            .bind(&persona.gravatar_md5)
+            .bind(persona.processed)
+            .bind(clan_tag_seen_at)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+
+    pub async fn update_persona(&self, transaction: &mut Transaction<'_, Postgres>, persona: &BattlelogPersona) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE personas SET name = $1, clan_tag = $2, gravatar_md5 = $3, processed = $4, last_updated = $5 WHERE id = $6"#)
+            .bind(&persona.name)
+            .bind(&persona.clan_tag)
+            .bind(&persona.gravatar_md5)
+            .bind(persona.processed)
+            .bind(persona.last_updated.map(|v| v as i64))
+            .bind(persona.id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn update_persona_last_updated(&self, transaction: &mut Transaction<'_, Postgres>, persona_id: u64, last_updated: u32) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE personas SET last_updated = $1 WHERE id = $2"#)
+            .bind(last_updated as i64)
+            .bind(persona_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
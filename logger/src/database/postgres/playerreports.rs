@@ -0,0 +1,298 @@
+use sqlx::{query, query_as, postgres::PgArguments, FromRow, Postgres, Transaction};
+use sqlx::query::Query;
+
+use crate::database::battlelog::playerreports::{BattlelogPlayerreport, PersonaRound, PersonaStats};
+
+use super::context::PostgresContext;
+
+const INSERT_PLAYERREPORT: &str = r#"INSERT INTO playerreports (report_id, persona_id, kills, deaths, shots_hit, shots_fired, vehicle_destroyed, assists, spm, kd_ratio, skill, vehicle_assists, accuracy, sc_unlock, sc_bomber, sc_vehiclesh, sc_vehicleajet, sc_engineer, sc_commander, sc_assault, vehicle, sc_vehicleaa, sc_award, sc_vehicleifv, sc_recon, sc_vehicleah, sc_support, sc_vehiclesjet, total, sc_vehiclembt, sc_vehicleaboat, heals, revives, team, kill_streak, squad_id, accuracy_detailed, dnf, is_commander, is_soldier)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40)"#;
+
+#[derive(FromRow)]
+struct Row {
+    pub report_id: i64,
+    pub persona_id: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub shots_hit: f32,
+    pub shots_fired: f32,
+    pub vehicle_destroyed: i64,
+    pub assists: i64,
+    pub spm: i64,
+    pub kd_ratio: f32,
+    pub skill: i32,
+    pub vehicle_assists: i64,
+    pub accuracy: i64,
+    pub sc_unlock: i64,
+    pub sc_bomber: i64,
+    pub sc_vehiclesh: i64,
+    pub sc_vehicleajet: i64,
+    pub sc_engineer: i64,
+    pub sc_commander: i64,
+    pub sc_assault: i64,
+    pub vehicle: i64,
+    pub sc_vehicleaa: i64,
+    pub sc_award: i64,
+    pub sc_vehicleifv: i64,
+    pub sc_recon: i64,
+    pub sc_vehicleah: i64,
+    pub sc_support: i64,
+    pub sc_vehiclesjet: i64,
+    pub total: i64,
+    pub sc_vehiclembt: i64,
+    pub sc_vehicleaboat: i64,
+    pub heals: i32,
+    pub revives: i32,
+    pub team: i16,
+    pub kill_streak: i32,
+    pub squad_id: i16,
+    pub accuracy_detailed: f32,
+    pub dnf: bool,
+    pub is_commander: bool,
+    pub is_soldier: bool,
+}
+
+impl From<Row> for BattlelogPlayerreport {
+    fn from(e: Row) -> Self {
+        BattlelogPlayerreport {
+            report_id: e.report_id as u64,
+            persona_id: e.persona_id as u64,
+            kills: e.kills as u32,
+            deaths: e.deaths as u32,
+            shots_hit: e.shots_hit,
+            shots_fired: e.shots_fired,
+            vehicle_destroyed: e.vehicle_destroyed as u32,
+            assists: e.assists as u32,
+            spm: e.spm as u32,
+            kd_ratio: e.kd_ratio,
+            skill: e.skill,
+            vehicle_assists: e.vehicle_assists as u32,
+            accuracy: e.accuracy as u32,
+            sc_unlock: e.sc_unlock as u32,
+            sc_bomber: e.sc_bomber as u32,
+            sc_vehiclesh: e.sc_vehiclesh as u32,
+            sc_vehicleajet: e.sc_vehicleajet as u32,
+            sc_engineer: e.sc_engineer as u32,
+            sc_commander: e.sc_commander as u32,
+            sc_assault: e.sc_assault as u32,
+            vehicle: e.vehicle as u32,
+            sc_vehicleaa: e.sc_vehicleaa as u32,
+            sc_award: e.sc_award as u32,
+            sc_vehicleifv: e.sc_vehicleifv as u32,
+            sc_recon: e.sc_recon as u32,
+            sc_vehicleah: e.sc_vehicleah as u32,
+            sc_support: e.sc_support as u32,
+            sc_vehiclesjet: e.sc_vehiclesjet as u32,
+            total: e.total as u32,
+            sc_vehiclembt: e.sc_vehiclembt as u32,
+            sc_vehicleaboat: e.sc_vehicleaboat as u32,
+            heals: e.heals as u16,
+            revives: e.revives as u16,
+            team: e.team as i8,
+            kill_streak: e.kill_streak as u16,
+            squad_id: e.squad_id as i8,
+            accuracy_detailed: e.accuracy_detailed,
+            dnf: e.dnf,
+            is_commander: e.is_commander,
+            is_soldier: e.is_soldier,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct RoundRow {
+    pub report_id: i64,
+    pub server_id: i32,
+    pub server_name: String,
+    pub map: String,
+    pub mode: String,
+    pub created_at: i64,
+    pub duration: i64,
+    pub team: i16,
+    pub winner: i16,
+    pub kills: i64,
+    pub deaths: i64,
+    pub spm: i64,
+    pub total: i64,
+    pub dnf: bool,
+}
+
+#[derive(FromRow)]
+struct StatsRow {
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub spm_total: i64,
+    pub seconds_played: i64,
+    pub first_seen: Option<i64>,
+    pub last_seen: Option<i64>,
+}
+
+fn bind_playerreport<'q>(query: Query<'q, Postgres, PgArguments>, playerreport: &BattlelogPlayerreport) -> Query<'q, Postgres, PgArguments> {
+    query
+        .bind(playerreport.report_id as i64)
+        .bind(playerreport.persona_id as i64)
+        .bind(playerreport.kills as i64)
+        .bind(playerreport.deaths as i64)
+        .bind(playerreport.shots_hit)
+        .bind(playerreport.shots_fired)
+        .bind(playerreport.vehicle_destroyed as i64)
+        .bind(playerreport.assists as i64)
+        .bind(playerreport.spm as i64)
+        .bind(playerreport.kd_ratio)
+        .bind(playerreport.skill)
+        .bind(playerreport.vehicle_assists as i64)
+        .bind(playerreport.accuracy as i64)
+        .bind(playerreport.sc_unlock as i64)
+        .bind(playerreport.sc_bomber as i64)
+        .bind(playerreport.sc_vehiclesh as i64)
+        .bind(playerreport.sc_vehicleajet as i64)
+        .bind(playerreport.sc_engineer as i64)
+        .bind(playerreport.sc_commander as i64)
+        .bind(playerreport.sc_assault as i64)
+        .bind(playerreport.vehicle as i64)
+        .bind(playerreport.sc_vehicleaa as i64)
+        .bind(playerreport.sc_award as i64)
+        .bind(playerreport.sc_vehicleifv as i64)
+        .bind(playerreport.sc_recon as i64)
+        .bind(playerreport.sc_vehicleah as i64)
+        .bind(playerreport.sc_support as i64)
+        .bind(playerreport.sc_vehiclesjet as i64)
+        .bind(playerreport.total as i64)
+        .bind(playerreport.sc_vehiclembt as i64)
+        .bind(playerreport.sc_vehicleaboat as i64)
+        .bind(playerreport.heals as i32)
+        .bind(playerreport.revives as i32)
+        .bind(playerreport.team as i16)
+        .bind(playerreport.kill_streak as i32)
+        .bind(playerreport.squad_id as i16)
+        .bind(playerreport.accuracy_detailed)
+        .bind(playerreport.dnf)
+        .bind(playerreport.is_commander)
+        .bind(playerreport.is_soldier)
+}
+
+impl PostgresContext {
+    pub async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from playerreports WHERE report_id = $1 AND persona_id = $2")
+            .bind(report_id as i64)
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogPlayerreport::from))
+    }
+
+    pub async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT * from playerreports WHERE report_id = $1 ORDER BY persona_id")
+            .bind(report_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(BattlelogPlayerreport::from).collect())
+    }
+
+    pub async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        let mut res: Vec<RoundRow> =
+            query_as(r#"SELECT pr.report_id, b.server_id, s.name AS server_name, b.map, b.mode, b.created_at, b.duration,
+                    pr.team, b.winner, pr.kills, pr.deaths, pr.spm, pr.total, pr.dnf
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN servers s ON s.id = b.server_id
+                WHERE pr.persona_id = $1
+                ORDER BY b.created_at DESC
+                LIMIT $2"#)
+            .bind(persona_id as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let res = res.drain(..).map(|e: RoundRow| PersonaRound {
+            report_id: e.report_id as u64,
+            server_id: e.server_id,
+            server_name: e.server_name,
+            map: e.map,
+            mode: e.mode,
+            created_at: e.created_at as u32,
+            duration: e.duration as u32,
+            team: e.team as i8,
+            winner: e.winner as i8,
+            kills: e.kills as u32,
+            deaths: e.deaths as u32,
+            spm: e.spm as u32,
+            total: e.total as u32,
+            dnf: e.dnf,
+        }).collect();
+
+        Ok(res)
+    }
+
+    pub async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        let e: StatsRow =
+            query_as(r#"SELECT COUNT(*) AS rounds,
+                    COALESCE(SUM(CASE WHEN pr.team = b.winner THEN 1 ELSE 0 END), 0)::BIGINT AS wins,
+                    COALESCE(SUM(pr.kills), 0)::BIGINT AS kills,
+                    COALESCE(SUM(pr.deaths), 0)::BIGINT AS deaths,
+                    COALESCE(SUM(pr.spm), 0)::BIGINT AS spm_total,
+                    COALESCE(SUM(b.duration), 0)::BIGINT AS seconds_played,
+                    MIN(b.created_at) AS first_seen,
+                    MAX(b.created_at) AS last_seen
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                WHERE pr.persona_id = $1"#)
+            .bind(persona_id as i64)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(PersonaStats {
+            rounds: e.rounds,
+            wins: e.wins,
+            kills: e.kills,
+            deaths: e.deaths,
+            kd_ratio: e.kills as f32 / e.deaths.max(1) as f32,
+            spm: if e.rounds > 0 { e.spm_total as f32 / e.rounds as f32 } else { 0.0 },
+            seconds_played: e.seconds_played,
+            first_seen: e.first_seen.map(|t| t as u32),
+            last_seen: e.last_seen.map(|t| t as u32),
+        })
+    }
+
+    /// Personas who played in any of the stored rounds of the persona.
+    pub async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        let res: Vec<(i64,)> =
+            query_as(r#"SELECT DISTINCT pr.persona_id
+                FROM playerreports own
+                JOIN playerreports pr ON pr.report_id = own.report_id
+                WHERE own.persona_id = $1 AND pr.persona_id <> $1
+                ORDER BY pr.persona_id"#)
+            .bind(persona_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|e| e.0 as u64).collect())
+    }
+
+    pub async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        let res = bind_playerreport(query(INSERT_PLAYERREPORT), playerreport)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the playerreport or replaces the stored stats with the latest fetched ones.
+    pub async fn upsert_playerreport(&self, transaction: &mut Transaction<'_, Postgres>, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        let sql = format!(r#"{}
+            ON CONFLICT (report_id, persona_id) DO UPDATE SET kills = EXCLUDED.kills, deaths = EXCLUDED.deaths, shots_hit = EXCLUDED.shots_hit, shots_fired = EXCLUDED.shots_fired, vehicle_destroyed = EXCLUDED.vehicle_destroyed, assists = EXCLUDED.assists, spm = EXCLUDED.spm, kd_ratio = EXCLUDED.kd_ratio, skill = EXCLUDED.skill, vehicle_assists = EXCLUDED.vehicle_assists, accuracy = EXCLUDED.accuracy, sc_unlock = EXCLUDED.sc_unlock, sc_bomber = EXCLUDED.sc_bomber, sc_vehiclesh = EXCLUDED.sc_vehiclesh, sc_vehicleajet = EXCLUDED.sc_vehicleajet, sc_engineer = EXCLUDED.sc_engineer, sc_commander = EXCLUDED.sc_commander, sc_assault = EXCLUDED.sc_assault, vehicle = EXCLUDED.vehicle, sc_vehicleaa = EXCLUDED.sc_vehicleaa, sc_award = EXCLUDED.sc_award, sc_vehicleifv = EXCLUDED.sc_vehicleifv, sc_recon = EXCLUDED.sc_recon, sc_vehicleah = EXCLUDED.sc_vehicleah, sc_support = EXCLUDED.sc_support, sc_vehiclesjet = EXCLUDED.sc_vehiclesjet, total = EXCLUDED.total, sc_vehiclembt = EXCLUDED.sc_vehiclembt, sc_vehicleaboat = EXCLUDED.sc_vehicleaboat, heals = EXCLUDED.heals, revives = EXCLUDED.revives, team = EXCLUDED.team, kill_streak = EXCLUDED.kill_streak, squad_id = EXCLUDED.squad_id, accuracy_detailed = EXCLUDED.accuracy_detailed, dnf = EXCLUDED.dnf, is_commander = EXCLUDED.is_commander, is_soldier = EXCLUDED.is_soldier"#, INSERT_PLAYERREPORT);
+        let res = bind_playerreport(query(&sql), playerreport)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
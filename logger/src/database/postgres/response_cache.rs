@@ -0,0 +1,60 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::response_cache::CachedResponseRow;
+
+use super::context::PostgresContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub cache_key: String,
+    pub body: String,
+    pub fetched_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+impl From<Row> for CachedResponseRow {
+    fn from(e: Row) -> Self {
+        CachedResponseRow {
+            cache_key: e.cache_key,
+            body: e.body,
+            fetched_at: e.fetched_at as u32,
+            expires_at: e.expires_at.map(|v| v as u32),
+        }
+    }
+}
+
+impl PostgresContext {
+    pub async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from response_cache WHERE cache_key = $1")
+            .bind(cache_key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(CachedResponseRow::from))
+    }
+
+    pub async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO response_cache (cache_key, body, fetched_at, expires_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (cache_key) DO UPDATE SET body = EXCLUDED.body, fetched_at = EXCLUDED.fetched_at, expires_at = EXCLUDED.expires_at"#)
+            .bind(&row.cache_key)
+            .bind(&row.body)
+            .bind(row.fetched_at as i64)
+            .bind(row.expires_at.map(|v| v as i64))
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        let res = query(r#"DELETE FROM response_cache WHERE expires_at IS NOT NULL AND expires_at < $1"#)
+            .bind(now as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
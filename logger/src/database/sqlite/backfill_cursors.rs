@@ -0,0 +1,59 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::backfill_cursors::BackfillCursor;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub persona_id: i64,
+    pub timestamp: u32,
+    pub cutoff: u32,
+    pub reports_ingested: u32,
+    pub reports_failed: u32,
+    pub completed: bool,
+    pub updated_at: u32,
+}
+
+impl From<Row> for BackfillCursor {
+    fn from(e: Row) -> Self {
+        BackfillCursor {
+            persona_id: e.persona_id as u64,
+            timestamp: e.timestamp,
+            cutoff: e.cutoff,
+            reports_ingested: e.reports_ingested,
+            reports_failed: e.reports_failed,
+            completed: e.completed,
+            updated_at: e.updated_at,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from backfill_cursors WHERE persona_id = ?")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BackfillCursor::from))
+    }
+
+    pub async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO backfill_cursors (persona_id, timestamp, cutoff, reports_ingested, reports_failed, completed, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (persona_id) DO UPDATE SET timestamp = excluded.timestamp, cutoff = excluded.cutoff, reports_ingested = excluded.reports_ingested, reports_failed = excluded.reports_failed, completed = excluded.completed, updated_at = excluded.updated_at"#)
+            .bind(cursor.persona_id as i64)
+            .bind(cursor.timestamp)
+            .bind(cursor.cutoff)
+            .bind(cursor.reports_ingested)
+            .bind(cursor.reports_failed)
+            .bind(cursor.completed)
+            .bind(cursor.updated_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
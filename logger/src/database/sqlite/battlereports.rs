@@ -0,0 +1,145 @@
+use sqlx::{query, query_as, FromRow, Sqlite, Transaction};
+
+use crate::database::battlelog::battlereports::BattlelogBattlereport;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub id: i64,
+    pub duration: u32,
+    pub winner: i8,
+    pub server_id: i32,
+    pub map: String,
+    pub mode: String,
+    pub created_at: u32,
+    pub processed: u8,
+}
+
+impl From<Row> for BattlelogBattlereport {
+    fn from(e: Row) -> Self {
+        BattlelogBattlereport {
+            id: e.id as u64,
+            duration: e.duration,
+            winner: e.winner,
+            server_id: e.server_id,
+            map: e.map,
+            mode: e.mode,
+            created_at: e.created_at,
+            processed: e.processed,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_battlereport_by_report_id(&self, report_id: u64) -> Result<Option<BattlelogBattlereport>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from battlereports WHERE id = ?")
+            .bind(report_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogBattlereport::from))
+    }
+
+    /// Ids of the stored battlereports, see `BattlelogContext::get_battlereport_ids`.
+    pub async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        let res: Vec<(i64,)> =
+            query_as(r#"SELECT id
+                FROM battlereports
+                WHERE (?1 IS NULL OR created_at >= ?1)
+                    AND (?2 IS NULL OR created_at < ?2)
+                    AND (?3 IS NULL OR server_id = ?3)
+                ORDER BY id"#)
+            .bind(from)
+            .bind(to)
+            .bind(server_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|e| e.0 as u64).collect())
+    }
+
+    pub async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration)
+            .bind(battlereport.winner)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the battlereport or refreshes the existing row, see `BattlelogContext::upsert_battlereport`.
+    pub async fn upsert_battlereport(&self, transaction: &mut Transaction<'_, Sqlite>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                duration = MAX(duration, excluded.duration),
+                winner = CASE WHEN excluded.winner >= 0 THEN excluded.winner ELSE winner END,
+                server_id = excluded.server_id,
+                map = CASE WHEN excluded.map <> '' THEN excluded.map ELSE map END,
+                mode = CASE WHEN excluded.mode <> '' THEN excluded.mode ELSE mode END,
+                processed = MAX(processed, excluded.processed)"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration)
+            .bind(battlereport.winner)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at)
+            .bind(battlereport.processed)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the battlereport or overwrites the stored columns, see `BattlelogContext::replace_battlereport`.
+    pub async fn replace_battlereport(&self, transaction: &mut Transaction<'_, Sqlite>, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereports (id, duration, winner, server_id, map, mode, created_at, processed) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                duration = excluded.duration,
+                winner = excluded.winner,
+                server_id = excluded.server_id,
+                map = excluded.map,
+                mode = excluded.mode,
+                created_at = excluded.created_at"#)
+            .bind(battlereport.id as i64)
+            .bind(battlereport.duration)
+            .bind(battlereport.winner)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at)
+            .bind(battlereport.processed)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn update_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE battlereports SET duration = ?, winner = ?, server_id = ?, map = ?, mode = ?, created_at = ?, processed = ? WHERE id = ?"#)
+            .bind(battlereport.duration)
+            .bind(battlereport.winner)
+            .bind(battlereport.server_id)
+            .bind(&battlereport.map)
+            .bind(&battlereport.mode)
+            .bind(battlereport.created_at)
+            .bind(battlereport.processed)
+            .bind(battlereport.id as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
@@ -0,0 +1,142 @@
+use sqlx::{query_as, FromRow};
+
+use crate::database::battlelog::{clans::{ClanActivity, ClanStats}, personas::PersonaSearchResult};
+
+use super::{context::SqliteContext, personas::SearchRow};
+
+#[derive(FromRow)]
+struct StatsRow {
+    pub clan_tag: String,
+    pub members: i64,
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub spm_total: i64,
+}
+
+impl From<StatsRow> for ClanStats {
+    fn from(e: StatsRow) -> Self {
+        ClanStats {
+            clan_tag: e.clan_tag,
+            members: e.members,
+            rounds: e.rounds,
+            wins: e.wins,
+            kills: e.kills,
+            deaths: e.deaths,
+            kd_ratio: e.kills as f32 / e.deaths.max(1) as f32,
+            spm: if e.rounds > 0 { e.spm_total as f32 / e.rounds as f32 } else { 0.0 },
+            win_rate: if e.rounds > 0 { e.wins as f32 / e.rounds as f32 } else { 0.0 },
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct ActivityRow {
+    pub day: String,
+    pub rounds: i64,
+    pub players: i64,
+}
+
+impl SqliteContext {
+    pub async fn get_clan_roster(&self, clan_tag: &str) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        let mut res: Vec<SearchRow> =
+            query_as(r#"SELECT p.id, p.name, p.clan_tag, p.gravatar_md5,
+                    MAX(b.created_at) AS last_seen,
+                    COUNT(b.id) AS rounds
+                FROM personas p
+                LEFT JOIN playerreports pr ON pr.persona_id = p.id
+                LEFT JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.clan_tag = ?
+                GROUP BY p.id
+                ORDER BY last_seen DESC"#)
+            .bind(clan_tag)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaSearchResult::from).collect())
+    }
+
+    /// Stats of the personas currently wearing the tag, optionally limited to a single server. Only
+    /// the rounds within the first and last time the name history saw a member with the tag count.
+    pub async fn get_clan_stats(&self, clan_tag: &str, server_id: Option<i32>) -> Result<ClanStats, sqlx::Error> {
+        let res: StatsRow =
+            query_as(r#"SELECT ?1 AS clan_tag,
+                    COUNT(DISTINCT p.id) AS members,
+                    COUNT(r.report_id) AS rounds,
+                    CAST(COALESCE(SUM(r.team = r.winner), 0) AS INTEGER) AS wins,
+                    CAST(COALESCE(SUM(r.kills), 0) AS INTEGER) AS kills,
+                    CAST(COALESCE(SUM(r.deaths), 0) AS INTEGER) AS deaths,
+                    CAST(COALESCE(SUM(r.spm), 0) AS INTEGER) AS spm_total
+                FROM personas p
+                LEFT JOIN (
+                    SELECT pr.persona_id, pr.report_id, pr.team, pr.kills, pr.deaths, pr.spm, b.winner, b.created_at
+                    FROM playerreports pr
+                    JOIN battlereports b ON b.id = pr.report_id
+                    WHERE ?2 IS NULL OR b.server_id = ?2
+                ) r ON r.persona_id = p.id AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND r.created_at BETWEEN h.first_seen AND h.last_seen)
+                WHERE p.clan_tag = ?1"#)
+            .bind(clan_tag)
+            .bind(server_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(ClanStats::from(res))
+    }
+
+    /// Rounds played by the clan's members per day since `since`, while wearing the tag like in `get_clan_stats`.
+    pub async fn get_clan_activity(&self, clan_tag: &str, since: u32) -> Result<Vec<ClanActivity>, sqlx::Error> {
+        let mut res: Vec<ActivityRow> =
+            query_as(r#"SELECT strftime('%Y-%m-%d', b.created_at, 'unixepoch') AS day,
+                    COUNT(*) AS rounds,
+                    COUNT(DISTINCT p.id) AS players
+                FROM personas p
+                JOIN playerreports pr ON pr.persona_id = p.id
+                JOIN battlereports b ON b.id = pr.report_id
+                WHERE p.clan_tag = ? AND b.created_at >= ?
+                    AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND b.created_at BETWEEN h.first_seen AND h.last_seen)
+                GROUP BY 1
+                ORDER BY 1"#)
+            .bind(clan_tag)
+            .bind(since)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(|e: ActivityRow| ClanActivity {
+            day: e.day,
+            rounds: e.rounds,
+            players: e.players,
+        }).collect())
+    }
+
+    /// Clans seen on the server since `since` with at least `min_rounds` member rounds, ordered by rounds.
+    /// Members are grouped by their current tag, with the rounds they played wearing it like in `get_clan_stats`.
+    pub async fn get_clan_leaderboard(&self, server_id: i32, since: u32, min_rounds: u32) -> Result<Vec<ClanStats>, sqlx::Error> {
+        let mut res: Vec<StatsRow> =
+            query_as(r#"SELECT p.clan_tag AS clan_tag,
+                    COUNT(DISTINCT p.id) AS members,
+                    COUNT(*) AS rounds,
+                    CAST(SUM(pr.team = b.winner) AS INTEGER) AS wins,
+                    CAST(SUM(pr.kills) AS INTEGER) AS kills,
+                    CAST(SUM(pr.deaths) AS INTEGER) AS deaths,
+                    CAST(SUM(pr.spm) AS INTEGER) AS spm_total
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN personas p ON p.id = pr.persona_id
+                WHERE b.server_id = ? AND b.created_at >= ? AND p.clan_tag IS NOT NULL AND p.clan_tag <> ''
+                    AND EXISTS (SELECT 1 FROM persona_name_history h
+                        WHERE h.persona_id = p.id AND h.clan_tag = p.clan_tag AND b.created_at BETWEEN h.first_seen AND h.last_seen)
+                GROUP BY p.clan_tag
+                HAVING COUNT(*) >= ?
+                ORDER BY 3 DESC"#)
+            .bind(server_id)
+            .bind(since)
+            .bind(min_rounds)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(ClanStats::from).collect())
+    }
+}
@@ -0,0 +1,61 @@
+use std::str::FromStr;
+
+use sqlx::{Executor, Sqlite, SqlitePool, Error, Transaction, sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions}, ConnectOptions};
+
+/// SQLite counterpart of `BattlelogContext` for deployments without a MySQL server. SQLite has no
+/// unsigned 64-bit integers, the ids are stored as `INTEGER` which fits every Battlelog id.
+#[derive(Clone, Debug)]
+pub struct SqliteContext {
+    pub pool: SqlitePool,
+}
+
+impl SqliteContext {
+    /// Opens or creates the database file and creates the missing tables. An in-memory database
+    /// lives in a single connection, so the pool can't be used while a transaction is open.
+    pub async fn connect(url: impl AsRef<str>) -> Result<Self, sqlx::Error> {
+        let url = url.as_ref();
+        let mut connection_options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(false);
+        connection_options
+            .disable_statement_logging();
+
+        let in_memory = url.contains(":memory:") || url.contains("mode=memory");
+        let pool = if in_memory {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(connection_options)
+                .await?
+        }
+        else {
+            SqlitePoolOptions::new()
+                .connect_with(connection_options.journal_mode(SqliteJournalMode::Wal))
+                .await?
+        };
+
+        let context = Self { pool };
+        context.create_schema().await?;
+        Ok(context)
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub async fn begin_transaction(&self) -> Result<Transaction<'_, Sqlite>, Error> {
+        self.pool.begin().await
+    }
+
+    async fn create_schema(&self) -> Result<(), sqlx::Error> {
+        self.pool.execute(include_str!("../../../schema/sqlite.sql")).await?;
+
+        // Files created before the column existed keep their old personas table
+        let (has_seen_at,): (bool,) = sqlx::query_as("SELECT COUNT(*) > 0 FROM pragma_table_info('personas') WHERE name = 'clan_tag_seen_at'")
+            .fetch_one(&self.pool)
+            .await?;
+        if !has_seen_at {
+            self.pool.execute("ALTER TABLE personas ADD COLUMN clan_tag_seen_at INTEGER NULL").await?;
+        }
+        Ok(())
+    }
+}
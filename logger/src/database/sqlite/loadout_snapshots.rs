@@ -0,0 +1,60 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::loadout_snapshots::LoadoutSnapshot;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub id: i64,
+    pub persona_id: i64,
+    pub created_at: u32,
+    pub loadout: String,
+}
+
+impl From<Row> for LoadoutSnapshot {
+    fn from(e: Row) -> Self {
+        LoadoutSnapshot {
+            id: e.id as u64,
+            persona_id: e.persona_id as u64,
+            created_at: e.created_at,
+            loadout: e.loadout,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_latest_loadout_snapshot(&self, persona_id: u64) -> Result<Option<LoadoutSnapshot>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from loadout_snapshots WHERE persona_id = ? ORDER BY created_at DESC, id DESC LIMIT 1")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(LoadoutSnapshot::from))
+    }
+
+    /// Newest snapshots first.
+    pub async fn get_loadout_snapshots(&self, persona_id: u64, limit: u32) -> Result<Vec<LoadoutSnapshot>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT * from loadout_snapshots WHERE persona_id = ? ORDER BY created_at DESC, id DESC LIMIT ?")
+            .bind(persona_id as i64)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(LoadoutSnapshot::from).collect())
+    }
+
+    pub async fn insert_loadout_snapshot(&self, snapshot: &LoadoutSnapshot) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO loadout_snapshots (persona_id, created_at, loadout) VALUES (?, ?, ?)"#)
+            .bind(snapshot.persona_id as i64)
+            .bind(snapshot.created_at)
+            .bind(&snapshot.loadout)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+
+        Ok(res as u64)
+    }
+}
@@ -0,0 +1,15 @@
+pub mod context;
+pub mod personas;
+pub mod battlereports;
+pub mod playerreports;
+pub mod servers;
+pub mod persona_game_expansion;
+pub mod persona_info;
+pub mod persona_name_history;
+pub mod clans;
+pub mod loadout_snapshots;
+pub mod response_cache;
+pub mod report_archives;
+pub mod backfill_cursors;
+pub mod exports;
+pub mod store;
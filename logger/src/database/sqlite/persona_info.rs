@@ -0,0 +1,86 @@
+use sqlx::{query, query_as, FromRow, Sqlite, Transaction};
+
+use crate::database::battlelog::persona_info::PersonaInfo;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub persona_id: i64,
+    pub locality: Option<String>,
+    pub location: Option<String>,
+    pub presentation: Option<String>,
+    pub login_counter: Option<u32>,
+    pub last_login: Option<u32>,
+}
+
+impl From<Row> for PersonaInfo {
+    fn from(e: Row) -> Self {
+        PersonaInfo {
+            persona_id: e.persona_id as u64,
+            locality: e.locality,
+            location: e.location,
+            presentation: e.presentation,
+            login_counter: e.login_counter,
+            last_login: e.last_login,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_persona_info_by_id(&self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from persona_infos WHERE persona_id = ?")
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(PersonaInfo::from))
+    }
+
+    pub async fn get_persona_info_by_id_with_transaction(&self, transaction: &mut Transaction<'_, Sqlite>, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from persona_infos WHERE persona_id = ?")
+            .bind(persona_id as i64)
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        Ok(res.map(PersonaInfo::from))
+    }
+
+    pub async fn upsert_persona_info(&self, transaction: &mut Transaction<'_, Sqlite>, p: &PersonaInfo) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO persona_infos (persona_id, locality, location, presentation, login_counter, last_login) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (persona_id) DO UPDATE SET
+                locality = excluded.locality,
+                location = excluded.location,
+                presentation = excluded.presentation,
+                login_counter = excluded.login_counter,
+                last_login = excluded.last_login"#)
+            .bind(p.persona_id as i64)
+            .bind(&p.locality)
+            .bind(&p.location)
+            .bind(&p.presentation)
+            .bind(p.login_counter)
+            .bind(p.last_login)
+            .execute(&mut *transaction)
+            .await?
+            .last_insert_rowid();
+
+        Ok(res as u64)
+    }
+
+    pub async fn update_persona_info(&self, transaction: &mut Transaction<'_, Sqlite>, p: &PersonaInfo) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE persona_infos SET locality = ?, location = ?, presentation = ?, login_counter = ?, last_login = ? WHERE persona_id = ?"#)
+            .bind(&p.locality)
+            .bind(&p.location)
+            .bind(&p.presentation)
+            .bind(p.login_counter)
+            .bind(p.last_login)
+            .bind(p.persona_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
@@ -0,0 +1,66 @@
+use sqlx::{query, query_as, FromRow, Sqlite, Transaction};
+
+use crate::database::battlelog::persona_name_history::PersonaNameHistory;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub persona_id: i64,
+    pub name: String,
+    pub clan_tag: String,
+    pub first_seen: u32,
+    pub last_seen: u32,
+}
+
+impl From<Row> for PersonaNameHistory {
+    fn from(e: Row) -> Self {
+        PersonaNameHistory {
+            persona_id: e.persona_id as u64,
+            name: e.name,
+            clan_tag: if e.clan_tag.is_empty() { None } else { Some(e.clan_tag) },
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_persona_name_history(&self, persona_id: u64) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT * from persona_name_history WHERE persona_id = ? ORDER BY last_seen DESC")
+            .bind(persona_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaNameHistory::from).collect())
+    }
+
+    pub async fn get_persona_name_history_by_name(&self, name: &str) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT * from persona_name_history WHERE name = ? ORDER BY last_seen DESC")
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PersonaNameHistory::from).collect())
+    }
+
+    /// Records the name and clan tag, widening the first/last seen range if it was already known.
+    pub async fn upsert_persona_name_history(&self, transaction: &mut Transaction<'_, Sqlite>, p: &PersonaNameHistory) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO persona_name_history (persona_id, name, clan_tag, first_seen, last_seen) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (persona_id, name, clan_tag) DO UPDATE SET
+                first_seen = MIN(first_seen, excluded.first_seen),
+                last_seen = MAX(last_seen, excluded.last_seen)"#)
+            .bind(p.persona_id as i64)
+            .bind(&p.name)
+            .bind(p.clan_tag.as_deref().unwrap_or(""))
+            .bind(p.first_seen)
+            .bind(p.last_seen)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,298 @@
+use sqlx::{query, query_as, sqlite::SqliteArguments, FromRow, Sqlite, Transaction};
+use sqlx::query::Query;
+
+use crate::database::battlelog::playerreports::{BattlelogPlayerreport, PersonaRound, PersonaStats};
+
+use super::context::SqliteContext;
+
+const INSERT_PLAYERREPORT: &str = r#"INSERT INTO playerreports (report_id, persona_id, kills, deaths, shots_hit, shots_fired, vehicle_destroyed, assists, spm, kd_ratio, skill, vehicle_assists, accuracy, sc_unlock, sc_bomber, sc_vehiclesh, sc_vehicleajet, sc_engineer, sc_commander, sc_assault, vehicle, sc_vehicleaa, sc_award, sc_vehicleifv, sc_recon, sc_vehicleah, sc_support, sc_vehiclesjet, total, sc_vehiclembt, sc_vehicleaboat, heals, revives, team, kill_streak, squad_id, accuracy_detailed, dnf, is_commander, is_soldier)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#;
+
+#[derive(FromRow)]
+struct Row {
+    pub report_id: i64,
+    pub persona_id: i64,
+    pub kills: u32,
+    pub deaths: u32,
+    pub shots_hit: f32,
+    pub shots_fired: f32,
+    pub vehicle_destroyed: u32,
+    pub assists: u32,
+    pub spm: u32,
+    pub kd_ratio: f32,
+    pub skill: i32,
+    pub vehicle_assists: u32,
+    pub accuracy: u32,
+    pub sc_unlock: u32,
+    pub sc_bomber: u32,
+    pub sc_vehiclesh: u32,
+    pub sc_vehicleajet: u32,
+    pub sc_engineer: u32,
+    pub sc_commander: u32,
+    pub sc_assault: u32,
+    pub vehicle: u32,
+    pub sc_vehicleaa: u32,
+    pub sc_award: u32,
+    pub sc_vehicleifv: u32,
+    pub sc_recon: u32,
+    pub sc_vehicleah: u32,
+    pub sc_support: u32,
+    pub sc_vehiclesjet: u32,
+    pub total: u32,
+    pub sc_vehiclembt: u32,
+    pub sc_vehicleaboat: u32,
+    pub heals: u16,
+    pub revives: u16,
+    pub team: i8,
+    pub kill_streak: u16,
+    pub squad_id: i8,
+    pub accuracy_detailed: f32,
+    pub dnf: bool,
+    pub is_commander: bool,
+    pub is_soldier: bool,
+}
+
+impl From<Row> for BattlelogPlayerreport {
+    fn from(e: Row) -> Self {
+        BattlelogPlayerreport {
+            report_id: e.report_id as u64,
+            persona_id: e.persona_id as u64,
+            kills: e.kills,
+            deaths: e.deaths,
+            shots_hit: e.shots_hit,
+            shots_fired: e.shots_fired,
+            vehicle_destroyed: e.vehicle_destroyed,
+            assists: e.assists,
+            spm: e.spm,
+            kd_ratio: e.kd_ratio,
+            skill: e.skill,
+            vehicle_assists: e.vehicle_assists,
+            accuracy: e.accuracy,
+            sc_unlock: e.sc_unlock,
+            sc_bomber: e.sc_bomber,
+            sc_vehiclesh: e.sc_vehiclesh,
+            sc_vehicleajet: e.sc_vehicleajet,
+            sc_engineer: e.sc_engineer,
+            sc_commander: e.sc_commander,
+            sc_assault: e.sc_assault,
+            vehicle: e.vehicle,
+            sc_vehicleaa: e.sc_vehicleaa,
+            sc_award: e.sc_award,
+            sc_vehicleifv: e.sc_vehicleifv,
+            sc_recon: e.sc_recon,
+            sc_vehicleah: e.sc_vehicleah,
+            sc_support: e.sc_support,
+            sc_vehiclesjet: e.sc_vehiclesjet,
+            total: e.total,
+            sc_vehiclembt: e.sc_vehiclembt,
+            sc_vehicleaboat: e.sc_vehicleaboat,
+            heals: e.heals,
+            revives: e.revives,
+            team: e.team,
+            kill_streak: e.kill_streak,
+            squad_id: e.squad_id,
+            accuracy_detailed: e.accuracy_detailed,
+            dnf: e.dnf,
+            is_commander: e.is_commander,
+            is_soldier: e.is_soldier,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct RoundRow {
+    pub report_id: i64,
+    pub server_id: i32,
+    pub server_name: String,
+    pub map: String,
+    pub mode: String,
+    pub created_at: u32,
+    pub duration: u32,
+    pub team: i8,
+    pub winner: i8,
+    pub kills: u32,
+    pub deaths: u32,
+    pub spm: u32,
+    pub total: u32,
+    pub dnf: bool,
+}
+
+#[derive(FromRow)]
+struct StatsRow {
+    pub rounds: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub spm_total: i64,
+    pub seconds_played: i64,
+    pub first_seen: Option<u32>,
+    pub last_seen: Option<u32>,
+}
+
+fn bind_playerreport<'q>(query: Query<'q, Sqlite, SqliteArguments<'q>>, playerreport: &BattlelogPlayerreport) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    query
+        .bind(playerreport.report_id as i64)
+        .bind(playerreport.persona_id as i64)
+        .bind(playerreport.kills)
+        .bind(playerreport.deaths)
+        .bind(playerreport.shots_hit)
+        .bind(playerreport.shots_fired)
+        .bind(playerreport.vehicle_destroyed)
+        .bind(playerreport.assists)
+        .bind(playerreport.spm)
+        .bind(playerreport.kd_ratio)
+        .bind(playerreport.skill)
+        .bind(playerreport.vehicle_assists)
+        .bind(playerreport.accuracy)
+        .bind(playerreport.sc_unlock)
+        .bind(playerreport.sc_bomber)
+        .bind(playerreport.sc_vehiclesh)
+        .bind(playerreport.sc_vehicleajet)
+        .bind(playerreport.sc_engineer)
+        .bind(playerreport.sc_commander)
+        .bind(playerreport.sc_assault)
+        .bind(playerreport.vehicle)
+        .bind(playerreport.sc_vehicleaa)
+        .bind(playerreport.sc_award)
+        .bind(playerreport.sc_vehicleifv)
+        .bind(playerreport.sc_recon)
+        .bind(playerreport.sc_vehicleah)
+        .bind(playerreport.sc_support)
+        .bind(playerreport.sc_vehiclesjet)
+        .bind(playerreport.total)
+        .bind(playerreport.sc_vehiclembt)
+        .bind(playerreport.sc_vehicleaboat)
+        .bind(playerreport.heals)
+        .bind(playerreport.revives)
+        .bind(playerreport.team)
+        .bind(playerreport.kill_streak)
+        .bind(playerreport.squad_id)
+        .bind(playerreport.accuracy_detailed)
+        .bind(playerreport.dnf)
+        .bind(playerreport.is_commander)
+        .bind(playerreport.is_soldier)
+}
+
+impl SqliteContext {
+    pub async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from playerreports WHERE report_id = ? AND persona_id = ?")
+            .bind(report_id as i64)
+            .bind(persona_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogPlayerreport::from))
+    }
+
+    pub async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        let mut res: Vec<Row> =
+            query_as("SELECT * from playerreports WHERE report_id = ? ORDER BY persona_id")
+            .bind(report_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(BattlelogPlayerreport::from).collect())
+    }
+
+    pub async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        let mut res: Vec<RoundRow> =
+            query_as(r#"SELECT pr.report_id, b.server_id, s.name AS server_name, b.map, b.mode, b.created_at, b.duration,
+                    pr.team, b.winner, pr.kills, pr.deaths, pr.spm, pr.total, pr.dnf
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                JOIN servers s ON s.id = b.server_id
+                WHERE pr.persona_id = ?
+                ORDER BY b.created_at DESC
+                LIMIT ?"#)
+            .bind(persona_id as i64)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let res = res.drain(..).map(|e: RoundRow| PersonaRound {
+            report_id: e.report_id as u64,
+            server_id: e.server_id,
+            server_name: e.server_name,
+            map: e.map,
+            mode: e.mode,
+            created_at: e.created_at,
+            duration: e.duration,
+            team: e.team,
+            winner: e.winner,
+            kills: e.kills,
+            deaths: e.deaths,
+            spm: e.spm,
+            total: e.total,
+            dnf: e.dnf,
+        }).collect();
+
+        Ok(res)
+    }
+
+    pub async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        let e: StatsRow =
+            query_as(r#"SELECT COUNT(*) AS rounds,
+                    CAST(COALESCE(SUM(pr.team = b.winner), 0) AS INTEGER) AS wins,
+                    CAST(COALESCE(SUM(pr.kills), 0) AS INTEGER) AS kills,
+                    CAST(COALESCE(SUM(pr.deaths), 0) AS INTEGER) AS deaths,
+                    CAST(COALESCE(SUM(pr.spm), 0) AS INTEGER) AS spm_total,
+                    CAST(COALESCE(SUM(b.duration), 0) AS INTEGER) AS seconds_played,
+                    MIN(b.created_at) AS first_seen,
+                    MAX(b.created_at) AS last_seen
+                FROM playerreports pr
+                JOIN battlereports b ON b.id = pr.report_id
+                WHERE pr.persona_id = ?"#)
+            .bind(persona_id as i64)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(PersonaStats {
+            rounds: e.rounds,
+            wins: e.wins,
+            kills: e.kills,
+            deaths: e.deaths,
+            kd_ratio: e.kills as f32 / e.deaths.max(1) as f32,
+            spm: if e.rounds > 0 { e.spm_total as f32 / e.rounds as f32 } else { 0.0 },
+            seconds_played: e.seconds_played,
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        })
+    }
+
+    /// Personas who played in any of the stored rounds of the persona.
+    pub async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        let res: Vec<(i64,)> =
+            query_as(r#"SELECT DISTINCT pr.persona_id
+                FROM playerreports own
+                JOIN playerreports pr ON pr.report_id = own.report_id
+                WHERE own.persona_id = ?1 AND pr.persona_id <> ?1
+                ORDER BY pr.persona_id"#)
+            .bind(persona_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|e| e.0 as u64).collect())
+    }
+
+    pub async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        let res = bind_playerreport(query(INSERT_PLAYERREPORT), playerreport)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    /// Inserts the playerreport or replaces the stored stats with the latest fetched ones.
+    pub async fn upsert_playerreport(&self, transaction: &mut Transaction<'_, Sqlite>, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        let sql = format!(r#"{}
+            ON CONFLICT (report_id, persona_id) DO UPDATE SET kills = excluded.kills, deaths = excluded.deaths, shots_hit = excluded.shots_hit, shots_fired = excluded.shots_fired, vehicle_destroyed = excluded.vehicle_destroyed, assists = excluded.assists, spm = excluded.spm, kd_ratio = excluded.kd_ratio, skill = excluded.skill, vehicle_assists = excluded.vehicle_assists, accuracy = excluded.accuracy, sc_unlock = excluded.sc_unlock, sc_bomber = excluded.sc_bomber, sc_vehiclesh = excluded.sc_vehiclesh, sc_vehicleajet = excluded.sc_vehicleajet, sc_engineer = excluded.sc_engineer, sc_commander = excluded.sc_commander, sc_assault = excluded.sc_assault, vehicle = excluded.vehicle, sc_vehicleaa = excluded.sc_vehicleaa, sc_award = excluded.sc_award, sc_vehicleifv = excluded.sc_vehicleifv, sc_recon = excluded.sc_recon, sc_vehicleah = excluded.sc_vehicleah, sc_support = excluded.sc_support, sc_vehiclesjet = excluded.sc_vehiclesjet, total = excluded.total, sc_vehiclembt = excluded.sc_vehiclembt, sc_vehicleaboat = excluded.sc_vehicleaboat, heals = excluded.heals, revives = excluded.revives, team = excluded.team, kill_streak = excluded.kill_streak, squad_id = excluded.squad_id, accuracy_detailed = excluded.accuracy_detailed, dnf = excluded.dnf, is_commander = excluded.is_commander, is_soldier = excluded.is_soldier"#, INSERT_PLAYERREPORT);
+        let res = bind_playerreport(query(&sql), playerreport)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
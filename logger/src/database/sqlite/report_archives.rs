@@ -0,0 +1,109 @@
+use sqlx::{query, query_as, FromRow, Sqlite, Transaction};
+
+use crate::database::battlelog::report_archives::{BattlereportArchive, PlayerreportArchive};
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct BattlereportArchiveRow {
+    pub report_id: i64,
+    pub data: Vec<u8>,
+    pub archived_at: u32,
+}
+
+impl From<BattlereportArchiveRow> for BattlereportArchive {
+    fn from(e: BattlereportArchiveRow) -> Self {
+        BattlereportArchive {
+            report_id: e.report_id as u64,
+            data: e.data,
+            archived_at: e.archived_at,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PlayerreportArchiveRow {
+    pub report_id: i64,
+    pub persona_id: i64,
+    pub data: Vec<u8>,
+    pub archived_at: u32,
+}
+
+impl From<PlayerreportArchiveRow> for PlayerreportArchive {
+    fn from(e: PlayerreportArchiveRow) -> Self {
+        PlayerreportArchive {
+            report_id: e.report_id as u64,
+            persona_id: e.persona_id as u64,
+            data: e.data,
+            archived_at: e.archived_at,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_battlereport_archive(&self, report_id: u64) -> Result<Option<BattlereportArchive>, sqlx::Error> {
+        let res: Option<BattlereportArchiveRow> =
+            query_as("SELECT * from battlereport_archives WHERE report_id = ?")
+            .bind(report_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlereportArchive::from))
+    }
+
+    pub async fn get_playerreport_archives(&self, report_id: u64) -> Result<Vec<PlayerreportArchive>, sqlx::Error> {
+        let mut res: Vec<PlayerreportArchiveRow> =
+            query_as("SELECT * from playerreport_archives WHERE report_id = ?")
+            .bind(report_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.drain(..).map(PlayerreportArchive::from).collect())
+    }
+
+    /// Ids of the archived battlereports, see `BattlelogContext::get_battlereport_archive_ids`.
+    pub async fn get_battlereport_archive_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        let res: Vec<(i64,)> =
+            query_as(r#"SELECT a.report_id
+                FROM battlereport_archives a
+                LEFT JOIN battlereports b ON b.id = a.report_id
+                WHERE (?1 IS NULL OR b.created_at >= ?1)
+                    AND (?2 IS NULL OR b.created_at < ?2)
+                    AND (?3 IS NULL OR b.server_id = ?3)
+                ORDER BY a.report_id"#)
+            .bind(from)
+            .bind(to)
+            .bind(server_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(res.iter().map(|e| e.0 as u64).collect())
+    }
+
+    pub async fn upsert_battlereport_archive(&self, transaction: &mut Transaction<'_, Sqlite>, archive: &BattlereportArchive) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO battlereport_archives (report_id, data, archived_at) VALUES (?, ?, ?)
+            ON CONFLICT (report_id) DO UPDATE SET data = excluded.data, archived_at = excluded.archived_at"#)
+            .bind(archive.report_id as i64)
+            .bind(&archive.data)
+            .bind(archive.archived_at)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn upsert_playerreport_archive(&self, transaction: &mut Transaction<'_, Sqlite>, archive: &PlayerreportArchive) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO playerreport_archives (report_id, persona_id, data, archived_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (report_id, persona_id) DO UPDATE SET data = excluded.data, archived_at = excluded.archived_at"#)
+            .bind(archive.report_id as i64)
+            .bind(archive.persona_id as i64)
+            .bind(&archive.data)
+            .bind(archive.archived_at)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
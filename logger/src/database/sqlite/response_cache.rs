@@ -0,0 +1,60 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::response_cache::CachedResponseRow;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub cache_key: String,
+    pub body: String,
+    pub fetched_at: u32,
+    pub expires_at: Option<u32>,
+}
+
+impl From<Row> for CachedResponseRow {
+    fn from(e: Row) -> Self {
+        CachedResponseRow {
+            cache_key: e.cache_key,
+            body: e.body,
+            fetched_at: e.fetched_at,
+            expires_at: e.expires_at,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from response_cache WHERE cache_key = ?")
+            .bind(cache_key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(CachedResponseRow::from))
+    }
+
+    pub async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        let res = query(r#"INSERT INTO response_cache (cache_key, body, fetched_at, expires_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (cache_key) DO UPDATE SET body = excluded.body, fetched_at = excluded.fetched_at, expires_at = excluded.expires_at"#)
+            .bind(&row.cache_key)
+            .bind(&row.body)
+            .bind(row.fetched_at)
+            .bind(row.expires_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+
+    pub async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        let res = query(r#"DELETE FROM response_cache WHERE expires_at IS NOT NULL AND expires_at < ?"#)
+            .bind(now)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,67 @@
+use sqlx::{query, query_as, FromRow};
+
+use crate::database::battlelog::servers::BattlelogServer;
+
+use super::context::SqliteContext;
+
+#[derive(FromRow)]
+struct Row {
+    pub id: i32,
+    pub name: String,
+    pub guid: String,
+}
+
+impl From<Row> for BattlelogServer {
+    fn from(e: Row) -> Self {
+        BattlelogServer {
+            id: e.id,
+            name: e.name,
+            guid: e.guid,
+        }
+    }
+}
+
+impl SqliteContext {
+    pub async fn get_server_by_server_id(&self, server_id: i32) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from servers WHERE id = ?")
+            .bind(server_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogServer::from))
+    }
+
+    pub async fn get_server_by_server_guid(&self, guid: &str) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        let res: Option<Row> =
+            query_as("SELECT * from servers WHERE guid = ?")
+            .bind(guid)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(res.map(BattlelogServer::from))
+    }
+
+    pub async fn insert_server(&self, server: &BattlelogServer) -> anyhow::Result<u64> {
+        let res = query(r#"INSERT INTO servers (name, guid) VALUES (?, ?)"#)
+            .bind(&server.name)
+            .bind(&server.guid)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+
+        Ok(res as u64)
+    }
+
+    pub async fn update_server(&self, server: &BattlelogServer) -> anyhow::Result<bool> {
+        let res = query(r#"UPDATE servers SET name = ?, guid = ? WHERE id = ?"#)
+            .bind(&server.name)
+            .bind(&server.guid)
+            .bind(server.id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(res > 0)
+    }
+}
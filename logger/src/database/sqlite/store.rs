@@ -0,0 +1,515 @@
+use async_trait::async_trait;
+use sqlx::{Acquire, Sqlite, Transaction};
+
+use crate::database::battlelog::{
+    backfill_cursors::BackfillCursor,
+    battlereports::BattlelogBattlereport,
+    clans::{ClanActivity, ClanStats},
+    exports::{ExportFilter, ExportRow},
+    loadout_snapshots::LoadoutSnapshot,
+    persona_game_expansion::{GameExpansionOwnership, PersonaGameExpansion, PersonaGameExpansionDetails, ServerGameExpansionShare},
+    persona_info::PersonaInfo,
+    persona_name_history::PersonaNameHistory,
+    personas::{BattlelogPersona, PersonaSearchResult},
+    playerreports::{BattlelogPlayerreport, PersonaRound, PersonaStats},
+    report_archives::{BattlereportArchive, PlayerreportArchive},
+    response_cache::CachedResponseRow,
+    servers::BattlelogServer,
+};
+use crate::database::store::{BattlelogStore, StoreTransaction};
+
+use super::context::SqliteContext;
+
+#[async_trait]
+impl BattlelogStore for SqliteContext {
+    async fn begin(&self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let transaction = self.begin_transaction().await?;
+        Ok(Box::new(SqliteTransaction { db: self, transaction }))
+    }
+
+    async fn close(&self) {
+        SqliteContext::close(self).await
+    }
+
+    async fn get_persona_by_persona_id(&self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        SqliteContext::get_persona_by_persona_id(self, persona_id).await
+    }
+
+    async fn get_persona_by_name(&self, name: &str) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        SqliteContext::get_persona_by_name(self, name).await
+    }
+
+    async fn get_personas_without_update(&self) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        SqliteContext::get_personas_without_update(self).await
+    }
+
+    async fn search_personas(&self, name: Option<&str>, clan_tag: Option<&str>, limit: u32) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        SqliteContext::search_personas(self, name, clan_tag, limit).await
+    }
+
+    async fn get_personas_for_refresh(&self, active_since: u32, stale_before: u32, limit: u32) -> Result<Vec<BattlelogPersona>, sqlx::Error> {
+        SqliteContext::get_personas_for_refresh(self, active_since, stale_before, limit).await
+    }
+
+    async fn insert_persona(&self, persona: &BattlelogPersona) -> anyhow::Result<u64> {
+        SqliteContext::insert_persona(self, persona).await
+    }
+
+    async fn get_battlereport_by_report_id(&self, report_id: u64) -> Result<Option<BattlelogBattlereport>, sqlx::Error> {
+        SqliteContext::get_battlereport_by_report_id(self, report_id).await
+    }
+
+    async fn get_battlereport_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        SqliteContext::get_battlereport_ids(self, from, to, server_id).await
+    }
+
+    async fn insert_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        SqliteContext::insert_battlereport(self, battlereport).await
+    }
+
+    async fn update_battlereport(&self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        SqliteContext::update_battlereport(self, battlereport).await
+    }
+
+    async fn get_server_by_server_id(&self, server_id: i32) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        SqliteContext::get_server_by_server_id(self, server_id).await
+    }
+
+    async fn get_server_by_server_guid(&self, guid: &str) -> Result<Option<BattlelogServer>, sqlx::Error> {
+        SqliteContext::get_server_by_server_guid(self, guid).await
+    }
+
+    async fn insert_server(&self, server: &BattlelogServer) -> anyhow::Result<u64> {
+        SqliteContext::insert_server(self, server).await
+    }
+
+    async fn update_server(&self, server: &BattlelogServer) -> anyhow::Result<bool> {
+        SqliteContext::update_server(self, server).await
+    }
+
+    async fn get_playerreport_by_report_id_and_persona_id(&self, report_id: u64, persona_id: u64) -> Result<Option<BattlelogPlayerreport>, sqlx::Error> {
+        SqliteContext::get_playerreport_by_report_id_and_persona_id(self, report_id, persona_id).await
+    }
+
+    async fn get_playerreports_by_report_id(&self, report_id: u64) -> Result<Vec<BattlelogPlayerreport>, sqlx::Error> {
+        SqliteContext::get_playerreports_by_report_id(self, report_id).await
+    }
+
+    async fn get_persona_recent_rounds(&self, persona_id: u64, limit: u32) -> Result<Vec<PersonaRound>, sqlx::Error> {
+        SqliteContext::get_persona_recent_rounds(self, persona_id, limit).await
+    }
+
+    async fn get_persona_stats(&self, persona_id: u64) -> Result<PersonaStats, sqlx::Error> {
+        SqliteContext::get_persona_stats(self, persona_id).await
+    }
+
+    async fn get_co_player_persona_ids(&self, persona_id: u64) -> Result<Vec<u64>, sqlx::Error> {
+        SqliteContext::get_co_player_persona_ids(self, persona_id).await
+    }
+
+    async fn insert_playerreport(&self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        SqliteContext::insert_playerreport(self, playerreport).await
+    }
+
+    async fn get_persona_name_history(&self, persona_id: u64) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        SqliteContext::get_persona_name_history(self, persona_id).await
+    }
+
+    async fn get_persona_name_history_by_name(&self, name: &str) -> Result<Vec<PersonaNameHistory>, sqlx::Error> {
+        SqliteContext::get_persona_name_history_by_name(self, name).await
+    }
+
+    async fn get_persona_info_by_id(&self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        SqliteContext::get_persona_info_by_id(self, persona_id).await
+    }
+
+    async fn get_persona_game_expansion_details(&self, persona_id: u64) -> Result<Vec<PersonaGameExpansionDetails>, sqlx::Error> {
+        SqliteContext::get_persona_game_expansion_details(self, persona_id).await
+    }
+
+    async fn get_game_expansion_ownership(&self) -> Result<Vec<GameExpansionOwnership>, sqlx::Error> {
+        SqliteContext::get_game_expansion_ownership(self).await
+    }
+
+    async fn get_server_game_expansion_share(&self, server_id: i32, since: u32) -> Result<ServerGameExpansionShare, sqlx::Error> {
+        SqliteContext::get_server_game_expansion_share(self, server_id, since).await
+    }
+
+    async fn get_clan_roster(&self, clan_tag: &str) -> Result<Vec<PersonaSearchResult>, sqlx::Error> {
+        SqliteContext::get_clan_roster(self, clan_tag).await
+    }
+
+    async fn get_clan_stats(&self, clan_tag: &str, server_id: Option<i32>) -> Result<ClanStats, sqlx::Error> {
+        SqliteContext::get_clan_stats(self, clan_tag, server_id).await
+    }
+
+    async fn get_clan_activity(&self, clan_tag: &str, since: u32) -> Result<Vec<ClanActivity>, sqlx::Error> {
+        SqliteContext::get_clan_activity(self, clan_tag, since).await
+    }
+
+    async fn get_clan_leaderboard(&self, server_id: i32, since: u32, min_rounds: u32) -> Result<Vec<ClanStats>, sqlx::Error> {
+        SqliteContext::get_clan_leaderboard(self, server_id, since, min_rounds).await
+    }
+
+    async fn get_latest_loadout_snapshot(&self, persona_id: u64) -> Result<Option<LoadoutSnapshot>, sqlx::Error> {
+        SqliteContext::get_latest_loadout_snapshot(self, persona_id).await
+    }
+
+    async fn get_loadout_snapshots(&self, persona_id: u64, limit: u32) -> Result<Vec<LoadoutSnapshot>, sqlx::Error> {
+        SqliteContext::get_loadout_snapshots(self, persona_id, limit).await
+    }
+
+    async fn insert_loadout_snapshot(&self, snapshot: &LoadoutSnapshot) -> anyhow::Result<u64> {
+        SqliteContext::insert_loadout_snapshot(self, snapshot).await
+    }
+
+    async fn get_cached_response(&self, cache_key: &str) -> Result<Option<CachedResponseRow>, sqlx::Error> {
+        SqliteContext::get_cached_response(self, cache_key).await
+    }
+
+    async fn upsert_cached_response(&self, row: &CachedResponseRow) -> anyhow::Result<bool> {
+        SqliteContext::upsert_cached_response(self, row).await
+    }
+
+    async fn delete_expired_cached_responses(&self, now: u32) -> anyhow::Result<u64> {
+        SqliteContext::delete_expired_cached_responses(self, now).await
+    }
+
+    async fn get_battlereport_archive(&self, report_id: u64) -> Result<Option<BattlereportArchive>, sqlx::Error> {
+        SqliteContext::get_battlereport_archive(self, report_id).await
+    }
+
+    async fn get_playerreport_archives(&self, report_id: u64) -> Result<Vec<PlayerreportArchive>, sqlx::Error> {
+        SqliteContext::get_playerreport_archives(self, report_id).await
+    }
+
+    async fn get_battlereport_archive_ids(&self, from: Option<u32>, to: Option<u32>, server_id: Option<i32>) -> Result<Vec<u64>, sqlx::Error> {
+        SqliteContext::get_battlereport_archive_ids(self, from, to, server_id).await
+    }
+
+    async fn get_export_rows(&self, filter: &ExportFilter, after: (u64, u64), limit: u32) -> Result<Vec<ExportRow>, sqlx::Error> {
+        SqliteContext::get_export_rows(self, filter, after, limit).await
+    }
+
+    async fn get_backfill_cursor(&self, persona_id: u64) -> Result<Option<BackfillCursor>, sqlx::Error> {
+        SqliteContext::get_backfill_cursor(self, persona_id).await
+    }
+
+    async fn upsert_backfill_cursor(&self, cursor: &BackfillCursor) -> anyhow::Result<bool> {
+        SqliteContext::upsert_backfill_cursor(self, cursor).await
+    }
+}
+
+pub struct SqliteTransaction<'c> {
+    db: &'c SqliteContext,
+    transaction: Transaction<'c, Sqlite>,
+}
+
+#[async_trait]
+impl<'c> StoreTransaction for SqliteTransaction<'c> {
+    async fn savepoint(&mut self) -> anyhow::Result<Box<dyn StoreTransaction + '_>> {
+        let transaction = self.transaction.begin().await?;
+        Ok(Box::new(SqliteTransaction { db: self.db, transaction }))
+    }
+
+    async fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.transaction.commit().await?)
+    }
+
+    async fn rollback(self: Box<Self>) -> anyhow::Result<()> {
+        Ok(self.transaction.rollback().await?)
+    }
+
+    async fn get_persona_by_persona_id(&mut self, persona_id: u64) -> Result<Option<BattlelogPersona>, sqlx::Error> {
+        self.db.get_persona_by_persona_id_with_transaction(&mut self.transaction, persona_id).await
+    }
+
+    async fn get_persona_info_by_id(&mut self, persona_id: u64) -> Result<Option<PersonaInfo>, sqlx::Error> {
+        self.db.get_persona_info_by_id_with_transaction(&mut self.transaction, persona_id).await
+    }
+
+    async fn upsert_persona(&mut self, persona: &BattlelogPersona, seen_at: u32) -> anyhow::Result<u64> {
+        self.db.upsert_persona(&mut self.transaction, persona, seen_at).await
+    }
+
+    async fn update_persona(&mut self, persona: &BattlelogPersona) -> anyhow::Result<bool> {
+        self.db.update_persona(&mut self.transaction, persona).await
+    }
+
+    async fn update_persona_last_updated(&mut self, persona_id: u64, last_updated: u32) -> anyhow::Result<bool> {
+        self.db.update_persona_last_updated(&mut self.transaction, persona_id, last_updated).await
+    }
+
+    async fn upsert_persona_name_history(&mut self, p: &PersonaNameHistory) -> anyhow::Result<u64> {
+        self.db.upsert_persona_name_history(&mut self.transaction, p).await
+    }
+
+    async fn upsert_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<u64> {
+        self.db.upsert_persona_info(&mut self.transaction, p).await
+    }
+
+    async fn update_persona_info(&mut self, p: &PersonaInfo) -> anyhow::Result<bool> {
+        self.db.update_persona_info(&mut self.transaction, p).await
+    }
+
+    async fn upsert_persona_game_expansion(&mut self, p: &PersonaGameExpansion) -> anyhow::Result<u64> {
+        self.db.upsert_persona_game_expansion(&mut self.transaction, p).await
+    }
+
+    async fn upsert_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        self.db.upsert_battlereport(&mut self.transaction, battlereport).await
+    }
+
+    async fn replace_battlereport(&mut self, battlereport: &BattlelogBattlereport) -> anyhow::Result<bool> {
+        self.db.replace_battlereport(&mut self.transaction, battlereport).await
+    }
+
+    async fn upsert_playerreport(&mut self, playerreport: &BattlelogPlayerreport) -> anyhow::Result<bool> {
+        self.db.upsert_playerreport(&mut self.transaction, playerreport).await
+    }
+
+    async fn upsert_battlereport_archive(&mut self, archive: &BattlereportArchive) -> anyhow::Result<bool> {
+        self.db.upsert_battlereport_archive(&mut self.transaction, archive).await
+    }
+
+    async fn upsert_playerreport_archive(&mut self, archive: &PlayerreportArchive) -> anyhow::Result<bool> {
+        self.db.upsert_playerreport_archive(&mut self.transaction, archive).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn memory_store() -> SqliteContext {
+        SqliteContext::connect("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_persona_upsert_keeps_known_values() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), Some("PLT".to_string()), Some("0123456789abcdef".to_string()), true, None), 1598211430).await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, None, None, None, false, None), 1598211430).await?;
+        transaction.commit().await?;
+
+        let persona = db.get_persona_by_persona_id(824078704).await?.unwrap();
+        assert_eq!(persona.name.as_deref(), Some("Tatarek99"));
+        assert_eq!(persona.clan_tag.as_deref(), Some("PLT"));
+        assert_eq!(persona.gravatar_md5.as_deref(), Some("0123456789abcdef"));
+        assert!(persona.processed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persona_upsert_keeps_latest_clan_tag() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), Some("PLT".to_string()), None, true, None), 1598211430).await?;
+        // An older report, e.g. from a re-imported BRR file, doesn't bring back the old tag
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), Some("OLD".to_string()), None, true, None), 1500000000).await?;
+        transaction.commit().await?;
+        assert_eq!(db.get_persona_by_persona_id(824078704).await?.unwrap().clan_tag.as_deref(), Some("PLT"));
+
+        // Leaving the clan shows up as a missing tag in a newer report
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), None, None, true, None), 1600000000).await?;
+        transaction.commit().await?;
+        assert_eq!(db.get_persona_by_persona_id(824078704).await?.unwrap().clan_tag, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_reads_own_writes() -> anyhow::Result<()> {
+        // The in-memory pool has a single connection, a read from the pool would wait for the transaction
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), None, None, true, None), 1598211430).await?;
+        transaction.upsert_persona_info(&PersonaInfo::new(824078704, Some("Helsinki".to_string()), None, None, Some(3), None)).await?;
+
+        let mut savepoint = transaction.savepoint().await?;
+        assert_eq!(savepoint.get_persona_by_persona_id(824078704).await?.unwrap().name.as_deref(), Some("Tatarek99"));
+        assert_eq!(savepoint.get_persona_info_by_id(824078704).await?.unwrap().login_counter, Some(3));
+        assert!(savepoint.get_persona_by_persona_id(1).await?.is_none());
+        savepoint.rollback().await?;
+        transaction.rollback().await?;
+
+        assert!(db.get_persona_by_persona_id(824078704).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_game_expansion_upsert_replaces_platforms() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona_game_expansion(&PersonaGameExpansion::new(824078704, 2048, 1)).await?;
+        transaction.upsert_persona_game_expansion(&PersonaGameExpansion::new(824078704, 2048, 3)).await?;
+        transaction.upsert_persona_info(&PersonaInfo::new(824078704, None, None, None, Some(3), None)).await?;
+        transaction.upsert_persona_info(&PersonaInfo::new(824078704, None, None, None, Some(4), None)).await?;
+        transaction.commit().await?;
+
+        let expansions = db.get_persona_game_expansion_details(824078704).await?;
+        assert_eq!(expansions.len(), 1);
+        assert_eq!(expansions[0].platforms, 3);
+        assert_eq!(expansions[0].explanation.as_deref(), Some("Battlefield 4"));
+        assert_eq!(db.get_persona_info_by_id(824078704).await?.unwrap().login_counter, Some(4));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replace_battlereport_overwrites() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let server_id = db.insert_server(&BattlelogServer::new("LSD".to_string(), "4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string())).await? as i32;
+
+        let mut transaction = db.begin().await?;
+        transaction.upsert_battlereport(&BattlelogBattlereport::new(1297613665940962880, 1261, 1, server_id, "MP_Tremors".to_string(), "2".to_string(), 1598211430, 1)).await?;
+        // Rebuilding can lower the duration and correct the map, the processed flag stays
+        transaction.replace_battlereport(&BattlelogBattlereport::new(1297613665940962880, 1000, 2, server_id, "MP_Prison".to_string(), "2".to_string(), 1598211430, 0)).await?;
+        transaction.commit().await?;
+
+        let battlereport = db.get_battlereport_by_report_id(1297613665940962880).await?.unwrap();
+        assert_eq!(battlereport.duration, 1000);
+        assert_eq!(battlereport.winner, 2);
+        assert_eq!(battlereport.map, "MP_Prison");
+        assert_eq!(battlereport.processed, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_battlereport_roundtrip() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let server_id = db.insert_server(&BattlelogServer::new("LSD".to_string(), "4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string())).await? as i32;
+
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), None, None, true, None), 1598211430).await?;
+        transaction.upsert_battlereport(&BattlelogBattlereport::new(1297613665940962880, 1261, 1, server_id, "MP_Tremors".to_string(), "2".to_string(), 1598211430, 1)).await?;
+        // A partial re-import must not reset the processed flag or the known map
+        transaction.upsert_battlereport(&BattlelogBattlereport::new(1297613665940962880, 1000, -1, server_id, "".to_string(), "2".to_string(), 1598211430, 0)).await?;
+        transaction.upsert_playerreport(&BattlelogPlayerreport::new(1297613665940962880, 824078704,
+            12, 3, 1.0, 29.0, 0, 0, 450, 4.0, 0, 0, 3,
+            0, 0, 0, 0, 0, 0, 38, 0, 0, 0, 0, 0, 0, 0, 0, 38, 0, 0,
+            0, 0, 1, 0, 0, 0.0344827586, false, false, true)).await?;
+        transaction.commit().await?;
+
+        let battlereport = db.get_battlereport_by_report_id(1297613665940962880).await?.unwrap();
+        assert_eq!(battlereport.duration, 1261);
+        assert_eq!(battlereport.winner, 1);
+        assert_eq!(battlereport.map, "MP_Tremors");
+        assert_eq!(battlereport.processed, 1);
+
+        let playerreport = db.get_playerreport_by_report_id_and_persona_id(1297613665940962880, 824078704).await?.unwrap();
+        assert_eq!(playerreport.kills, 12);
+        assert!(playerreport.is_soldier);
+
+        let stats = db.get_persona_stats(824078704).await?;
+        assert_eq!(stats.rounds, 1);
+        assert_eq!(stats.wins, 1);
+        assert_eq!(stats.kills, 12);
+        assert_eq!(stats.seconds_played, 1261);
+
+        let rounds = db.get_persona_recent_rounds(824078704, 10).await?;
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].server_name, "LSD");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_name_history_widens_seen_range() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona_name_history(&PersonaNameHistory::new(824078704, "Tatarek99".to_string(), None, 200, 200)).await?;
+        transaction.upsert_persona_name_history(&PersonaNameHistory::new(824078704, "Tatarek99".to_string(), None, 100, 150)).await?;
+        transaction.upsert_persona_name_history(&PersonaNameHistory::new(824078704, "Tatarek99".to_string(), Some("PLT".to_string()), 300, 300)).await?;
+        transaction.commit().await?;
+
+        let history = db.get_persona_name_history(824078704).await?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].clan_tag.as_deref(), Some("PLT"));
+        assert_eq!(history[1].clan_tag, None);
+        assert_eq!((history[1].first_seen, history[1].last_seen), (100, 200));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_savepoint_rollback_keeps_transaction() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        transaction.upsert_persona(&BattlelogPersona::new(1, Some("Kept".to_string()), None, None, true, None), 1598211430).await?;
+
+        let mut savepoint = transaction.savepoint().await?;
+        savepoint.upsert_persona(&BattlelogPersona::new(2, Some("Discarded".to_string()), None, None, true, None), 1598211430).await?;
+        savepoint.rollback().await?;
+        transaction.commit().await?;
+
+        assert!(db.get_persona_by_persona_id(1).await?.is_some());
+        assert!(db.get_persona_by_persona_id(2).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backfill_cursor_upsert_replaces_progress() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut cursor = BackfillCursor::new(824078704, 1666170000, 1600000000);
+        db.upsert_backfill_cursor(&cursor).await?;
+
+        cursor.timestamp = 1666160000;
+        cursor.reports_ingested = 2;
+        cursor.completed = true;
+        db.upsert_backfill_cursor(&cursor).await?;
+
+        let stored = db.get_backfill_cursor(824078704).await?.unwrap();
+        assert_eq!(stored.timestamp, 1666160000);
+        assert_eq!(stored.cutoff, 1600000000);
+        assert_eq!(stored.reports_ingested, 2);
+        assert!(stored.completed);
+        assert!(db.get_backfill_cursor(1).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clan_stats_count_rounds_with_the_tag() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let server_id = db.insert_server(&BattlelogServer::new("LSD".to_string(), "4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string())).await? as i32;
+
+        let mut transaction = db.begin().await?;
+        // Played the first round before joining STB
+        for (report_id, created_at, clan_tag) in [(1, 100, None), (2, 200, Some("STB".to_string())), (3, 300, Some("STB".to_string()))] {
+            let persona = BattlelogPersona::new(824078704, Some("Tatarek99".to_string()), clan_tag, None, true, None);
+            transaction.upsert_persona(&persona, created_at).await?;
+            transaction.upsert_persona_name_history(&PersonaNameHistory::from_persona(&persona, created_at).unwrap()).await?;
+            transaction.upsert_battlereport(&BattlelogBattlereport::new(report_id, 1200, 1, server_id, "MP_Tremors".to_string(), "2".to_string(), created_at, 1)).await?;
+            transaction.upsert_playerreport(&BattlelogPlayerreport::new(report_id, 824078704,
+                10, 5, 0.0, 0.0, 0, 0, 300, 2.0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 1, 0, 0, 0.0, false, false, true)).await?;
+        }
+        transaction.commit().await?;
+
+        let stats = db.get_clan_stats("STB", None).await?;
+        assert_eq!(stats.members, 1);
+        assert_eq!(stats.rounds, 2);
+        assert_eq!(stats.kills, 20);
+
+        let leaderboard = db.get_clan_leaderboard(server_id, 0, 1).await?;
+        assert_eq!(leaderboard.len(), 1);
+        assert_eq!(leaderboard[0].rounds, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_co_players_share_a_round() -> anyhow::Result<()> {
+        let db = memory_store().await;
+        let mut transaction = db.begin().await?;
+        for (report_id, persona_id) in [(1, 824078704), (1, 1000000001), (1, 1000000002), (2, 1000000003)] {
+            transaction.upsert_playerreport(&BattlelogPlayerreport::new(report_id, persona_id,
+                0, 0, 0.0, 0.0, 0, 0, 0, 0.0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 1, 0, 0, 0.0, false, false, true)).await?;
+        }
+        transaction.commit().await?;
+
+        assert_eq!(db.get_co_player_persona_ids(824078704).await?, vec![1000000001, 1000000002]);
+        assert!(db.get_co_player_persona_ids(1000000003).await?.is_empty());
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
+use std::sync::Mutex;
+
 use chrono::{Utc};
+use tokio::{sync::mpsc, task::JoinHandle};
 use webhook::client::{WebhookClient, WebhookResult};
 
+use crate::metrics::{DISCORD_MESSAGES_FAILED, DISCORD_MESSAGES_SENT};
+
 pub async fn send_message_webhook(webhook_url: &str, soldier_name: &str, chat_message: &str) -> WebhookResult<()> {
     let message_time = Utc::now();
 
@@ -22,17 +27,104 @@ pub async fn send_message_webhook(webhook_url: &str, soldier_name: &str, chat_me
     Ok(())
 }
 
+struct DiscordMessage {
+    soldier_name: String,
+    chat_message: String,
+}
+
+/// Posts the messages to the webhook one at a time in the background, `flush` waits for the
+/// queued messages to be sent so none are lost on shutdown.
+pub struct DiscordQueue {
+    sender: Mutex<Option<mpsc::UnboundedSender<DiscordMessage>>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DiscordQueue {
+    pub fn new(webhook_url: String) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<DiscordMessage>();
+        let worker = tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                match send_message_webhook(&webhook_url, &message.soldier_name, &message.chat_message).await {
+                    Ok(()) => DISCORD_MESSAGES_SENT.inc(),
+                    Err(err) => {
+                        DISCORD_MESSAGES_FAILED.inc();
+                        error!("Failed to post message of {} to Discord: {}", message.soldier_name, err);
+                    },
+                }
+            }
+        });
+
+        Self {
+            sender: Mutex::new(Some(sender)),
+            worker: Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Queue posting to the `DISCORD_WEBHOOK`, `None` if it isn't set.
+    pub fn from_env() -> Option<Self> {
+        dotenv::var("DISCORD_WEBHOOK").ok().map(DiscordQueue::new)
+    }
+
+    pub fn send(&self, soldier_name: &str, chat_message: &str) {
+        let message = DiscordMessage { soldier_name: soldier_name.to_string(), chat_message: chat_message.to_string() };
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => {
+                if sender.send(message).is_err() {
+                    DISCORD_MESSAGES_FAILED.inc();
+                    error!("Discord queue closed, dropping message of {}", soldier_name);
+                }
+            },
+            None => {
+                DISCORD_MESSAGES_FAILED.inc();
+                warn!("Discord queue already flushed, dropping message of {}", soldier_name);
+            },
+        }
+    }
+
+    /// Stops accepting messages and waits until the queued ones are posted.
+    pub async fn flush(&self) {
+        self.sender.lock().unwrap().take();
+        let worker = self.worker.lock().unwrap().take();
+        if let Some(worker) = worker {
+            if let Err(err) = worker.await {
+                error!("Discord queue worker failed: {}", err);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dotenv::dotenv;
+    use crate::fixture_server::FixtureServer;
 
     #[tokio::test]
     async fn test_webhook_post() {
-        dotenv().ok();
+        let server = FixtureServer::start().await.unwrap();
+
+        send_message_webhook(&server.webhook_url(), "Webhook tester", "Test message").await.unwrap();
+
+        let messages = server.webhook_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["username"], "Webhook tester");
+        assert_eq!(messages[0]["embeds"][0]["description"], "Test message");
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_queue_flush_sends_queued_messages() {
+        let server = FixtureServer::start().await.unwrap();
+        let queue = DiscordQueue::new(server.webhook_url());
+
+        queue.send("First", "First message");
+        queue.send("Second", "Second message");
+        queue.flush().await;
 
-        let webhook_path = dotenv::var("DISCORD_WEBHOOK").unwrap();
+        let messages = server.webhook_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["username"], "Second");
 
-        send_message_webhook(&webhook_path, "Webhook tester", "Test message").await.unwrap();
+        server.stop().await;
     }
 }
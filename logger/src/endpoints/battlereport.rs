@@ -1,22 +1,30 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpResponse};
 use battlelog::{battlereport, BattlereportResponse, playerreport, PlayerreportResponse};
 use serde::{Deserialize, Serialize};
 
-use crate::{round_stats::battlereport::{add_battlereport_by_id, fetch_battlereports_for_user}, database::battlelog::battlereports::BattlelogBattlereport};
+use crate::{battlelog_client::{client::battlelog, endpoint::BattlelogEndpoint}, round_stats::battlereport::{add_battlereport_by_id, fetch_battlereports_for_user, UnusableReport}, database::{battlelog::battlereports::BattlelogBattlereport, store::BattlelogStore}, cache::response_cache::ResponseCache};
+
+use super::cached_json_response;
+use super::error::ApiError;
+
+#[derive(Deserialize)]
+pub struct CacheParams {
+    refresh: Option<bool>,
+}
 
 #[get("/battlereport/{report_id}")]
-pub async fn get_battlereport_by_id(report_id: web::Path<String>) -> impl Responder {
-    
-    match get_battlereport(&report_id).await {
-        Ok(report) => {
-            return HttpResponse::Ok()
-                .content_type(mime::APPLICATION_JSON)
-                .json(report)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
-        },
-    };
+pub async fn get_battlereport_by_id(cache: web::Data<ResponseCache>, report_id: web::Path<String>, params: web::Query<CacheParams>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("report id", &report_id)?;
+
+    let ttl = cache.ttls.battlereport_incomplete;
+    let cached = cache.get_or_fetch(&format!("battlereport/{}", report_id), params.refresh.unwrap_or(false), || async {
+        let report = get_battlereport(&report_id).await.map_err(ApiError::battlelog)?;
+        // Reports are immutable once the round is over and every player is in
+        let ttl = if is_complete(&report) { None } else { Some(ttl) };
+        Ok::<_, anyhow::Error>((serde_json::to_string(&report)?, ttl))
+    }).await?;
+
+    Ok(cached_json_response(cached))
 }
 
 #[derive(Deserialize)]
@@ -26,18 +34,18 @@ pub struct PlayerReportParams {
 }
 
 #[get("/battlereport/{report_id}/{persona_id}")]
-pub async fn get_playerreport_by_id(params: web::Path<PlayerReportParams>) -> impl Responder {
-    
-    match get_playerreport(&params.report_id, &params.persona_id).await {
-        Ok(report) => {
-            return HttpResponse::Ok()
-                .content_type(mime::APPLICATION_JSON)
-                .json(report)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
-        },
-    };
+pub async fn get_playerreport_by_id(cache: web::Data<ResponseCache>, params: web::Path<PlayerReportParams>, cache_params: web::Query<CacheParams>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("report id", &params.report_id)?;
+    ApiError::parse_id("persona id", &params.persona_id)?;
+
+    let ttl = cache.ttls.playerreport;
+    let key = format!("playerreport/{}/{}", params.report_id, params.persona_id);
+    let cached = cache.get_or_fetch(&key, cache_params.refresh.unwrap_or(false), || async {
+        let report = get_playerreport(&params.report_id, &params.persona_id).await.map_err(ApiError::battlelog)?;
+        Ok::<_, anyhow::Error>((serde_json::to_string(&report)?, ttl))
+    }).await?;
+
+    Ok(cached_json_response(cached))
 }
 
 #[derive(Deserialize)]
@@ -47,33 +55,25 @@ pub struct BattlereportsMoreParams {
 }
 
 #[get("/battlereports/{persona_id}/{timestamp}")]
-pub async fn get_battlereports_more(params: web::Path<BattlereportsMoreParams>) -> impl Responder {
-    match fetch_battlereports_for_user(&params.persona_id, &params.timestamp).await {
-        Ok(data) => {
-            return HttpResponse::Ok()
-                .content_type(mime::APPLICATION_JSON)
-                .json(data)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
-        },
-    };
+pub async fn get_battlereports_more(params: web::Path<BattlereportsMoreParams>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("persona id", &params.persona_id)?;
+
+    let data = fetch_battlereports_for_user(&params.persona_id, &params.timestamp).await.map_err(ApiError::battlelog)?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(data))
 }
 
 #[get("/battlereports/text/{persona_id}/{timestamp}")]
-pub async fn get_battlereports_more_text(params: web::Path<BattlereportsMoreParams>) -> impl Responder {
-    match fetch_battlereports_for_user(&params.persona_id, &params.timestamp).await {
-        Ok(data) => {
-            let test: Vec<String> = data.iter().map(|r| format!("{} $ {}", r.game_report_id, r.name)).collect();
-
-            return HttpResponse::Ok()
-                .content_type(mime::APPLICATION_JSON)
-                .json(test)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
-        },
-    };
+pub async fn get_battlereports_more_text(params: web::Path<BattlereportsMoreParams>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("persona id", &params.persona_id)?;
+
+    let data = fetch_battlereports_for_user(&params.persona_id, &params.timestamp).await.map_err(ApiError::battlelog)?;
+    let test: Vec<String> = data.iter().map(|r| format!("{} $ {}", r.game_report_id, r.name)).collect();
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(test))
 }
 
 #[derive(Debug, Serialize)]
@@ -84,25 +84,28 @@ pub struct BattleReportAddingResponse {
 }
 
 #[post("/battlereport/{report_id}")]
-pub async fn post_battlereport_by_id(report_id: web::Path<String>) -> impl Responder {
-    match add_battlereport_by_id(&report_id).await {
-        Ok(report) => {
-            return HttpResponse::Ok()
-                .content_type(mime::APPLICATION_JSON)
-                .json(report)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
-        },
-    };
+pub async fn post_battlereport_by_id(db: web::Data<dyn BattlelogStore>, report_id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("report id", &report_id)?;
+
+    let report = add_battlereport_by_id(&db, &report_id).await.map_err(|err| match err.downcast::<UnusableReport>() {
+        Ok(unusable) => ApiError::ReportUnusable(unusable.0),
+        Err(err) => ApiError::from(err),
+    })?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(report))
 }
 
 async fn get_battlereport(report_id: &str) -> Result<BattlereportResponse, anyhow::Error> {
-    let report = battlereport(report_id).await?;
+    let report = battlelog().request(BattlelogEndpoint::Battlereport { report_id }, || battlereport(report_id)).await?;
     Ok(report)
 }
 
 async fn get_playerreport(report_id: &str, persona_id: &str) -> Result<PlayerreportResponse, anyhow::Error> {
-    let report = playerreport(report_id, persona_id).await?;
+    let report = battlelog().request(BattlelogEndpoint::Playerreport { report_id, persona_id }, || playerreport(report_id, persona_id)).await?;
     Ok(report)
-}
\ No newline at end of file
+}
+
+fn is_complete(report: &BattlereportResponse) -> bool {
+    report.players.is_some() && report.duration as u32 > 0
+}
@@ -0,0 +1,68 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::database::store::BattlelogStore;
+
+use super::days_ago;
+use super::error::ApiError;
+
+#[derive(Deserialize)]
+pub struct ClanStatsParams {
+    server_id: Option<i32>,
+}
+
+/// Stats of the current members of the clan. A persona's rounds count from the first to the last
+/// time it was seen with the tag, rounds from before joining or under another clan are left out.
+#[get("/clans/{clan_tag}")]
+pub async fn get_clan_stats(db: web::Data<dyn BattlelogStore>, clan_tag: web::Path<String>, params: web::Query<ClanStatsParams>) -> Result<HttpResponse, ApiError> {
+    let stats = db.get_clan_stats(&clan_tag, params.server_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(stats))
+}
+
+#[get("/clans/{clan_tag}/roster")]
+pub async fn get_clan_roster(db: web::Data<dyn BattlelogStore>, clan_tag: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let roster = db.get_clan_roster(&clan_tag).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(roster))
+}
+
+#[derive(Deserialize)]
+pub struct ClanActivityParams {
+    days: Option<u32>,
+}
+
+/// Rounds per day of the current members, counted like in `get_clan_stats`.
+#[get("/clans/{clan_tag}/activity")]
+pub async fn get_clan_activity(db: web::Data<dyn BattlelogStore>, clan_tag: web::Path<String>, params: web::Query<ClanActivityParams>) -> Result<HttpResponse, ApiError> {
+    let activity = db.get_clan_activity(&clan_tag, days_ago(params.days.unwrap_or(30))).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(activity))
+}
+
+#[derive(Deserialize)]
+pub struct ClanLeaderboardParams {
+    days: Option<u32>,
+    min_rounds: Option<u32>,
+    sort: Option<String>,
+}
+
+/// Clans of the personas on the server by their current tag, the rounds counted like in `get_clan_stats`.
+#[get("/servers/{server_id}/clans")]
+pub async fn get_clan_leaderboard(db: web::Data<dyn BattlelogStore>, server_id: web::Path<i32>, params: web::Query<ClanLeaderboardParams>) -> Result<HttpResponse, ApiError> {
+    let mut leaderboard = db.get_clan_leaderboard(*server_id, days_ago(params.days.unwrap_or(30)), params.min_rounds.unwrap_or(10)).await?;
+    match params.sort.as_deref() {
+        Some("wins") => leaderboard.sort_by(|a, b| b.wins.cmp(&a.wins)),
+        Some("kd") => leaderboard.sort_by(|a, b| b.kd_ratio.total_cmp(&a.kd_ratio)),
+        Some("spm") => leaderboard.sort_by(|a, b| b.spm.total_cmp(&a.spm)),
+        Some("win_rate") => leaderboard.sort_by(|a, b| b.win_rate.total_cmp(&a.win_rate)),
+        _ => {}, // already ordered by rounds
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(leaderboard))
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use actix_web::{error::{PathError, QueryPayloadError}, http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// Error of an API endpoint. The response carries a stable `error` code and a short message, the
+/// underlying error is only logged.
+#[derive(Debug)]
+pub enum ApiError {
+    /// A path parameter that should be a numeric id isn't one.
+    InvalidId { name: &'static str, value: String },
+    /// A query or path parameter other than an id doesn't parse.
+    InvalidParameter { name: &'static str, value: String },
+    /// A required query parameter was left out, the message names what's needed.
+    MissingParameter(&'static str),
+    NotFound(String),
+    /// Battlelog returned the report but it's missing what's needed to store it.
+    ReportUnusable(String),
+    /// Battlelog failed the request or answered with something unexpected.
+    Battlelog(anyhow::Error),
+    /// Battlelog timed out or couldn't be reached.
+    BattlelogUnavailable(anyhow::Error),
+    Database(sqlx::Error),
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    /// Parses the id of a path parameter, `name` is used in the error message.
+    pub fn parse_id(name: &'static str, value: &str) -> Result<u64, ApiError> {
+        value.parse::<u64>().map_err(|_| ApiError::InvalidId { name, value: value.to_string() })
+    }
+
+    /// Classifies a failed Battlelog request, a 404 from Battlelog means there's no such resource.
+    pub fn battlelog(err: anyhow::Error) -> Self {
+        let (not_found, unavailable) = match err.chain().find_map(|cause| cause.downcast_ref::<reqwest::Error>()) {
+            Some(cause) => (cause.status() == Some(reqwest::StatusCode::NOT_FOUND), cause.is_timeout() || cause.is_connect()),
+            None => (false, err.chain().any(|cause| cause.is::<tokio::time::error::Elapsed>())),
+        };
+
+        if not_found {
+            ApiError::NotFound("Not found from Battlelog".to_string())
+        }
+        else if unavailable {
+            ApiError::BattlelogUnavailable(err)
+        }
+        else {
+            ApiError::Battlelog(err)
+        }
+    }
+
+    /// Stable code of the error, clients can match on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidId { .. } => "invalid_id",
+            ApiError::InvalidParameter { .. } => "invalid_parameter",
+            ApiError::MissingParameter(_) => "missing_parameter",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::ReportUnusable(_) => "report_unusable",
+            ApiError::Battlelog(_) => "battlelog_error",
+            ApiError::BattlelogUnavailable(_) => "battlelog_unavailable",
+            ApiError::Database(err) if is_unavailable(err) => "database_unavailable",
+            ApiError::Database(_) => "database_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+/// Path parameters that don't deserialize, such as a persona id that isn't a number, answer with
+/// the same JSON body as the handlers.
+pub fn path_error(_err: PathError, req: &HttpRequest) -> actix_web::Error {
+    ApiError::InvalidParameter { name: "path", value: req.path().to_string() }.into()
+}
+
+pub fn query_error(_err: QueryPayloadError, req: &HttpRequest) -> actix_web::Error {
+    ApiError::InvalidParameter { name: "query", value: req.query_string().to_string() }.into()
+}
+
+/// The pool couldn't hand out a connection, as opposed to a query that failed.
+fn is_unavailable(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_))
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::InvalidId { name, value } | ApiError::InvalidParameter { name, value } => write!(f, "Invalid {} {}", name, value),
+            ApiError::MissingParameter(name) => write!(f, "Missing {}", name),
+            ApiError::NotFound(message) | ApiError::ReportUnusable(message) => write!(f, "{}", message),
+            ApiError::Battlelog(_) => write!(f, "Battlelog request failed"),
+            ApiError::BattlelogUnavailable(_) => write!(f, "Battlelog is unavailable"),
+            ApiError::Database(err) if is_unavailable(err) => write!(f, "Database is unavailable"),
+            ApiError::Database(_) => write!(f, "Database query failed"),
+            ApiError::Internal(_) => write!(f, "Internal server error"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Battlelog(err) | ApiError::BattlelogUnavailable(err) | ApiError::Internal(err) => Some(err.as_ref()),
+            ApiError::Database(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Errors returned with `?` in the fetches keep their `ApiError`, database errors and Battlelog
+/// requests are recognized from the cause and anything else is internal.
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<ApiError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<sqlx::Error>() {
+            Ok(err) => return ApiError::Database(err),
+            Err(err) => err,
+        };
+
+        if err.chain().any(|cause| cause.is::<reqwest::Error>()) {
+            ApiError::battlelog(err)
+        }
+        else {
+            ApiError::Internal(err)
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        ApiError::Database(err)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidId { .. } | ApiError::InvalidParameter { .. } | ApiError::MissingParameter(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::ReportUnusable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Battlelog(_) => StatusCode::BAD_GATEWAY,
+            ApiError::BattlelogUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(err) if is_unavailable(err) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ApiError::Battlelog(err) | ApiError::BattlelogUnavailable(err) | ApiError::Internal(err) => error!("{}: {:?}", self, err),
+            ApiError::Database(err) => error!("{}: {:?}", self, err),
+            _ => {},
+        }
+
+        HttpResponse::build(self.status_code())
+            .content_type(mime::APPLICATION_JSON)
+            .json(ErrorBody { error: self.code(), message: self.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(ApiError::parse_id("report id", "1554146423587197504").unwrap(), 1554146423587197504);
+
+        let err = ApiError::parse_id("report id", "abc").unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.code(), "invalid_id");
+        assert_eq!(err.to_string(), "Invalid report id abc");
+    }
+
+    #[test]
+    fn test_from_anyhow_keeps_api_error() {
+        let err: anyhow::Error = ApiError::NotFound("No such report".to_string()).into();
+        let err = ApiError::from(err);
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(err.to_string(), "No such report");
+    }
+
+    #[test]
+    fn test_from_anyhow_keeps_unusable_report() {
+        let err: anyhow::Error = ApiError::ReportUnusable("No players in the BattleReport with id: 1".to_string()).into();
+        let err = ApiError::from(err);
+        assert_eq!(err.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(err.code(), "report_unusable");
+        assert_eq!(err.to_string(), "No players in the BattleReport with id: 1");
+    }
+
+    #[test]
+    fn test_from_anyhow_classifies_database_errors() {
+        let err = ApiError::from(anyhow::Error::new(sqlx::Error::PoolTimedOut));
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.code(), "database_unavailable");
+
+        let err = ApiError::from(anyhow::Error::new(sqlx::Error::RowNotFound));
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.code(), "database_error");
+    }
+
+    #[test]
+    fn test_internal_error_hides_details() {
+        let err = ApiError::from(anyhow::anyhow!("Connection string with a password"));
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.code(), "internal_error");
+        assert_eq!(err.to_string(), "Internal server error");
+    }
+}
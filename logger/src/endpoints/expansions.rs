@@ -0,0 +1,36 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::database::store::BattlelogStore;
+
+use super::days_ago;
+use super::error::ApiError;
+
+#[get("/expansions")]
+pub async fn get_expansion_ownership(db: web::Data<dyn BattlelogStore>) -> Result<HttpResponse, ApiError> {
+    let ownership = db.get_game_expansion_ownership().await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(ownership))
+}
+
+#[derive(Deserialize)]
+pub struct ServerExpansionParams {
+    days: Option<u32>,
+}
+
+#[get("/servers/{server_id}/expansions")]
+pub async fn get_server_expansion_share(db: web::Data<dyn BattlelogStore>, server_id: web::Path<i32>, params: web::Query<ServerExpansionParams>) -> Result<HttpResponse, ApiError> {
+    let share = db.get_server_game_expansion_share(*server_id, days_ago(params.days.unwrap_or(30))).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(share))
+}
+
+#[get("/personas/{persona_id}/expansions")]
+pub async fn get_persona_expansions(db: web::Data<dyn BattlelogStore>, persona_id: web::Path<u64>) -> Result<HttpResponse, ApiError> {
+    let expansions = db.get_persona_game_expansion_details(*persona_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(expansions))
+}
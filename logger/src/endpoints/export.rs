@@ -0,0 +1,50 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::database::{battlelog::exports::ExportFilter, store::BattlelogStore};
+use crate::export::{export_stream, ExportFormat};
+
+use super::error::ApiError;
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    server_id: Option<i32>,
+    /// `YYYY-MM-DD` in UTC, inclusive.
+    from: Option<String>,
+    /// `YYYY-MM-DD` in UTC, exclusive.
+    to: Option<String>,
+    map: Option<String>,
+}
+
+impl ExportParams {
+    fn filter(&self) -> Result<ExportFilter, ApiError> {
+        Ok(ExportFilter {
+            server_id: self.server_id,
+            from: self.from.as_deref().map(|date| parse_date("from date", date)).transpose()?,
+            to: self.to.as_deref().map(|date| parse_date("to date", date)).transpose()?,
+            map: self.map.clone(),
+        })
+    }
+}
+
+/// Battlereports joined with their playerreports, personas and servers as `csv`, `jsonl` or
+/// `parquet`, streamed while it's read from the database.
+#[get("/export/battlereports.{format}")]
+pub async fn export_battlereports(db: web::Data<dyn BattlelogStore>, format: web::Path<String>, params: web::Query<ExportParams>) -> Result<HttpResponse, ApiError> {
+    let format = ExportFormat::parse(&format)
+        .map_err(|_| ApiError::InvalidParameter { name: "export format", value: format.to_string() })?;
+    let filter = params.filter()?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"battlereports.{}\"", format.extension())))
+        .streaming(export_stream(db.into_inner(), filter, format)))
+}
+
+fn parse_date(name: &'static str, date: &str) -> Result<u32, ApiError> {
+    let midnight = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| ApiError::InvalidParameter { name, value: date.to_string() })?;
+    Ok(midnight.and_utc().timestamp() as u32)
+}
@@ -1,6 +1,29 @@
 use actix_web::{get, HttpResponse, Responder};
 
+use crate::battlelog_client::client::battlelog;
+use crate::metrics;
+
+use super::error::ApiError;
+
 #[get("/health")]
 pub async fn check() -> impl Responder {
     HttpResponse::Ok().body("Healthy")
 }
+
+/// Request counts, failures, retries and latencies of the Battlelog requests per endpoint.
+#[get("/health/battlelog")]
+pub async fn battlelog_metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(battlelog().metrics())
+}
+
+/// Counters and histograms of the ingestion, Battlelog, database, RCON and Discord in the
+/// Prometheus text format.
+#[get("/metrics")]
+pub async fn prometheus_metrics() -> Result<HttpResponse, ApiError> {
+    let body = metrics::render().map_err(ApiError::Internal)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
+}
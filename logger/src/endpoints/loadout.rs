@@ -1,7 +1,14 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse};
 use serde::{Deserialize};
 
+use crate::cache::response_cache::ResponseCache;
+use crate::database::store::BattlelogStore;
+use crate::loadout::loadout_cache::LoadoutCache;
 use crate::loadout::loadout_checker::get_user_loadout;
+use crate::loadout::loadout_history::get_loadout_history;
+
+use super::cached_json_response;
+use super::error::ApiError;
 
 
 #[derive(Deserialize)]
@@ -10,17 +17,49 @@ pub struct LoadoutParams {
     persona_id: String,
 }
 
+#[derive(Deserialize)]
+pub struct LoadoutCacheParams {
+    refresh: Option<bool>,
+}
+
 #[get("/loadout/{soldier_name}/{persona_id}")]
-pub async fn get_persona_loadout(params: web::Path<LoadoutParams>) -> impl Responder {
-    
-    match get_user_loadout(&params.soldier_name, &params.persona_id).await {
-        Ok(report) => {
-            return HttpResponse::Ok()
+pub async fn get_persona_loadout(db: web::Data<dyn BattlelogStore>, cache: web::Data<ResponseCache>, params: web::Path<LoadoutParams>, cache_params: web::Query<LoadoutCacheParams>) -> Result<HttpResponse, ApiError> {
+    ApiError::parse_id("persona id", &params.persona_id)?;
+
+    let ttl = cache.ttls.loadout;
+    let key = format!("loadout/{}/{}", params.soldier_name, params.persona_id);
+    let cached = cache.get_or_fetch(&key, cache_params.refresh.unwrap_or(false), || async {
+        let loadout = get_user_loadout(&db, &params.soldier_name, &params.persona_id).await.map_err(ApiError::battlelog)?;
+        Ok::<_, anyhow::Error>((serde_json::to_string(&loadout)?, Some(ttl)))
+    }).await?;
+
+    Ok(cached_json_response(cached))
+}
+
+#[derive(Deserialize)]
+pub struct LoadoutHistoryParams {
+    limit: Option<u32>,
+}
+
+#[get("/personas/{persona_id}/loadouts")]
+pub async fn get_persona_loadout_history(db: web::Data<dyn BattlelogStore>, persona_id: web::Path<u64>, params: web::Query<LoadoutHistoryParams>) -> Result<HttpResponse, ApiError> {
+    let history = get_loadout_history(&db, *persona_id, params.limit.unwrap_or(20).min(100)).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(history))
+}
+
+#[get("/servers/{server_id}/loadouts")]
+pub async fn get_server_loadouts(db: web::Data<dyn BattlelogStore>, cache: web::Data<LoadoutCache>, server_id: web::Path<i32>) -> Result<HttpResponse, ApiError> {
+    let server = db.get_server_by_server_id(*server_id).await?;
+
+    // Only the server we're connected to over RCON has its players cached
+    match server {
+        Some(server) if cache.server_guid() == Some(server.guid.as_str()) => {
+            Ok(HttpResponse::Ok()
                 .content_type(mime::APPLICATION_JSON)
-                .json(report)
-        },
-        Err(err) => {
-            return HttpResponse::InternalServerError().body(format!("Error {:?}", err))
+                .json(cache.summary()))
         },
-    };
+        _ => Err(ApiError::NotFound(format!("Loadouts of server {} are not tracked", server_id))),
+    }
 }
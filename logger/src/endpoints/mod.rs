@@ -1,3 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::HttpResponse;
+
+use crate::cache::response_cache::CachedResponse;
+
 pub mod battlereport;
+pub mod error;
 pub mod health;
-pub mod loadout;
\ No newline at end of file
+pub mod loadout;
+pub mod personas;
+pub mod clans;
+pub mod expansions;
+pub mod export;
+
+/// Unix timestamp of the moment `days` days ago, used for the `days` query parameters.
+fn days_ago(days: u32) -> u32 {
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    time_now.saturating_sub(days as u64 * 24 * 60 * 60) as u32
+}
+
+/// JSON response with the cache status in `X-Cache` and the seconds since it was fetched in `Age`.
+fn cached_json_response(cached: CachedResponse) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .insert_header(("X-Cache", cached.status.header_value()))
+        .insert_header(("Age", cached.age().to_string()))
+        .body(cached.body)
+}
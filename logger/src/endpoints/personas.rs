@@ -0,0 +1,57 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+
+use crate::{database::store::BattlelogStore, persona::persona_profile::get_persona_profile};
+
+use super::error::ApiError;
+
+#[derive(Deserialize)]
+pub struct PersonaSearchParams {
+    name: Option<String>,
+    clan: Option<String>,
+    limit: Option<u32>,
+}
+
+#[get("/personas")]
+pub async fn search_personas(db: web::Data<dyn BattlelogStore>, params: web::Query<PersonaSearchParams>) -> Result<HttpResponse, ApiError> {
+    let name = params.name.as_deref().filter(|name| !name.is_empty());
+    let clan = params.clan.as_deref().filter(|clan| !clan.is_empty());
+    if name.is_none() && clan.is_none() {
+        return Err(ApiError::MissingParameter("name or clan"));
+    }
+
+    let personas = db.search_personas(name, clan, params.limit.unwrap_or(50).min(500)).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(personas))
+}
+
+#[get("/personas/{persona_id}/names")]
+pub async fn get_persona_names(db: web::Data<dyn BattlelogStore>, persona_id: web::Path<u64>) -> Result<HttpResponse, ApiError> {
+    let history = db.get_persona_name_history(*persona_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(history))
+}
+
+#[get("/personas/by-name/{name}")]
+pub async fn get_personas_by_name(db: web::Data<dyn BattlelogStore>, name: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let history = db.get_persona_name_history_by_name(&name).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(history))
+}
+
+#[derive(Deserialize)]
+pub struct PersonaProfileParams {
+    refresh: Option<bool>,
+}
+
+#[get("/personas/{persona_id}")]
+pub async fn get_persona(db: web::Data<dyn BattlelogStore>, persona_id: web::Path<u64>, params: web::Query<PersonaProfileParams>) -> Result<HttpResponse, ApiError> {
+    let profile = get_persona_profile(&db, *persona_id, params.refresh.unwrap_or(false)).await?
+        .ok_or_else(|| ApiError::NotFound(format!("Persona {} not found", persona_id)))?;
+    Ok(HttpResponse::Ok()
+        .content_type(mime::APPLICATION_JSON)
+        .json(profile))
+}
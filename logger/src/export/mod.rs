@@ -0,0 +1,133 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use futures::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::battlelog::exports::ExportFilter;
+use crate::database::store::{self, BattlelogStore};
+use crate::round_stats::battlereport::get_db_coninfo;
+use crate::round_stats::reprocess::env_date;
+
+use self::writer::{CsvRowWriter, JsonLinesRowWriter, ParquetRowWriter, RowWriter};
+
+pub mod writer;
+
+/// Rows read from the database at a time, a Parquet export gets a row group per page.
+const PAGE_SIZE: u32 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ExportFormat::JsonLines),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(anyhow::anyhow!("Unknown export format {}, expected csv, jsonl or parquet", value)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::JsonLines => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn row_writer(&self) -> anyhow::Result<Box<dyn RowWriter>> {
+        Ok(match self {
+            ExportFormat::Csv => Box::new(CsvRowWriter::new()),
+            ExportFormat::JsonLines => Box::new(JsonLinesRowWriter),
+            ExportFormat::Parquet => Box::new(ParquetRowWriter::new()?),
+        })
+    }
+}
+
+/// Reads `EXPORT_SERVER_ID`, `EXPORT_MAP`, and `EXPORT_FROM` and `EXPORT_TO` as `YYYY-MM-DD`
+/// dates in UTC.
+pub fn export_filter_from_env() -> anyhow::Result<ExportFilter> {
+    Ok(ExportFilter {
+        server_id: match dotenv::var("EXPORT_SERVER_ID") {
+            Ok(server_id) => Some(server_id.parse::<i32>()?),
+            Err(_) => None,
+        },
+        from: env_date("EXPORT_FROM")?,
+        to: env_date("EXPORT_TO")?,
+        map: dotenv::var("EXPORT_MAP").ok(),
+    })
+}
+
+/// The encoded export, read from the database a page at a time so only a page of rows is held
+/// in memory however large the export is.
+pub fn export_stream(db: Arc<dyn BattlelogStore>, filter: ExportFilter, format: ExportFormat) -> impl Stream<Item = anyhow::Result<Bytes>> {
+    let state = format.row_writer().map(|writer| (writer, (0, 0)));
+    futures::stream::unfold(Some(state), move |state| {
+        let db = db.clone();
+        let filter = filter.clone();
+        async move {
+            let (mut writer, after) = match state? {
+                Ok(state) => state,
+                Err(err) => return Some((Err(err), None)),
+            };
+
+            let rows = match db.get_export_rows(&filter, after, PAGE_SIZE).await {
+                Ok(rows) => rows,
+                Err(err) => return Some((Err(err.into()), None)),
+            };
+
+            match rows.last() {
+                Some(last) => {
+                    let after = (last.report_id, last.persona_id);
+                    let chunk = writer.write_rows(&rows).map(Bytes::from);
+                    let next = chunk.is_ok().then(|| Ok((writer, after)));
+                    Some((chunk, next))
+                },
+                None => Some((writer.finish().map(Bytes::from), None)),
+            }
+        }
+    })
+}
+
+/// Writes the rows of the `EXPORT_*` filter to `path`.
+pub async fn export_to_file(path: &str, format: ExportFormat, filter: ExportFilter, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    let mut bytes_written = 0;
+    let stream = export_stream(db.clone(), filter, format);
+    futures::pin_mut!(stream);
+    while let Some(chunk) = stream.next().await {
+        if shutdown.is_cancelled() {
+            warn!("Stopping the export after {} bytes, {} is incomplete", bytes_written, path);
+            break;
+        }
+
+        let chunk = chunk?;
+        file.write_all(&chunk)?;
+        bytes_written += chunk.len();
+    }
+    file.flush()?;
+    info!("Exported {} bytes to {}", bytes_written, path);
+
+    db.close().await;
+
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::schema::parser::parse_message_type;
+
+use crate::database::battlelog::exports::ExportRow;
+
+/// Encodes export rows a page at a time, the returned bytes are appended to the output as is.
+pub trait RowWriter: Send {
+    fn write_rows(&mut self, rows: &[ExportRow]) -> anyhow::Result<Vec<u8>>;
+    /// Bytes ending the output once every row is written.
+    fn finish(&mut self) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Comma separated values with a header line before the first row.
+pub struct CsvRowWriter {
+    wrote_header: bool,
+}
+
+impl CsvRowWriter {
+    pub fn new() -> Self {
+        Self { wrote_header: false }
+    }
+}
+
+impl RowWriter for CsvRowWriter {
+    fn write_rows(&mut self, rows: &[ExportRow]) -> anyhow::Result<Vec<u8>> {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(!self.wrote_header)
+            .from_writer(Vec::new());
+        for row in rows {
+            writer.serialize(row)?;
+        }
+        self.wrote_header |= !rows.is_empty();
+
+        Ok(writer.into_inner().map_err(|err| anyhow!("Failed to write the CSV rows: {}", err))?)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A JSON object per line.
+pub struct JsonLinesRowWriter;
+
+impl RowWriter for JsonLinesRowWriter {
+    fn write_rows(&mut self, rows: &[ExportRow]) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        for row in rows {
+            serde_json::to_writer(&mut buffer, row)?;
+            buffer.push(b'\n');
+        }
+
+        Ok(buffer)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+const PARQUET_SCHEMA: &str = "
+message export_row {
+    REQUIRED INT64 report_id (INTEGER(64, false));
+    REQUIRED INT64 created_at;
+    REQUIRED INT64 duration;
+    REQUIRED BYTE_ARRAY map (UTF8);
+    REQUIRED BYTE_ARRAY mode (UTF8);
+    REQUIRED INT32 winner;
+    REQUIRED INT32 server_id;
+    REQUIRED BYTE_ARRAY server_name (UTF8);
+    REQUIRED BYTE_ARRAY server_guid (UTF8);
+    REQUIRED INT64 persona_id (INTEGER(64, false));
+    OPTIONAL BYTE_ARRAY persona_name (UTF8);
+    OPTIONAL BYTE_ARRAY clan_tag (UTF8);
+    REQUIRED INT32 team;
+    REQUIRED INT32 squad_id;
+    REQUIRED INT64 kills;
+    REQUIRED INT64 deaths;
+    REQUIRED INT64 assists;
+    REQUIRED FLOAT kd_ratio;
+    REQUIRED INT64 spm;
+    REQUIRED INT32 skill;
+    REQUIRED FLOAT shots_hit;
+    REQUIRED FLOAT shots_fired;
+    REQUIRED FLOAT accuracy_detailed;
+    REQUIRED INT32 heals;
+    REQUIRED INT32 revives;
+    REQUIRED INT32 kill_streak;
+    REQUIRED INT64 vehicle_destroyed;
+    REQUIRED INT64 total;
+    REQUIRED BOOLEAN dnf;
+    REQUIRED BOOLEAN is_commander;
+    REQUIRED BOOLEAN is_soldier;
+}
+";
+
+/// Buffer the Parquet writer writes into, drained after every row group so the file can be
+/// streamed while it's written.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Parquet with a row group per page of rows.
+pub struct ParquetRowWriter {
+    buffer: SharedBuffer,
+    writer: Option<SerializedFileWriter<SharedBuffer>>,
+}
+
+impl ParquetRowWriter {
+    pub fn new() -> anyhow::Result<Self> {
+        let buffer = SharedBuffer::default();
+        let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        let writer = SerializedFileWriter::new(buffer.clone(), schema, properties)?;
+
+        Ok(Self { buffer, writer: Some(writer) })
+    }
+}
+
+impl RowWriter for ParquetRowWriter {
+    fn write_rows(&mut self, rows: &[ExportRow]) -> anyhow::Result<Vec<u8>> {
+        if rows.is_empty() {
+            return Ok(self.buffer.take());
+        }
+
+        let writer = self.writer.as_mut().ok_or_else(|| anyhow!("Parquet export already finished"))?;
+        let mut row_group = writer.next_row_group()?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.report_id as i64).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.created_at as i64).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.duration as i64).collect())?;
+        write_column::<ByteArrayType>(&mut row_group, rows.iter().map(|e| ByteArray::from(e.map.as_str())).collect())?;
+        write_column::<ByteArrayType>(&mut row_group, rows.iter().map(|e| ByteArray::from(e.mode.as_str())).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.winner as i32).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.server_id).collect())?;
+        write_column::<ByteArrayType>(&mut row_group, rows.iter().map(|e| ByteArray::from(e.server_name.as_str())).collect())?;
+        write_column::<ByteArrayType>(&mut row_group, rows.iter().map(|e| ByteArray::from(e.server_guid.as_str())).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.persona_id as i64).collect())?;
+        write_optional_string(&mut row_group, rows.iter().map(|e| e.persona_name.as_deref()))?;
+        write_optional_string(&mut row_group, rows.iter().map(|e| e.clan_tag.as_deref()))?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.team as i32).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.squad_id as i32).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.kills as i64).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.deaths as i64).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.assists as i64).collect())?;
+        write_column::<FloatType>(&mut row_group, rows.iter().map(|e| e.kd_ratio).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.spm as i64).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.skill).collect())?;
+        write_column::<FloatType>(&mut row_group, rows.iter().map(|e| e.shots_hit).collect())?;
+        write_column::<FloatType>(&mut row_group, rows.iter().map(|e| e.shots_fired).collect())?;
+        write_column::<FloatType>(&mut row_group, rows.iter().map(|e| e.accuracy_detailed).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.heals as i32).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.revives as i32).collect())?;
+        write_column::<Int32Type>(&mut row_group, rows.iter().map(|e| e.kill_streak as i32).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.vehicle_destroyed as i64).collect())?;
+        write_column::<Int64Type>(&mut row_group, rows.iter().map(|e| e.total as i64).collect())?;
+        write_column::<BoolType>(&mut row_group, rows.iter().map(|e| e.dnf).collect())?;
+        write_column::<BoolType>(&mut row_group, rows.iter().map(|e| e.is_commander).collect())?;
+        write_column::<BoolType>(&mut row_group, rows.iter().map(|e| e.is_soldier).collect())?;
+        row_group.close()?;
+
+        Ok(self.buffer.take())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<Vec<u8>> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+
+        Ok(self.buffer.take())
+    }
+}
+
+/// Writes the next column of the row group, the columns have to come in the order of the schema.
+fn write_column<T: DataType>(row_group: &mut SerializedRowGroupWriter<'_, SharedBuffer>, values: Vec<T::T>) -> anyhow::Result<()> {
+    write_column_levels::<T>(row_group, &values, None)
+}
+
+fn write_optional_string<'a>(row_group: &mut SerializedRowGroupWriter<'_, SharedBuffer>, values: impl Iterator<Item = Option<&'a str>>) -> anyhow::Result<()> {
+    let mut present = Vec::new();
+    let mut def_levels = Vec::new();
+    for value in values {
+        match value {
+            Some(value) => {
+                present.push(ByteArray::from(value));
+                def_levels.push(1);
+            },
+            None => def_levels.push(0),
+        }
+    }
+
+    write_column_levels::<ByteArrayType>(row_group, &present, Some(&def_levels))
+}
+
+fn write_column_levels<T: DataType>(row_group: &mut SerializedRowGroupWriter<'_, SharedBuffer>, values: &[T::T], def_levels: Option<&[i16]>) -> anyhow::Result<()> {
+    let mut column = row_group.next_column()?
+        .ok_or_else(|| anyhow!("Parquet schema has fewer columns than the export rows"))?;
+    column.typed::<T>().write_batch(values, def_levels, None)?;
+    column.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::web::Bytes;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::*;
+
+    fn export_row(persona_id: u64, persona_name: Option<&str>) -> ExportRow {
+        ExportRow {
+            report_id: 1297613665940962880,
+            created_at: 1598211430,
+            duration: 1261,
+            map: "MP_Tremors".to_string(),
+            mode: "2".to_string(),
+            winner: 1,
+            server_id: 1,
+            server_name: "LSD".to_string(),
+            server_guid: "4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string(),
+            persona_id,
+            persona_name: persona_name.map(|name| name.to_string()),
+            clan_tag: None,
+            team: 1,
+            squad_id: 0,
+            kills: 12,
+            deaths: 3,
+            assists: 0,
+            kd_ratio: 4.0,
+            spm: 450,
+            skill: 0,
+            shots_hit: 1.0,
+            shots_fired: 29.0,
+            accuracy_detailed: 0.0344827586,
+            heals: 0,
+            revives: 0,
+            kill_streak: 0,
+            vehicle_destroyed: 0,
+            total: 38,
+            dnf: false,
+            is_commander: false,
+            is_soldier: true,
+        }
+    }
+
+    #[test]
+    fn test_csv_header_only_before_the_first_page() -> anyhow::Result<()> {
+        let mut writer = CsvRowWriter::new();
+        let first = String::from_utf8(writer.write_rows(&[export_row(824078704, Some("Tatarek99"))])?)?;
+        let second = String::from_utf8(writer.write_rows(&[export_row(1000000001, None)])?)?;
+
+        assert!(first.starts_with("report_id,created_at,"));
+        assert_eq!(first.lines().count(), 2);
+        assert_eq!(second.lines().count(), 1);
+        assert!(second.starts_with("1297613665940962880,"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_lines_row_per_line() -> anyhow::Result<()> {
+        let output = JsonLinesRowWriter.write_rows(&[export_row(824078704, Some("Tatarek99")), export_row(1000000001, None)])?;
+        let rows: Vec<serde_json::Value> = String::from_utf8(output)?.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["persona_name"], "Tatarek99");
+        assert!(rows[1]["persona_name"].is_null());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_row_group_per_page() -> anyhow::Result<()> {
+        let mut writer = ParquetRowWriter::new()?;
+        let mut output = writer.write_rows(&[export_row(824078704, Some("Tatarek99")), export_row(1000000001, None)])?;
+        output.extend(writer.write_rows(&[export_row(1000000002, None)])?);
+        output.extend(writer.finish()?);
+
+        let reader = SerializedFileReader::new(Bytes::from(output))?;
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        Ok(())
+    }
+}
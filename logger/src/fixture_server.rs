@@ -0,0 +1,156 @@
+use std::{fs, net::TcpListener, path::PathBuf, sync::{Arc, Mutex}};
+
+use actix_web::{dev::ServerHandle, web, App, HttpResponse, HttpServer};
+use serde::Deserialize;
+
+use crate::battlelog_client::client::{BattlelogClient, BattlelogClientConfig};
+
+/// Recorded responses live in `tests/fixtures`, see the README there for the layout.
+pub fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// Local HTTP server replaying the recorded Battlelog responses on the paths of
+/// `BattlelogEndpoint`, and accepting Discord webhook posts so tests can run without network.
+pub struct FixtureServer {
+    pub base_url: String,
+    webhook_messages: Arc<Mutex<Vec<serde_json::Value>>>,
+    handle: ServerHandle,
+}
+
+struct FixtureState {
+    dir: PathBuf,
+    webhook_messages: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl FixtureServer {
+    pub async fn start() -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+
+        let webhook_messages = Arc::new(Mutex::new(Vec::new()));
+        let state = web::Data::new(FixtureState { dir: fixtures_dir(), webhook_messages: webhook_messages.clone() });
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .route("/bf4/battlereport/loadgeneralreport/{report_id}/1/1/", web::get().to(battlereport))
+                .route("/bf4/battlereport/loadplayerreport/{report_id}/1/{persona_id}/", web::get().to(playerreport))
+                .route("/bf4/warsawbattlereportspopulatemore/{persona_id}/2048/1/{timestamp}/", web::get().to(battlereports_populate_more))
+                .route("/bf4/users/", web::get().to(users))
+                .route("/bf4/loadout/get/{soldier_name}/{persona_id}/1/", web::get().to(loadout))
+                .route("/api/webhooks/{id}/{token}", web::get().to(webhook_information))
+                .route("/api/webhooks/{id}/{token}", web::post().to(webhook_post))
+        })
+        .disable_signals()
+        .workers(1)
+        .listen(listener)?
+        .run();
+
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(Self { base_url, webhook_messages, handle })
+    }
+
+    /// Battlelog client sending its requests to this server, without rate limiting worth noting.
+    pub fn client(&self) -> BattlelogClient {
+        BattlelogClient::new(BattlelogClientConfig {
+            rate_limit: 1000.0,
+            burst: 100,
+            max_in_flight: 4,
+            max_retries: 0,
+            backoff: std::time::Duration::from_millis(1),
+            timeout: std::time::Duration::from_secs(5),
+            connect_timeout: std::time::Duration::from_secs(1),
+            base_url: Some(self.base_url.clone()),
+        })
+    }
+
+    pub fn webhook_url(&self) -> String {
+        format!("{}/api/webhooks/1/token", self.base_url)
+    }
+
+    /// Bodies of the messages posted to the webhook so far.
+    pub fn webhook_messages(&self) -> Vec<serde_json::Value> {
+        self.webhook_messages.lock().unwrap().clone()
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+fn fixture_response(state: &FixtureState, path: PathBuf) -> HttpResponse {
+    match fs::read_to_string(state.dir.join(&path)) {
+        Ok(body) => HttpResponse::Ok().content_type(mime::APPLICATION_JSON).body(body),
+        Err(_) => HttpResponse::NotFound().body(format!("No fixture {}", path.display())),
+    }
+}
+
+async fn battlereport(state: web::Data<FixtureState>, path: web::Path<String>) -> HttpResponse {
+    fixture_response(&state, PathBuf::from("battlelog/battlereport").join(format!("{}.json", path.into_inner())))
+}
+
+async fn playerreport(state: web::Data<FixtureState>, path: web::Path<(String, String)>) -> HttpResponse {
+    let (report_id, persona_id) = path.into_inner();
+    fixture_response(&state, PathBuf::from("battlelog/playerreport").join(format!("{}_{}.json", report_id, persona_id)))
+}
+
+/// The first page is requested with the current time, so it's looked up by the persona alone.
+async fn battlereports_populate_more(state: web::Data<FixtureState>, path: web::Path<(String, String)>) -> HttpResponse {
+    let (persona_id, timestamp) = path.into_inner();
+    let page = PathBuf::from("battlelog/battlereports_populate_more").join(format!("{}_{}.json", persona_id, timestamp));
+    if state.dir.join(&page).exists() {
+        return fixture_response(&state, page);
+    }
+
+    fixture_response(&state, PathBuf::from("battlelog/battlereports_populate_more").join(format!("{}.json", persona_id)))
+}
+
+#[derive(Deserialize)]
+struct UsersQuery {
+    names: Option<String>,
+    persona_ids: Option<String>,
+}
+
+/// Users are recorded one per file and returned together, the unknown ones are left out like
+/// Battlelog does.
+async fn users(state: web::Data<FixtureState>, query: web::Query<UsersQuery>) -> HttpResponse {
+    let (dir, keys) = match (&query.names, &query.persona_ids) {
+        (Some(names), _) => ("battlelog/users", names),
+        (None, Some(persona_ids)) => ("battlelog/users_by_persona_ids", persona_ids),
+        (None, None) => return HttpResponse::BadRequest().body("Either names or persona_ids required"),
+    };
+
+    let mut users = Vec::new();
+    for key in keys.split(',').filter(|key| !key.is_empty()) {
+        if let Ok(body) = fs::read_to_string(state.dir.join(dir).join(format!("{}.json", key.to_lowercase()))) {
+            match serde_json::from_str::<serde_json::Value>(&body) {
+                Ok(user) => users.push(user),
+                Err(err) => return HttpResponse::InternalServerError().body(format!("Invalid fixture {}: {}", key, err)),
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(users)
+}
+
+async fn loadout(state: web::Data<FixtureState>, path: web::Path<(String, String)>) -> HttpResponse {
+    let (_, persona_id) = path.into_inner();
+    fixture_response(&state, PathBuf::from("battlelog/loadout").join(format!("{}.json", persona_id)))
+}
+
+async fn webhook_information(state: web::Data<FixtureState>) -> HttpResponse {
+    fixture_response(&state, PathBuf::from("discord/webhook.json"))
+}
+
+async fn webhook_post(state: web::Data<FixtureState>, body: web::Bytes) -> HttpResponse {
+    match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(message) => {
+            state.webhook_messages.lock().unwrap().push(message);
+            HttpResponse::NoContent().finish()
+        },
+        Err(err) => HttpResponse::BadRequest().body(format!("Invalid webhook message: {}", err)),
+    }
+}
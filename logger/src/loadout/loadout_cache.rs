@@ -0,0 +1,139 @@
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{database::store::BattlelogStore, persona::persona_lookup::get_persona_id_by_name};
+
+use super::{loadout_checker::get_user_loadout, loadout_rules::LoadoutItems};
+
+struct CachedLoadout {
+    loadout: Value,
+    fetched_at: Instant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeaponUsage {
+    pub weapon: String,
+    pub players: usize,
+    pub share: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadoutSummary {
+    pub players: usize,
+    /// Players with a cached loadout, the shares are calculated from these.
+    pub with_loadout: usize,
+    pub primary_weapons: Vec<WeaponUsage>,
+}
+
+/// Loadouts of the players on our server, kept for `ttl` before being fetched again.
+pub struct LoadoutCache {
+    db: Arc<dyn BattlelogStore>,
+    ttl: Duration,
+    server_guid: Option<String>,
+    loadouts: Mutex<HashMap<String, CachedLoadout>>,
+    players: Mutex<HashSet<String>>,
+}
+
+impl LoadoutCache {
+    pub fn new(db: Arc<dyn BattlelogStore>, ttl: Duration, server_guid: Option<String>) -> Self {
+        Self {
+            db,
+            ttl,
+            server_guid,
+            loadouts: Mutex::new(HashMap::new()),
+            players: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn from_env(db: Arc<dyn BattlelogStore>) -> Self {
+        let ttl: u64 = dotenv::var("LOADOUT_CACHE_TTL")
+            .map(|var| var.parse::<u64>())
+            .unwrap_or(Ok(600))
+            .unwrap();
+
+        LoadoutCache::new(db, Duration::from_secs(ttl), dotenv::var("SERVER_GUID").ok())
+    }
+
+    /// GUID of the server the cached players are on, if configured.
+    pub fn server_guid(&self) -> Option<&str> {
+        self.server_guid.as_deref()
+    }
+
+    pub fn set_players(&self, soldier_names: Vec<String>) {
+        let mut players = self.players.lock().unwrap();
+        players.clear();
+        players.extend(soldier_names);
+    }
+
+    pub fn add_player(&self, soldier_name: &str) {
+        self.players.lock().unwrap().insert(soldier_name.to_string());
+    }
+
+    pub fn remove_player(&self, soldier_name: &str) {
+        self.players.lock().unwrap().remove(soldier_name);
+        self.loadouts.lock().unwrap().remove(soldier_name);
+    }
+
+    pub fn players(&self) -> Vec<String> {
+        self.players.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Cached loadout of the player, fetched from Battlelog if missing or expired.
+    pub async fn get_loadout(&self, soldier_name: &str) -> anyhow::Result<Value> {
+        if let Some(cached) = self.loadouts.lock().unwrap().get(soldier_name) {
+            if cached.fetched_at.elapsed() < self.ttl {
+                return Ok(cached.loadout.clone());
+            }
+        }
+
+        let persona_id = match get_persona_id_by_name(&self.db, soldier_name).await? {
+            Some(persona_id) => persona_id,
+            None => return Err(anyhow::anyhow!("Persona not found for {}", soldier_name)),
+        };
+
+        let loadout = get_user_loadout(&self.db, soldier_name, &persona_id).await?;
+        let loadout = serde_json::to_value(&loadout)?;
+        self.loadouts.lock().unwrap().insert(soldier_name.to_string(), CachedLoadout {
+            loadout: loadout.clone(),
+            fetched_at: Instant::now(),
+        });
+
+        Ok(loadout)
+    }
+
+    /// Primary weapon usage among the current players with a fresh loadout, most used first.
+    pub fn summary(&self) -> LoadoutSummary {
+        let players = self.players.lock().unwrap();
+        let loadouts = self.loadouts.lock().unwrap();
+
+        let mut with_loadout = 0;
+        let mut usage: HashMap<String, usize> = HashMap::new();
+        for player in players.iter() {
+            let cached = match loadouts.get(player) {
+                Some(cached) if cached.fetched_at.elapsed() < self.ttl => cached,
+                _ => continue,
+            };
+
+            with_loadout += 1;
+            if let Some(weapon) = LoadoutItems::from_loadout(&cached.loadout).primary_weapon() {
+                *usage.entry(weapon.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        let mut primary_weapons: Vec<WeaponUsage> = usage.drain().map(|(weapon, count)| WeaponUsage {
+            weapon,
+            players: count,
+            share: count as f32 / with_loadout as f32,
+        }).collect();
+        primary_weapons.sort_by(|a, b| b.players.cmp(&a.players).then_with(|| a.weapon.cmp(&b.weapon)));
+
+        LoadoutSummary {
+            players: players.len(),
+            with_loadout,
+            primary_weapons,
+        }
+    }
+}
@@ -1,5 +1,24 @@
 use battlelog::{get_loadout, LoadoutResult};
 
-pub async fn get_user_loadout(soldier_name: &str, persona_id: &str) -> anyhow::Result<LoadoutResult> {
-    Ok(get_loadout(soldier_name, persona_id).await?)
+use crate::battlelog_client::{client::battlelog, endpoint::BattlelogEndpoint};
+use crate::database::store::BattlelogStore;
+
+use super::loadout_history::archive_loadout;
+
+/// Fetches the loadout from Battlelog and archives it if it changed since the last snapshot.
+pub async fn get_user_loadout(db: &dyn BattlelogStore, soldier_name: &str, persona_id: &str) -> anyhow::Result<LoadoutResult> {
+    let loadout = battlelog().request(BattlelogEndpoint::Loadout { soldier_name, persona_id }, || get_loadout(soldier_name, persona_id)).await?;
+
+    match persona_id.parse::<u64>() {
+        Ok(persona_id) => {
+            if let Err(err) = archive_loadout(db, persona_id, &loadout).await {
+                error!("Failed to archive loadout of {}: {}", persona_id, err);
+            }
+        },
+        Err(err) => {
+            warn!("Not archiving loadout of {} with invalid persona id {}: {}", soldier_name, persona_id, err);
+        },
+    }
+
+    Ok(loadout)
 }
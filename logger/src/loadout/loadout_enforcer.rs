@@ -0,0 +1,129 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use ascii::IntoAsciiString;
+use battlefield_rcon::bf4::Visibility;
+
+use crate::discord::DiscordQueue;
+use crate::rcon::RconConnection;
+
+use super::{loadout_cache::LoadoutCache, loadout_rules::{LoadoutRules, LoadoutViolation, RuleAction}};
+
+/// Checks the loadouts of players joining and spawning on the server against the rules and
+/// warns, reports or kicks them depending on the violated rule.
+pub struct LoadoutEnforcer {
+    cache: Arc<LoadoutCache>,
+    rcon: Arc<RconConnection>,
+    rules: LoadoutRules,
+    discord: Option<Arc<DiscordQueue>>,
+    check_interval: Duration,
+    last_checked: Mutex<HashMap<String, Instant>>,
+    level: Mutex<(Option<String>, Option<String>)>,
+}
+
+impl LoadoutEnforcer {
+    pub fn new(cache: Arc<LoadoutCache>, rcon: Arc<RconConnection>, rules: LoadoutRules, discord: Option<Arc<DiscordQueue>>, check_interval: Duration) -> Self {
+        Self {
+            cache,
+            rcon,
+            rules,
+            discord,
+            check_interval,
+            last_checked: Mutex::new(HashMap::new()),
+            level: Mutex::new((None, None)),
+        }
+    }
+
+    /// Enforcer configured with the rules from `LOADOUT_RULES_PATH`, `None` if it isn't set.
+    pub fn from_env(cache: Arc<LoadoutCache>, rcon: Arc<RconConnection>, discord: Option<Arc<DiscordQueue>>) -> anyhow::Result<Option<Self>> {
+        let path = match dotenv::var("LOADOUT_RULES_PATH") {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+
+        let rules = LoadoutRules::from_file(&path)?;
+        info!("Loaded {} loadout rules from {}", rules.rules.len(), path);
+
+        let check_interval: u64 = dotenv::var("LOADOUT_CHECK_INTERVAL")
+            .map(|var| var.parse::<u64>())
+            .unwrap_or(Ok(300))
+            .unwrap();
+
+        Ok(Some(LoadoutEnforcer::new(cache, rcon, rules, discord, Duration::from_secs(check_interval))))
+    }
+
+    pub fn set_level(&self, map: String, mode: String) {
+        *self.level.lock().unwrap() = (Some(map), Some(mode));
+        // Rules may differ per map, check everyone again
+        self.last_checked.lock().unwrap().clear();
+    }
+
+    /// Checks the player unless they were checked within the check interval.
+    pub async fn check_player(&self, soldier_name: &str) {
+        {
+            let mut last_checked = self.last_checked.lock().unwrap();
+            if let Some(checked) = last_checked.get(soldier_name) {
+                if checked.elapsed() < self.check_interval {
+                    return;
+                }
+            }
+            last_checked.insert(soldier_name.to_string(), Instant::now());
+        }
+
+        match self.get_violations(soldier_name).await {
+            Ok(violations) => {
+                if !violations.is_empty() {
+                    self.enforce(soldier_name, &violations).await;
+                }
+            },
+            Err(err) => {
+                warn!("Failed to check loadout of {}: {}", soldier_name, err);
+            },
+        }
+    }
+
+    async fn get_violations(&self, soldier_name: &str) -> anyhow::Result<Vec<LoadoutViolation>> {
+        let loadout = self.cache.get_loadout(soldier_name).await?;
+
+        let (map, mode) = self.level.lock().unwrap().clone();
+        Ok(self.rules.evaluate(&loadout, map.as_deref(), mode.as_deref()))
+    }
+
+    async fn enforce(&self, soldier_name: &str, violations: &[LoadoutViolation]) {
+        for violation in violations {
+            info!("{} violates loadout rule {} with {:?} {}", soldier_name, violation.rule, violation.kind, violation.item);
+        }
+
+        for violation in violations.iter().filter(|v| v.action == RuleAction::Warn) {
+            if let Err(err) = self.warn_player(soldier_name, &violation.message).await {
+                error!("Failed to warn {}: {}", soldier_name, err);
+            }
+        }
+
+        let reported: Vec<String> = violations.iter()
+            .filter(|v| v.action != RuleAction::Warn)
+            .map(|v| format!("{} ({:?} {}, {:?})", v.rule, v.kind, v.item, v.action))
+            .collect();
+        if !reported.is_empty() {
+            if let Some(discord) = &self.discord {
+                discord.send(soldier_name, &format!("Loadout rule violations:\n{}", reported.join("\n")));
+            }
+        }
+
+        if let Some(violation) = violations.iter().find(|v| v.action == RuleAction::Kick) {
+            if let Err(err) = self.kick_player(soldier_name, &violation.message).await {
+                error!("Failed to kick {}: {}", soldier_name, err);
+            }
+        }
+    }
+
+    async fn warn_player(&self, soldier_name: &str, message: &str) -> anyhow::Result<()> {
+        let player = soldier_name.into_ascii_string()?;
+        self.rcon.client().say(message.into_ascii_string()?, Visibility::Player(player)).await
+            .map_err(|err| anyhow::anyhow!("{:?}", err))
+    }
+
+    async fn kick_player(&self, soldier_name: &str, reason: &str) -> anyhow::Result<()> {
+        self.rcon.client().kick(soldier_name.into_ascii_string()?, reason.into_ascii_string()?).await
+            .map_err(|err| anyhow::anyhow!("{:?}", err))
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use battlelog::LoadoutResult;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::database::{battlelog::loadout_snapshots::LoadoutSnapshot, store::BattlelogStore};
+
+/// A single value that differs between two loadouts, `path` points to it like `currentLoadout.kits[0][1]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadoutChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadoutHistoryEntry {
+    pub id: u64,
+    pub created_at: u32,
+    pub loadout: Value,
+    /// Changes compared to the previous snapshot, empty for the first one.
+    pub changes: Vec<LoadoutChange>,
+}
+
+/// Stores the loadout as a new snapshot unless it's identical to the latest one.
+/// Returns `true` if a snapshot was stored.
+pub async fn archive_loadout(db: &dyn BattlelogStore, persona_id: u64, loadout: &LoadoutResult) -> anyhow::Result<bool> {
+    let loadout = serde_json::to_value(loadout)?;
+
+    let latest = db.get_latest_loadout_snapshot(persona_id).await?;
+    if let Some(latest) = latest {
+        // Compare as values, map ordering in the serialized JSON isn't stable
+        let latest: Value = serde_json::from_str(&latest.loadout)?;
+        if latest == loadout {
+            trace!("Loadout of {} unchanged", persona_id);
+            return Ok(false);
+        }
+    }
+
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    db.insert_loadout_snapshot(&LoadoutSnapshot::new(persona_id, time_now as u32, loadout.to_string())).await?;
+    debug!("Stored new loadout snapshot for {}", persona_id);
+
+    Ok(true)
+}
+
+/// Latest `limit` snapshots of the persona, newest first, each with the changes since the one before it.
+pub async fn get_loadout_history(db: &dyn BattlelogStore, persona_id: u64, limit: u32) -> anyhow::Result<Vec<LoadoutHistoryEntry>> {
+    // One extra to have something to compare the oldest returned snapshot against
+    let snapshots = db.get_loadout_snapshots(persona_id, limit + 1).await?;
+    let mut loadouts: Vec<(LoadoutSnapshot, Value)> = Vec::new();
+    for snapshot in snapshots {
+        let loadout: Value = serde_json::from_str(&snapshot.loadout)?;
+        loadouts.push((snapshot, loadout));
+    }
+
+    let mut history = Vec::new();
+    for (i, (snapshot, loadout)) in loadouts.iter().enumerate().take(limit as usize) {
+        let changes = match loadouts.get(i + 1) {
+            Some((_, previous)) => diff_loadouts(previous, loadout),
+            None => Vec::new(),
+        };
+
+        history.push(LoadoutHistoryEntry {
+            id: snapshot.id,
+            created_at: snapshot.created_at,
+            loadout: loadout.clone(),
+            changes,
+        });
+    }
+
+    Ok(history)
+}
+
+pub fn diff_loadouts(old: &Value, new: &Value) -> Vec<LoadoutChange> {
+    let mut changes = Vec::new();
+    diff_values("", Some(old), Some(new), &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<LoadoutChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+                diff_values(&child, old.get(key), new.get(key), changes);
+            }
+        },
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                diff_values(&format!("{}[{}]", path, i), old.get(i), new.get(i), changes);
+            }
+        },
+        (old, new) => {
+            if old != new {
+                changes.push(LoadoutChange {
+                    path: path.to_string(),
+                    old: old.cloned(),
+                    new: new.cloned(),
+                });
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_loadouts() {
+        let old = json!({ "currentLoadout": { "selectedKit": 0, "kits": [["1", "2"], ["3"]] } });
+        let new = json!({ "currentLoadout": { "selectedKit": 1, "kits": [["1", "4"], ["3", "5"]] } });
+
+        let changes = diff_loadouts(&old, &new);
+        assert_eq!(changes, vec![
+            LoadoutChange { path: "currentLoadout.kits[0][1]".to_string(), old: Some(json!("2")), new: Some(json!("4")) },
+            LoadoutChange { path: "currentLoadout.kits[1][1]".to_string(), old: None, new: Some(json!("5")) },
+            LoadoutChange { path: "currentLoadout.selectedKit".to_string(), old: Some(json!(0)), new: Some(json!(1)) },
+        ]);
+    }
+
+    #[test]
+    fn test_diff_identical_loadouts() {
+        let loadout = json!({ "currentLoadout": { "kits": [["1", "2"]] } });
+        assert!(diff_loadouts(&loadout, &loadout).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadoutItemKind {
+    /// Primary and secondary weapon of the selected kit.
+    Weapon,
+    /// Attachments of the weapons in the selected kit.
+    Attachment,
+    /// Gadgets, grenades and the knife of the selected kit.
+    Gadget,
+    /// Vehicle unlocks.
+    Vehicle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Tell the player in-game.
+    Warn,
+    /// Post an alert to Discord.
+    Discord,
+    /// Kick the player.
+    Kick,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoadoutRule {
+    pub name: String,
+    pub kind: LoadoutItemKind,
+    /// Battlelog item ids the rule bans.
+    pub items: Vec<String>,
+    /// Maps the rule applies to, e.g. `MP_Prison`. Applies to every map if empty.
+    #[serde(default)]
+    pub maps: Vec<String>,
+    /// Modes the rule applies to, e.g. `ConquestLarge0`. Applies to every mode if empty.
+    #[serde(default)]
+    pub modes: Vec<String>,
+    pub action: RuleAction,
+    /// Message shown to the player, defaults to the rule name.
+    pub message: Option<String>,
+}
+
+impl LoadoutRule {
+    fn applies_to(&self, map: Option<&str>, mode: Option<&str>) -> bool {
+        matches_filter(&self.maps, map) && matches_filter(&self.modes, mode)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadoutViolation {
+    pub rule: String,
+    pub kind: LoadoutItemKind,
+    pub item: String,
+    pub action: RuleAction,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LoadoutRules {
+    pub rules: Vec<LoadoutRule>,
+}
+
+impl LoadoutRules {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Checks the loadout (as serialized `LoadoutResult`) against every rule active on the map and mode.
+    pub fn evaluate(&self, loadout: &Value, map: Option<&str>, mode: Option<&str>) -> Vec<LoadoutViolation> {
+        let items = LoadoutItems::from_loadout(loadout);
+
+        let mut violations = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.applies_to(map, mode)) {
+            for item in items.of_kind(rule.kind) {
+                if rule.items.iter().any(|banned| banned.eq_ignore_ascii_case(item)) {
+                    violations.push(LoadoutViolation {
+                        rule: rule.name.clone(),
+                        kind: rule.kind,
+                        item: item.to_string(),
+                        action: rule.action,
+                        message: rule.message.clone().unwrap_or_else(|| rule.name.clone()),
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// Item ids of the loadout the player is currently using. Battlelog keeps every kit in
+/// `currentLoadout.kits` with the selected one in `currentLoadout.selectedKit`, kit slots 0 and 1
+/// being the primary and secondary weapon, and attachments per weapon id in `currentLoadout.weapons`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadoutItems {
+    pub weapons: Vec<String>,
+    pub attachments: Vec<String>,
+    pub gadgets: Vec<String>,
+    pub vehicles: Vec<String>,
+}
+
+impl LoadoutItems {
+    pub fn from_loadout(loadout: &Value) -> Self {
+        let mut items = LoadoutItems::default();
+
+        let current = match find_key(loadout, &["currentLoadout", "current_loadout"]) {
+            Some(current) => current,
+            None => return items,
+        };
+
+        let selected_kit = find_key(current, &["selectedKit", "selected_kit"])
+            .and_then(|kit| kit.as_u64().or_else(|| kit.as_str().and_then(|kit| kit.parse::<u64>().ok())))
+            .unwrap_or(0) as usize;
+
+        if let Some(kit) = find_key(current, &["kits"]).and_then(|kits| kits.get(selected_kit)) {
+            for (slot, item) in strings(kit).into_iter().enumerate() {
+                if slot < 2 {
+                    items.weapons.push(item);
+                }
+                else {
+                    items.gadgets.push(item);
+                }
+            }
+        }
+
+        if let Some(weapons) = find_key(current, &["weapons"]) {
+            for weapon in items.weapons.iter() {
+                if let Some(attachments) = weapons.get(weapon) {
+                    items.attachments.extend(strings(attachments));
+                }
+            }
+        }
+
+        if let Some(vehicles) = find_key(current, &["vehicles"]) {
+            items.vehicles = strings(vehicles);
+        }
+
+        items
+    }
+
+    pub fn primary_weapon(&self) -> Option<&str> {
+        self.weapons.first().map(|w| w.as_str())
+    }
+
+    fn of_kind(&self, kind: LoadoutItemKind) -> &[String] {
+        match kind {
+            LoadoutItemKind::Weapon => &self.weapons,
+            LoadoutItemKind::Attachment => &self.attachments,
+            LoadoutItemKind::Gadget => &self.gadgets,
+            LoadoutItemKind::Vehicle => &self.vehicles,
+        }
+    }
+}
+
+/// Looks up the first existing key, searching through a wrapping `data` object as well.
+fn find_key<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    for key in keys {
+        if let Some(found) = value.get(key) {
+            return Some(found);
+        }
+    }
+
+    value.get("data").and_then(|data| find_key(data, keys))
+}
+
+/// Every non-empty string or number leaf of the value, ids show up as both in Battlelog data.
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) if !s.is_empty() && s != "0" => vec![s.to_string()],
+        Value::Number(n) if n.as_u64() != Some(0) => vec![n.to_string()],
+        Value::Array(values) => values.iter().flat_map(strings).collect(),
+        Value::Object(values) => values.values().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn matches_filter(filter: &[String], value: Option<&str>) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+
+    match value {
+        Some(value) => filter.iter().any(|f| f.eq_ignore_ascii_case(value)),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn loadout() -> Value {
+        json!({
+            "data": {
+                "currentLoadout": {
+                    "selectedKit": "1",
+                    "kits": [
+                        ["100", "200", "300"],
+                        ["101", "201", "301", "302"]
+                    ],
+                    "weapons": {
+                        "100": ["1000"],
+                        "101": ["1010", "1011"]
+                    },
+                    "vehicles": [["5000", "0"]]
+                }
+            }
+        })
+    }
+
+    fn rule(kind: LoadoutItemKind, items: &[&str], maps: &[&str]) -> LoadoutRule {
+        LoadoutRule {
+            name: "No test items".to_string(),
+            kind,
+            items: items.iter().map(|i| i.to_string()).collect(),
+            maps: maps.iter().map(|m| m.to_string()).collect(),
+            modes: Vec::new(),
+            action: RuleAction::Warn,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_loadout_items_use_selected_kit() {
+        let items = LoadoutItems::from_loadout(&loadout());
+        assert_eq!(items.weapons, vec!["101", "201"]);
+        assert_eq!(items.gadgets, vec!["301", "302"]);
+        assert_eq!(items.attachments, vec!["1010", "1011"]);
+        assert_eq!(items.vehicles, vec!["5000"]);
+        assert_eq!(items.primary_weapon(), Some("101"));
+    }
+
+    #[test]
+    fn test_evaluate_matches_kind_and_map() {
+        let rules = LoadoutRules { rules: vec![
+            rule(LoadoutItemKind::Attachment, &["1011"], &[]),
+            rule(LoadoutItemKind::Weapon, &["100"], &[]),
+            rule(LoadoutItemKind::Gadget, &["302"], &["MP_Prison"]),
+        ]};
+
+        let violations = rules.evaluate(&loadout(), Some("MP_Siege"), None);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].item, "1011");
+
+        let violations = rules.evaluate(&loadout(), Some("mp_prison"), None);
+        assert_eq!(violations.len(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use battlefield_rcon::bf4::Visibility;
+
+use crate::rcon::RconConnection;
+
+use super::{loadout_cache::LoadoutCache, loadout_enforcer::LoadoutEnforcer};
+
+/// Reacts to the server events, keeping the loadout cache filled with the current players and
+/// running the loadout rules on them.
+pub struct LoadoutWatcher {
+    rcon: Arc<RconConnection>,
+    cache: Arc<LoadoutCache>,
+    enforcer: Option<Arc<LoadoutEnforcer>>,
+    /// Fetch the loadouts of every player, not only the ones the enforcer checks.
+    track_all: bool,
+}
+
+impl LoadoutWatcher {
+    pub fn new(rcon: Arc<RconConnection>, cache: Arc<LoadoutCache>, enforcer: Option<Arc<LoadoutEnforcer>>, track_all: bool) -> Self {
+        Self {
+            rcon,
+            cache,
+            enforcer,
+            track_all,
+        }
+    }
+
+    /// Refreshes the player list and fetches the loadouts of everyone on the server.
+    pub async fn on_level_loaded(&self, map: String, mode: String) {
+        if let Some(enforcer) = &self.enforcer {
+            enforcer.set_level(map, mode);
+        }
+
+        let players = match self.rcon.client().list_players(Visibility::All).await {
+            Ok(players) => players,
+            Err(err) => {
+                error!("Failed to list players: {:?}", err);
+                return;
+            },
+        };
+
+        let soldier_names: Vec<String> = players.iter().map(|p| p.player_name.to_string()).collect();
+        info!("Level loaded with {} players, fetching loadouts", soldier_names.len());
+        self.cache.set_players(soldier_names.clone());
+
+        for soldier_name in soldier_names {
+            self.fetch(&soldier_name).await;
+        }
+    }
+
+    pub async fn on_player_joined(&self, soldier_name: &str) {
+        self.cache.add_player(soldier_name);
+        self.fetch(soldier_name).await;
+    }
+
+    pub async fn on_player_spawned(&self, soldier_name: &str) {
+        if let Some(enforcer) = &self.enforcer {
+            enforcer.check_player(soldier_name).await;
+        }
+    }
+
+    pub fn on_player_left(&self, soldier_name: &str) {
+        self.cache.remove_player(soldier_name);
+    }
+
+    async fn fetch(&self, soldier_name: &str) {
+        if let Some(enforcer) = &self.enforcer {
+            // Goes through the cache as well
+            enforcer.check_player(soldier_name).await;
+        }
+        else if self.track_all {
+            if let Err(err) = self.cache.get_loadout(soldier_name).await {
+                warn!("Failed to fetch loadout of {}: {}", soldier_name, err);
+            }
+        }
+    }
+}
@@ -1 +1,6 @@
-pub mod loadout_checker;
\ No newline at end of file
+pub mod loadout_checker;
+pub mod loadout_history;
+pub mod loadout_rules;
+pub mod loadout_enforcer;
+pub mod loadout_cache;
+pub mod loadout_watcher;
\ No newline at end of file
@@ -2,24 +2,38 @@
 extern crate log;
 
 use actix_web::{middleware, web, App, HttpServer};
-use battlefield_rcon::{rcon::{RconConnectionInfo}, bf4::{Bf4Client, Event, Visibility}};
+use battlefield_rcon::{rcon::{RconConnectionInfo}, bf4::{Event, Visibility}};
 use chrono_tz::Tz;
 use dotenv::{dotenv, var};
 use round_stats::battlereport;
 use ascii::{IntoAsciiString};
-use async_std::task;
+use std::sync::Arc;
 use futures::join;
 use futures::StreamExt;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
-use crate::discord::send_message_webhook;
+use crate::cache::response_cache::ResponseCache;
+use crate::discord::DiscordQueue;
+use crate::loadout::loadout_cache::LoadoutCache;
+use crate::loadout::loadout_enforcer::LoadoutEnforcer;
+use crate::loadout::loadout_watcher::LoadoutWatcher;
+use crate::rcon::RconConnection;
 
+mod battlelog_client;
+mod cache;
 mod database;
 mod discord;
+mod export;
 mod logging;
+mod metrics;
 mod round_stats;
 mod endpoints;
 pub mod loadout;
 mod persona;
+mod rcon;
+mod shutdown;
+#[cfg(test)]
+mod fixture_server;
 
 fn get_rcon_coninfo() -> anyhow::Result<RconConnectionInfo> {
     let ip = var("RCON_IP").unwrap_or_else(|_| "127.0.0.1".into());
@@ -35,6 +49,21 @@ fn get_rcon_coninfo() -> anyhow::Result<RconConnectionInfo> {
     })
 }
 
+/// Label of the event in the RCON event metrics, events the logger doesn't handle are `other`.
+fn rcon_event_name<E>(ev: &Result<Event, E>) -> &'static str {
+    match ev {
+        Ok(Event::RoundOverPlayers { .. }) => "round_over_players",
+        Ok(Event::LevelLoaded { .. }) => "level_loaded",
+        Ok(Event::Authenticated { .. }) => "authenticated",
+        Ok(Event::Spawn { .. }) => "spawn",
+        Ok(Event::Leave { .. }) => "leave",
+        Ok(Event::Chat { .. }) => "chat",
+        Ok(Event::Kill { .. }) => "kill",
+        Ok(_) => "other",
+        Err(_) => "error",
+    }
+}
+
 fn get_timezone() -> Tz {
     let timezone = dotenv::var("CHRONO_TIMEZONE").unwrap_or("Europe/Helsinki".to_string());
     timezone.parse().unwrap()
@@ -44,47 +73,153 @@ fn get_timezone() -> Tz {
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     logging::init_logging();
+    metrics::init();
 
     info!("BR Logger starting");
     info!("Using time zone: {}", get_timezone().name());
 
+    let shutdown = CancellationToken::new();
+    tokio::spawn(shutdown::cancel_on_signal(shutdown.clone()));
+
     if dotenv::var("UPDATE_PERSONAS").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
-        persona::persona_updater::update_personas_without_last_update().await?;
+        persona::persona_updater::update_personas_without_last_update(&shutdown).await?;
     }
     
+    let db = database::store::connect_env().await?;
+
     if dotenv::var("READ_BRR").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
-        battlereport::read_brr(&dotenv::var("BRR_PATH").unwrap()).await?;
+        battlereport::read_brr(&*db, &dotenv::var("BRR_PATH").unwrap(), &shutdown).await?;
+    }
+
+    if dotenv::var("REPROCESS").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        let filter = round_stats::reprocess::ReprocessFilter::from_env()?;
+        match dotenv::var("REPROCESS_BRR_PATH") {
+            Ok(path) => round_stats::reprocess::reprocess_brr(&path, &filter, &shutdown).await?,
+            Err(_) => round_stats::reprocess::reprocess_archives(&filter, &shutdown).await?,
+        }
+    }
+
+    if dotenv::var("BACKFILL").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        round_stats::backfill::backfill_personas(&round_stats::backfill::BackfillConfig::from_env()?, &shutdown).await?;
+    }
+
+    if dotenv::var("CRAWL").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        round_stats::crawler::crawl(&round_stats::crawler::CrawlConfig::from_env()?, &shutdown).await?;
     }
 
     if dotenv::var("READ_REPORT_IDS").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
-        battlereport::read_battlereport_ids(&dotenv::var("REPORT_IDS_PATH").unwrap()).await?;
+        battlereport::read_battlereport_ids(&*db, &dotenv::var("REPORT_IDS_PATH").unwrap(), &shutdown).await?;
+    }
+
+    if dotenv::var("WRITE_BRR").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        round_stats::brr::write_brr(&dotenv::var("WRITE_BRR_PATH").unwrap(), &round_stats::reprocess::ReprocessFilter::from_env_with_prefix("WRITE_BRR")?, &shutdown).await?;
+    }
+
+    if dotenv::var("EXPORT").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        let format = export::ExportFormat::parse(&dotenv::var("EXPORT_FORMAT").unwrap_or_else(|_| "csv".to_string()))?;
+        export::export_to_file(&dotenv::var("EXPORT_PATH").unwrap(), format, export::export_filter_from_env()?, &shutdown).await?;
+    }
+
+    if shutdown.is_cancelled() {
+        db.close().await;
+        return Ok(());
     }
 
     // let webhook_path = dotenv::var("DISCORD_WEBHOOK").unwrap();
     let rconinfo = get_rcon_coninfo()?;
 
-    let bf4 = Bf4Client::connect((rconinfo.ip, rconinfo.port), rconinfo.password)
-        .await
-        .unwrap();
+    let rcon = Arc::new(RconConnection::connect(rconinfo).await?);
 
-    let events_bf4 = bf4.clone();
-    let events_task = task::spawn(async move {
-        if dotenv::var("ENABLE_RCON_EVENTS").map(|var| var.parse::<bool>()).unwrap_or(Ok(true)).unwrap() {
-            let mut event_stream = events_bf4.event_stream().await.unwrap();
-            while let Some(ev) = event_stream.next().await {
-                match ev {
-                    Ok(Event::RoundOverPlayers { players }) => {
+    let discord = DiscordQueue::from_env().map(Arc::new);
 
-                    },
-                    Ok(_) => {}, // ignore other events.
+    // Background work is tracked so shutdown can wait for it to finish
+    let tasks = TaskTracker::new();
+
+    if dotenv::var("PERSONA_REFRESH").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap() {
+        tasks.spawn(persona::persona_scheduler::run_persona_refresh(db.clone(), persona::persona_scheduler::PersonaRefreshConfig::from_env(), shutdown.clone()));
+    }
+
+    let loadout_cache = Arc::new(LoadoutCache::from_env(db.clone()));
+    let loadout_enforcer = LoadoutEnforcer::from_env(loadout_cache.clone(), rcon.clone(), discord.clone())?.map(Arc::new);
+    let track_loadouts = dotenv::var("LOADOUT_TRACKING").map(|var| var.parse::<bool>()).unwrap_or(Ok(false)).unwrap();
+    let loadout_watcher = Arc::new(LoadoutWatcher::new(rcon.clone(), loadout_cache.clone(), loadout_enforcer, track_loadouts));
+
+    let events_rcon = rcon.clone();
+    let events_shutdown = shutdown.clone();
+    let events_tasks = tasks.clone();
+    let events_task = tokio::spawn(async move {
+        if dotenv::var("ENABLE_RCON_EVENTS").map(|var| var.parse::<bool>()).unwrap_or(Ok(true)).unwrap() {
+            // The server may drop the connection, the event stream is opened again on a new one
+            loop {
+                let client = events_rcon.client();
+                let mut event_stream = match client.event_stream().await {
+                    Ok(event_stream) => event_stream,
                     Err(err) => {
-                        error!("Got error: {:?}", err);
+                        error!("Failed to open the RCON event stream: {:?}", err);
+                        if !events_rcon.reconnect(&events_shutdown).await {
+                            break;
+                        }
+                        continue;
                     },
+                };
+                metrics::RCON_CONNECTED.set(1);
+                loop {
+                    let ev = tokio::select! {
+                        ev = event_stream.next() => match ev {
+                            Some(ev) => ev,
+                            None => break,
+                        },
+                        _ = events_shutdown.cancelled() => break,
+                    };
+                    metrics::RCON_EVENTS.with_label_values(&[rcon_event_name(&ev)]).inc();
+
+                    match ev {
+                        Ok(Event::RoundOverPlayers { players }) => {
+
+                        },
+                        Ok(Event::LevelLoaded { level_name, game_mode, .. }) => {
+                            let watcher = loadout_watcher.clone();
+                            events_tasks.spawn(async move {
+                                watcher.on_level_loaded(level_name.rcon_encode().to_string(), game_mode.rcon_encode().to_string()).await;
+                            });
+                        },
+                        Ok(Event::Authenticated { player }) => {
+                            let watcher = loadout_watcher.clone();
+                            events_tasks.spawn(async move {
+                                watcher.on_player_joined(player.name.as_str()).await;
+                            });
+                        },
+                        Ok(Event::Spawn { player, .. }) => {
+                            let watcher = loadout_watcher.clone();
+                            events_tasks.spawn(async move {
+                                watcher.on_player_spawned(player.name.as_str()).await;
+                            });
+                        },
+                        Ok(Event::Leave { player, .. }) => {
+                            loadout_watcher.on_player_left(player.name.as_str());
+                        },
+                        Ok(_) => {}, // ignore other events.
+                        Err(err) => {
+                            error!("Got error: {:?}", err);
+                        },
+                    }
+                }
+                metrics::RCON_CONNECTED.set(0);
+
+                if events_shutdown.is_cancelled() {
+                    break;
+                }
+                warn!("RCON event stream closed, reconnecting");
+                if !events_rcon.reconnect(&events_shutdown).await {
+                    break;
                 }
             }
         }
     });
 
+    let response_cache = Arc::new(ResponseCache::from_env(db.clone()));
+    tasks.spawn(response_cache.clone().purge_expired(shutdown.clone()));
+
     let rest_api_address = dotenv::var("RESTAPI_ADDRESS").unwrap_or("0.0.0.0".to_string());
     let rest_api_port: u16 = dotenv::var("RESTAPI_PORT")
         .map(|var| var.parse::<u16>())
@@ -96,23 +231,63 @@ async fn main() -> anyhow::Result<()> {
         &rest_api_address, &rest_api_port
     );
 
+    let api_db = db.clone();
     let rest_api = HttpServer::new(move || {
         App::new()
             .wrap(middleware::Compress::default())
-            .app_data(web::Data::new(bf4.clone()))
+            .app_data(web::Data::from(rcon.clone()))
+            .app_data(web::Data::from(api_db.clone()))
+            .app_data(web::Data::from(loadout_cache.clone()))
+            .app_data(web::Data::from(response_cache.clone()))
+            .app_data(web::PathConfig::default().error_handler(endpoints::error::path_error))
+            .app_data(web::QueryConfig::default().error_handler(endpoints::error::query_error))
             .service(endpoints::health::check)
+            .service(endpoints::health::battlelog_metrics)
+            .service(endpoints::health::prometheus_metrics)
             .service(endpoints::battlereport::get_battlereport_by_id)
             .service(endpoints::battlereport::get_playerreport_by_id)
             .service(endpoints::battlereport::post_battlereport_by_id)
             .service(endpoints::battlereport::get_battlereports_more)
             .service(endpoints::battlereport::get_battlereports_more_text)
             .service(endpoints::loadout::get_persona_loadout)
+            .service(endpoints::loadout::get_persona_loadout_history)
+            .service(endpoints::loadout::get_server_loadouts)
+            .service(endpoints::personas::search_personas)
+            .service(endpoints::personas::get_persona)
+            .service(endpoints::personas::get_persona_names)
+            .service(endpoints::personas::get_personas_by_name)
+            .service(endpoints::clans::get_clan_stats)
+            .service(endpoints::clans::get_clan_roster)
+            .service(endpoints::clans::get_clan_activity)
+            .service(endpoints::clans::get_clan_leaderboard)
+            .service(endpoints::expansions::get_expansion_ownership)
+            .service(endpoints::expansions::get_server_expansion_share)
+            .service(endpoints::expansions::get_persona_expansions)
+            .service(endpoints::export::export_battlereports)
     })
+    .disable_signals()
     .bind((rest_api_address, rest_api_port))
     .unwrap()
     .run();
 
+    let rest_api_handle = rest_api.handle();
+    let rest_api_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        rest_api_shutdown.cancelled().await;
+        rest_api_handle.stop(true).await;
+    });
+
     _ = join!(events_task, rest_api);
 
+    info!("Waiting for the background work to finish");
+    tasks.close();
+    tasks.wait().await;
+
+    if let Some(discord) = discord {
+        discord.flush().await;
+    }
+    db.close().await;
+    info!("BR Logger stopped");
+
     Ok(())
 }
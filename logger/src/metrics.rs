@@ -0,0 +1,121 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+
+pub static BATTLEREPORTS_INGESTED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_battlereports_ingested_total", "Battlereports stored with every playerreport").unwrap()
+});
+
+pub static BATTLEREPORTS_FAILED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_battlereports_failed_total", "Battlereports that failed or are missing playerreports").unwrap()
+});
+
+/// Unix timestamp of the last fully ingested battlereport, the one to alert on when it stalls.
+pub static BATTLEREPORT_LAST_INGESTED: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("brlogger_battlereport_last_ingested_timestamp_seconds", "Unix time of the last ingested battlereport").unwrap()
+});
+
+pub static PLAYERREPORT_FETCH_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_playerreport_fetch_errors_total", "Playerreports that couldn't be fetched from Battlelog").unwrap()
+});
+
+pub static BATTLELOG_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "brlogger_battlelog_request_duration_seconds",
+        "Latency of the Battlelog requests, every retry is observed separately",
+        &["endpoint", "outcome"],
+        vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+    ).unwrap()
+});
+
+pub static DB_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "brlogger_db_query_duration_seconds",
+        "Latency of the database calls by store method",
+        &["query"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    ).unwrap()
+});
+
+pub static RCON_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("brlogger_rcon_events_total", "RCON events received by type", &["event"]).unwrap()
+});
+
+pub static RCON_CONNECTIONS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_rcon_connections_total", "RCON connections established, counting every reconnect").unwrap()
+});
+
+pub static RCON_CONNECTED: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("brlogger_rcon_connected", "1 while the RCON event stream is open").unwrap()
+});
+
+pub static DISCORD_MESSAGES_SENT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_discord_messages_sent_total", "Messages posted to the Discord webhook").unwrap()
+});
+
+pub static DISCORD_MESSAGES_FAILED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_discord_messages_failed_total", "Messages that failed to post or were dropped").unwrap()
+});
+
+pub static PERSONAS_UPDATED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_personas_updated_total", "Personas updated from Battlelog").unwrap()
+});
+
+pub static PERSONA_UPDATE_FAILURES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("brlogger_persona_update_failures_total", "Personas that failed to update or weren't returned by Battlelog").unwrap()
+});
+
+pub static PERSONA_UPDATER_LAST_RUN: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("brlogger_persona_updater_last_run_timestamp_seconds", "Unix time of the last updated persona chunk").unwrap()
+});
+
+/// Registers every metric so they are exported from the start, not only once first used.
+pub fn init() {
+    Lazy::force(&BATTLEREPORTS_INGESTED);
+    Lazy::force(&BATTLEREPORTS_FAILED);
+    Lazy::force(&BATTLEREPORT_LAST_INGESTED);
+    Lazy::force(&PLAYERREPORT_FETCH_ERRORS);
+    Lazy::force(&BATTLELOG_REQUEST_DURATION);
+    Lazy::force(&DB_QUERY_DURATION);
+    Lazy::force(&RCON_EVENTS);
+    Lazy::force(&RCON_CONNECTIONS);
+    Lazy::force(&RCON_CONNECTED);
+    Lazy::force(&DISCORD_MESSAGES_SENT);
+    Lazy::force(&DISCORD_MESSAGES_FAILED);
+    Lazy::force(&PERSONAS_UPDATED);
+    Lazy::force(&PERSONA_UPDATE_FAILURES);
+    Lazy::force(&PERSONA_UPDATER_LAST_RUN);
+}
+
+/// Every registered metric in the Prometheus text format.
+pub fn render() -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_contains_metrics() {
+        PLAYERREPORT_FETCH_ERRORS.inc();
+        RCON_EVENTS.with_label_values(&["spawn"]).inc();
+
+        let rendered = render().unwrap();
+        assert!(rendered.contains("brlogger_playerreport_fetch_errors_total"));
+        assert!(rendered.contains("brlogger_rcon_events_total{event=\"spawn\"}"));
+    }
+}
@@ -1 +1,4 @@
-pub mod persona_updater;
\ No newline at end of file
+pub mod persona_updater;
+pub mod persona_scheduler;
+pub mod persona_profile;
+pub mod persona_lookup;
\ No newline at end of file
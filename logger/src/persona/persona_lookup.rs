@@ -0,0 +1,20 @@
+use battlelog::get_users;
+
+use crate::battlelog_client::{client::battlelog, endpoint::BattlelogEndpoint};
+use crate::database::store::BattlelogStore;
+
+/// Resolves the persona id of a soldier name, from the database if we've seen the player before
+/// and from Battlelog otherwise.
+pub async fn get_persona_id_by_name(db: &dyn BattlelogStore, soldier_name: &str) -> anyhow::Result<Option<String>> {
+    if let Some(persona) = db.get_persona_by_name(soldier_name).await? {
+        return Ok(Some(persona.id.to_string()));
+    }
+
+    let soldier_names = vec![soldier_name.to_string()];
+    let users = battlelog().request(BattlelogEndpoint::Users { soldier_names: &soldier_names }, || get_users(soldier_names.clone())).await?;
+    let persona_id = users.iter()
+        .find(|user| user.persona.persona_name.eq_ignore_ascii_case(soldier_name))
+        .map(|user| user.persona.persona_id.to_string());
+
+    Ok(persona_id)
+}
@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+use crate::database::{store::BattlelogStore, battlelog::{personas::BattlelogPersona, persona_info::PersonaInfo, persona_game_expansion::PersonaGameExpansionDetails, persona_name_history::PersonaNameHistory, playerreports::{PersonaRound, PersonaStats}}};
+
+use super::persona_updater::{update_persona_chunk, PersonaUpdateReport};
+
+const RECENT_ROUNDS: u32 = 20;
+
+/// Everything we have stored about a persona.
+#[derive(Debug, Serialize)]
+pub struct PersonaProfile {
+    pub persona: BattlelogPersona,
+    pub info: Option<PersonaInfo>,
+    pub expansions: Vec<PersonaGameExpansionDetails>,
+    pub name_history: Vec<PersonaNameHistory>,
+    pub recent_rounds: Vec<PersonaRound>,
+    pub stats: PersonaStats,
+    /// Result of refreshing the persona from Battlelog, only present when a refresh was requested.
+    pub refresh: Option<PersonaUpdateReport>,
+}
+
+/// Assembles the profile from the database, refreshing the persona from Battlelog first if
+/// `refresh` is set. A failed refresh is logged and the stored data is returned as is.
+pub async fn get_persona_profile(db: &dyn BattlelogStore, persona_id: u64, refresh: bool) -> anyhow::Result<Option<PersonaProfile>> {
+    let mut refresh_report = None;
+    if refresh {
+        match update_persona_chunk(db, vec![persona_id.to_string()]).await {
+            Ok(report) => {
+                refresh_report = Some(report);
+            },
+            Err(err) => {
+                warn!("Failed to refresh persona {}: {}", persona_id, err);
+            },
+        }
+    }
+
+    let persona = db.get_persona_by_persona_id(persona_id).await?;
+    if persona.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(PersonaProfile {
+        persona: persona.unwrap(),
+        info: db.get_persona_info_by_id(persona_id).await?,
+        expansions: db.get_persona_game_expansion_details(persona_id).await?,
+        name_history: db.get_persona_name_history(persona_id).await?,
+        recent_rounds: db.get_persona_recent_rounds(persona_id, RECENT_ROUNDS).await?,
+        stats: db.get_persona_stats(persona_id).await?,
+        refresh: refresh_report,
+    }))
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+use crate::database::store::BattlelogStore;
+
+use super::persona_updater::update_persona_chunk;
+
+/// Personas fetched from Battlelog with a single request.
+const CHUNK_SIZE: u32 = 100;
+
+#[derive(Debug, Clone)]
+pub struct PersonaRefreshConfig {
+    /// Battlelog requests the scheduler is allowed to make per hour.
+    pub requests_per_hour: u32,
+    /// Personas seen on our servers within this many days are refreshed first.
+    pub active_days: u32,
+    /// Personas updated within this many days are left alone.
+    pub stale_days: u32,
+}
+
+impl PersonaRefreshConfig {
+    pub fn from_env() -> Self {
+        Self {
+            requests_per_hour: env_u32("PERSONA_REFRESH_REQUESTS_PER_HOUR", 60).max(1),
+            active_days: env_u32("PERSONA_REFRESH_ACTIVE_DAYS", 7),
+            stale_days: env_u32("PERSONA_REFRESH_STALE_DAYS", 30),
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(3600.0 / self.requests_per_hour as f64)
+    }
+}
+
+/// Keeps refreshing the stalest personas until shutdown. Every Battlelog request made costs one
+/// tick, so a chunk that needed a retry waits for an extra tick before the next one.
+pub async fn run_persona_refresh(db: Arc<dyn BattlelogStore>, config: PersonaRefreshConfig, shutdown: CancellationToken) {
+    info!("Persona refresh running every {:?} ({} requests per hour)", config.interval(), config.requests_per_hour);
+
+    let mut interval = tokio::time::interval(config.interval());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Requests already made that still have to be paid for with a tick
+    let mut requests_owed: u32 = 1;
+    loop {
+        while requests_owed > 0 {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = shutdown.cancelled() => {
+                    info!("Persona refresh stopped");
+                    return;
+                },
+            }
+            requests_owed -= 1;
+        }
+        requests_owed = 1;
+
+        let time_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs() as u32;
+        let active_since = time_now.saturating_sub(config.active_days * 24 * 60 * 60);
+        let stale_before = time_now.saturating_sub(config.stale_days * 24 * 60 * 60);
+
+        let personas = match db.get_personas_for_refresh(active_since, stale_before, CHUNK_SIZE).await {
+            Ok(personas) => personas,
+            Err(err) => {
+                error!("Failed to get personas to refresh: {}", err);
+                continue;
+            },
+        };
+
+        if personas.is_empty() {
+            debug!("No stale personas to refresh");
+            continue;
+        }
+
+        let persona_ids: Vec<String> = personas.iter().map(|p| p.id.to_string()).collect();
+        match update_persona_chunk(&db, persona_ids).await {
+            Ok(report) => {
+                report.log();
+                requests_owed = report.requests.max(1);
+            },
+            // The request count is lost with the report, charge for the retry to stay in budget
+            Err(err) => {
+                error!("Failed to refresh personas: {}", err);
+                requests_owed = 2;
+            },
+        }
+    }
+}
+
+fn env_u32(name: &str, default: u32) -> u32 {
+    dotenv::var(name)
+        .map(|var| var.parse::<u32>())
+        .unwrap_or(Ok(default))
+        .unwrap()
+}
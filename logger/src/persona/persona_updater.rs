@@ -1,14 +1,18 @@
-use core::time;
-use std::{thread, time::{SystemTime, UNIX_EPOCH}};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use anyhow::Context;
 use battlelog::{get_users_by_persona_ids, UserResult};
-use sqlx::{Transaction, MySql};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 
+use crate::battlelog_client::{client::battlelog, endpoint::BattlelogEndpoint};
 use crate::database::battlelog::{context::BattlelogContext, personas::BattlelogPersona, persona_game_expansion::PersonaGameExpansion, game_expansions, persona_info::PersonaInfo};
+use crate::database::store::{self, BattlelogStore, StoreTransaction};
+use crate::metrics;
 
-pub async fn update_personas_without_last_update() -> anyhow::Result<()> {
+pub async fn update_personas_without_last_update(shutdown: &CancellationToken) -> anyhow::Result<()> {
     let uri = BattlelogContext::get_db_coninfo()?;
-    let db = BattlelogContext::connect(uri).await?;
+    let db = store::connect(&uri).await?;
 
     let personas: Vec<BattlelogPersona> = db.get_personas_without_update().await?;
     let personas: Vec<String> = personas.iter().map(|p| p.id.to_string()).collect();
@@ -18,96 +22,230 @@ pub async fn update_personas_without_last_update() -> anyhow::Result<()> {
     let total_to_process = personas.len();
     let mut total_processed = 0;
     for persona_chunk in personas.chunks(chunk_size) {
+        if shutdown.is_cancelled() {
+            info!("Stopping the persona update at {}/{} personas", total_processed, total_to_process);
+            break;
+        }
+
         total_processed += persona_chunk.len();
-        match get_users_by_persona_ids(persona_chunk.to_vec()).await {
-            Ok(results) => {
-                if results.len() != chunk_size {
-                    warn!("Got {}/{} results", results.len(), chunk_size);
-                }
-                else {
-                    info!("Got {}/{} results", results.len(), chunk_size);
-                }
-
-                let mut transaction = db.begin_transaction().await?;
-                if let Err(err) = update_persona_infos(&db, &mut transaction, results).await {
-                    error!("Error updating personas: {}", err)
-                }
-                transaction.commit().await?;
-                info!("{}/{} personas updated", total_processed, total_to_process);
-
-                thread::sleep(time::Duration::from_millis(100));
+        match update_persona_chunk(&db, persona_chunk.to_vec()).await {
+            Ok(report) => {
+                report.log();
+                info!("{}/{} personas processed", total_processed, total_to_process);
             },
             Err(err) => {
-                error!("Failed to fetch personas: {}", err)
+                error!("Failed to update personas: {}", err)
             },
         }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
 
+    db.close().await;
+
     Ok(())
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonaUpdateFailure {
+    pub persona_id: String,
+    pub reason: String,
+}
+
+/// Outcome of updating a chunk of personas, every requested persona ends up in exactly one of
+/// `updated`, `missing`, `failed` or `not_returned`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PersonaUpdateReport {
+    pub updated: Vec<u64>,
+    /// Returned by Battlelog but not found from the database.
+    pub missing: Vec<u64>,
+    pub failed: Vec<PersonaUpdateFailure>,
+    /// Requested but not returned by Battlelog, even after retrying.
+    pub not_returned: Vec<String>,
+    /// Returned by Battlelog without being requested, these are not updated.
+    pub unexpected: Vec<String>,
+    /// Battlelog requests made for the chunk, the retry makes it two.
+    pub requests: u32,
+}
 
-async fn update_persona_infos(db: &BattlelogContext, transaction: &mut Transaction<'_, MySql>, user_results: Vec<UserResult>) -> anyhow::Result<()> {
-    for user_result in user_results {
-        let persona_id = user_result.persona_id.parse::<u64>().unwrap();
-        trace!("Updating persona {}", persona_id);
+impl PersonaUpdateReport {
+    pub fn log(&self) {
+        info!("{} personas updated, {} missing, {} failed, {} not returned, {} unexpected", 
+            self.updated.len(), self.missing.len(), self.failed.len(), self.not_returned.len(), self.unexpected.len());
+        for failure in self.failed.iter() {
+            warn!("Failed to update persona {}: {}", failure.persona_id, failure.reason);
+        }
+        if !self.missing.is_empty() {
+            warn!("Personas not found from database: {:?}", self.missing);
+        }
+        if !self.not_returned.is_empty() {
+            warn!("Personas not returned by Battlelog: {:?}", self.not_returned);
+        }
+        if !self.unexpected.is_empty() {
+            warn!("Unexpected personas returned by Battlelog: {:?}", self.unexpected);
+        }
+    }
 
-        let persona = db.get_persona_by_persona_id(persona_id).await?;
+    fn record_metrics(&self) {
+        metrics::PERSONAS_UPDATED.inc_by(self.updated.len() as u64);
+        metrics::PERSONA_UPDATE_FAILURES.inc_by((self.failed.len() + self.not_returned.len()) as u64);
+        metrics::PERSONA_UPDATER_LAST_RUN.set(metrics::unix_now());
+    }
+}
+
+/// Fetches the personas from Battlelog and updates them in a single transaction. Each persona is
+/// updated inside its own savepoint so a failing one doesn't take the rest of the chunk with it.
+/// Personas missing from the Battlelog response are requested once more before giving up on them.
+/// The ones that couldn't be updated get their `last_updated` bumped so they don't hog the refresh
+/// queue.
+pub async fn update_persona_chunk(db: &dyn BattlelogStore, persona_chunk: Vec<String>) -> anyhow::Result<PersonaUpdateReport> {
+    let mut report = PersonaUpdateReport::default();
+
+    report.requests += 1;
+    let mut results = battlelog().request(BattlelogEndpoint::UsersByPersonaIds { persona_ids: &persona_chunk }, || get_users_by_persona_ids(persona_chunk.clone())).await?;
+    if results.len() != persona_chunk.len() {
+        warn!("Got {}/{} results", results.len(), persona_chunk.len());
+    }
+    else {
+        info!("Got {}/{} results", results.len(), persona_chunk.len());
+    }
+
+    let returned: Vec<&str> = results.iter().map(|r| r.persona_id.as_str()).collect();
+    let not_returned = missing_persona_ids(&persona_chunk, &returned);
+    if !not_returned.is_empty() {
+        info!("Retrying {} personas missing from the response", not_returned.len());
+        report.requests += 1;
+        match battlelog().request(BattlelogEndpoint::UsersByPersonaIds { persona_ids: &not_returned }, || get_users_by_persona_ids(not_returned.clone())).await {
+            Ok(retried) => results.extend(retried),
+            Err(err) => warn!("Failed to retry missing personas: {}", err),
+        }
+
+        let returned: Vec<&str> = results.iter().map(|r| r.persona_id.as_str()).collect();
+        report.not_returned = missing_persona_ids(&persona_chunk, &returned);
+    }
+
+    let mut transaction = db.begin().await?;
+    for user_result in results {
+        if !persona_chunk.contains(&user_result.persona_id) {
+            report.unexpected.push(user_result.persona_id);
+            continue;
+        }
+
+        let persona_id = match user_result.persona_id.parse::<u64>() {
+            Ok(persona_id) => persona_id,
+            Err(err) => {
+                report.failed.push(PersonaUpdateFailure { persona_id: user_result.persona_id, reason: format!("Invalid persona id: {}", err) });
+                continue;
+            },
+        };
 
-        if persona.is_none() {
-            return Err(anyhow::anyhow!("Persona {} not found from database", persona_id));
+        let mut savepoint = transaction.savepoint().await?;
+        match update_persona_info(savepoint.as_mut(), persona_id, user_result).await {
+            Ok(true) => {
+                savepoint.commit().await?;
+                report.updated.push(persona_id);
+            },
+            Ok(false) => {
+                savepoint.rollback().await?;
+                report.missing.push(persona_id);
+            },
+            Err(err) => {
+                savepoint.rollback().await?;
+                report.failed.push(PersonaUpdateFailure { persona_id: persona_id.to_string(), reason: err.to_string() });
+            },
         }
+    }
+
+    // Don't keep asking for personas Battlelog doesn't know about or that keep failing
+    let time_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as u32;
+    let not_updated = report.not_returned.iter()
+        .chain(report.failed.iter().map(|failure| &failure.persona_id))
+        .filter_map(|persona_id| persona_id.parse::<u64>().ok())
+        .chain(report.missing.iter().copied());
+    for persona_id in not_updated {
+        transaction.update_persona_last_updated(persona_id, time_now).await?;
+    }
+    transaction.commit().await?;
+    report.record_metrics();
 
-        let mut persona = persona.unwrap();
+    Ok(report)
+}
 
-        // Insert game expansions
-        trace!("Inserting game expansions for {}", persona_id);
-        for game_expansion in user_result.game_expansions {
-            let game_expansion_id = game_expansion.0.parse::<u64>().unwrap();
-            let platforms = game_expansion.1.iter().sum::<i32>() as u32;
-            db.upsert_persona_game_expansion(transaction, &PersonaGameExpansion::new(persona_id, game_expansion_id, platforms)).await?;
+/// Requested persona ids that are not in `returned`, duplicates in the request are reported once.
+fn missing_persona_ids(requested: &[String], returned: &[&str]) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    for persona_id in requested {
+        if !returned.contains(&persona_id.as_str()) && !missing.contains(persona_id) {
+            missing.push(persona_id.to_string());
         }
-        
-        // Insert/Update persona info
-        if user_result.info.is_some() {
-            trace!("Updating persona info for {}", persona_id);
-
-            let user_info = user_result.info.unwrap();
-
-            let persona_info = db.get_persona_info_by_id(persona_id).await?;
-            if persona_info.is_none() {
-                // Insert
-                db.upsert_persona_info(transaction, &PersonaInfo::new(persona_id, user_info.locality, user_info.location, user_info.presentation, user_info.login_counter, user_info.last_login)).await?;
-            }
-            else {
-                // Update
-                let mut persona_info = persona_info.unwrap();
-                persona_info.locality = user_info.locality;
-                persona_info.location = user_info.location;
-                persona_info.presentation = user_info.presentation;
-                persona_info.login_counter = user_info.login_counter;
-                persona_info.last_login = user_info.last_login;
-                db.update_persona_info_with_transaction(transaction, &persona_info).await?;
-            }
+    }
+
+    missing
+}
+
+/// Updates a single persona, returns `false` if the persona doesn't exist in the database.
+async fn update_persona_info(transaction: &mut (dyn StoreTransaction + '_), persona_id: u64, user_result: UserResult) -> anyhow::Result<bool> {
+    trace!("Updating persona {}", persona_id);
+
+    let persona = transaction.get_persona_by_persona_id(persona_id).await?;
+
+    if persona.is_none() {
+        return Ok(false);
+    }
+
+    let mut persona = persona.unwrap();
+
+    // Insert game expansions
+    trace!("Inserting game expansions for {}", persona_id);
+    for game_expansion in user_result.game_expansions {
+        let game_expansion_id = game_expansion.0.parse::<u64>()
+            .with_context(|| format!("Invalid game expansion id {}", game_expansion.0))?;
+        let platforms = game_expansion.1.iter().sum::<i32>() as u32;
+        transaction.upsert_persona_game_expansion(&PersonaGameExpansion::new(persona_id, game_expansion_id, platforms)).await?;
+    }
+    
+    // Insert/Update persona info
+    if user_result.info.is_some() {
+        trace!("Updating persona info for {}", persona_id);
+
+        let user_info = user_result.info.unwrap();
+
+        let persona_info = transaction.get_persona_info_by_id(persona_id).await?;
+        if persona_info.is_none() {
+            // Insert
+            transaction.upsert_persona_info(&PersonaInfo::new(persona_id, user_info.locality, user_info.location, user_info.presentation, user_info.login_counter, user_info.last_login)).await?;
         }
         else {
-            trace!("Skipping updating persona info for {}", persona_id);
+            // Update
+            let mut persona_info = persona_info.unwrap();
+            persona_info.locality = user_info.locality;
+            persona_info.location = user_info.location;
+            persona_info.presentation = user_info.presentation;
+            persona_info.login_counter = user_info.login_counter;
+            persona_info.last_login = user_info.last_login;
+            transaction.update_persona_info(&persona_info).await?;
         }
+    }
+    else {
+        trace!("Skipping updating persona info for {}", persona_id);
+    }
 
-        // Update persona last_updated time
-        trace!("Updating last_update for {}", persona_id);
+    // Update persona last_updated time
+    trace!("Updating last_update for {}", persona_id);
 
-        let start = SystemTime::now();
-        let since_the_epoch = start
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        let time_now = since_the_epoch.as_secs();
+    let start = SystemTime::now();
+    let since_the_epoch = start
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards");
+    let time_now = since_the_epoch.as_secs();
 
-        persona.last_updated = Some(time_now as u32);
-        db.update_persona_with_transaction(transaction, &persona).await?;
-    }
+    persona.last_updated = Some(time_now as u32);
+    transaction.update_persona(&persona).await?;
 
-    Ok(())
+    Ok(true)
 }
 
 
@@ -115,11 +253,18 @@ async fn update_persona_infos(db: &BattlelogContext, transaction: &mut Transacti
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_missing_persona_ids() {
+        let requested = vec!["1".to_string(), "2".to_string(), "3".to_string(), "3".to_string()];
+        assert_eq!(missing_persona_ids(&requested, &["2"]), vec!["1".to_string(), "3".to_string()]);
+        assert!(missing_persona_ids(&requested, &["1", "2", "3", "4"]).is_empty());
+    }
+
+    #[ignore]
     #[tokio::test]
-    async fn test_update_non_premium_users() {
-        update_non_premium_users()
+    async fn test_update_personas_without_last_update() {
+        update_personas_without_last_update(&CancellationToken::new())
             .await
             .unwrap();
     }
 }
-
@@ -0,0 +1,64 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ascii::AsciiString;
+use battlefield_rcon::{bf4::Bf4Client, rcon::RconConnectionInfo};
+use tokio_util::sync::CancellationToken;
+
+use crate::metrics;
+
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// The RCON client, replaced with a new connection when the server drops the event stream.
+/// Holders take the current client for every command so they pick up the reconnects.
+pub struct RconConnection {
+    ip: String,
+    port: u16,
+    password: AsciiString,
+    client: RwLock<Arc<Bf4Client>>,
+}
+
+impl RconConnection {
+    pub async fn connect(info: RconConnectionInfo) -> anyhow::Result<Self> {
+        let client = Bf4Client::connect((info.ip.clone(), info.port), info.password.clone()).await
+            .map_err(|err| anyhow::anyhow!("RCON connection to {}:{} failed: {:?}", info.ip, info.port, err))?;
+        metrics::RCON_CONNECTIONS.inc();
+
+        Ok(Self {
+            ip: info.ip,
+            port: info.port,
+            password: info.password,
+            client: RwLock::new(client),
+        })
+    }
+
+    pub fn client(&self) -> Arc<Bf4Client> {
+        self.client.read().unwrap().clone()
+    }
+
+    /// Connects again, doubling the wait between the attempts up to a minute. Returns false if
+    /// shutdown was requested before a connection was made.
+    pub async fn reconnect(&self, shutdown: &CancellationToken) -> bool {
+        let mut backoff = RECONNECT_BACKOFF_MIN;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {},
+                _ = shutdown.cancelled() => return false,
+            }
+
+            match Bf4Client::connect((self.ip.clone(), self.port), self.password.clone()).await {
+                Ok(client) => {
+                    *self.client.write().unwrap() = client;
+                    metrics::RCON_CONNECTIONS.inc();
+                    info!("Reconnected to RCON at {}:{}", self.ip, self.port);
+                    return true;
+                },
+                Err(err) => {
+                    backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                    warn!("RCON reconnect to {}:{} failed, retrying in {:?}: {:?}", self.ip, self.port, backoff, err);
+                },
+            }
+        }
+    }
+}
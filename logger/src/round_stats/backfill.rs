@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use battlelog::{BattlereportResponse, GameReport};
+use tokio_util::sync::CancellationToken;
+
+use crate::battlelog_client::{client::{battlelog, BattlelogClient}, endpoint::BattlelogEndpoint};
+use crate::database::battlelog::backfill_cursors::BackfillCursor;
+use crate::database::store::{self, BattlelogStore};
+
+use super::battlereport::{fetch_battlereports_page, get_db_coninfo, ingest_battlereport};
+use super::reprocess::env_date;
+
+/// Whose report histories are backfilled, how far back, and which servers' reports are ingested.
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    pub persona_ids: Vec<u64>,
+    /// Reports created before this are left out, 0 walks the whole history.
+    pub cutoff: u32,
+    pub server_guids: HashSet<String>,
+}
+
+impl BackfillConfig {
+    /// Reads the comma separated `BACKFILL_PERSONA_IDS` and `BACKFILL_SERVER_GUIDS`, and
+    /// `BACKFILL_CUTOFF` as a `YYYY-MM-DD` date in UTC.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let persona_ids = split_list(&dotenv::var("BACKFILL_PERSONA_IDS").unwrap_or_default())
+            .map(|persona_id| persona_id.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()?;
+        let server_guids: HashSet<String> = split_list(&dotenv::var("BACKFILL_SERVER_GUIDS").unwrap_or_default())
+            .map(|guid| guid.to_string())
+            .collect();
+        if server_guids.is_empty() {
+            return Err(anyhow::anyhow!("BACKFILL_SERVER_GUIDS needs at least one server GUID"));
+        }
+
+        Ok(Self {
+            persona_ids,
+            cutoff: env_date("BACKFILL_CUTOFF")?.unwrap_or(0),
+            server_guids,
+        })
+    }
+}
+
+/// State shared by the report histories walked in one run.
+pub(super) struct HistoryWalk<'a> {
+    client: &'a BattlelogClient,
+    server_guids: &'a HashSet<String>,
+    cutoff: u32,
+    /// Battlelog requests left, `None` when they aren't limited.
+    budget: Option<u32>,
+    /// Reports looked at during this run, most of them aren't from our servers so they are never
+    /// stored and co-players would otherwise fetch them again.
+    seen_reports: HashSet<u64>,
+    /// Reports ingested and failed during this run, the cursors count those of every run.
+    pub reports_ingested: u32,
+    pub reports_failed: u32,
+}
+
+impl<'a> HistoryWalk<'a> {
+    pub fn new(client: &'a BattlelogClient, server_guids: &'a HashSet<String>, cutoff: u32, budget: Option<u32>) -> Self {
+        Self {
+            client,
+            server_guids,
+            cutoff,
+            budget,
+            seen_reports: HashSet::new(),
+            reports_ingested: 0,
+            reports_failed: 0,
+        }
+    }
+
+    pub fn budget_left(&self) -> bool {
+        self.budget.map(|budget| budget > 0).unwrap_or(true)
+    }
+
+    /// Takes `requests` from the budget, returns false without taking any if it's used up.
+    fn take_requests(&mut self, requests: u32) -> bool {
+        match self.budget {
+            Some(0) => false,
+            Some(budget) => {
+                self.budget = Some(budget.saturating_sub(requests));
+                true
+            },
+            None => true,
+        }
+    }
+}
+
+/// Walks the report history of every configured persona back to the cutoff and ingests the
+/// reports played on our servers. The paging cursor is stored after every page, so an
+/// interrupted backfill continues where it stopped and a finished one is skipped unless the
+/// cutoff is moved further back.
+pub async fn backfill_personas(config: &BackfillConfig, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    info!("Backfilling the reports of {} personas", config.persona_ids.len());
+    let mut walk = HistoryWalk::new(battlelog(), &config.server_guids, config.cutoff, None);
+    for persona_id in config.persona_ids.iter() {
+        if shutdown.is_cancelled() {
+            info!("Stopping the backfill before {}", persona_id);
+            break;
+        }
+
+        let (ingested_before, failed_before) = (walk.reports_ingested, walk.reports_failed);
+        match backfill_persona(&db, &mut walk, *persona_id, shutdown).await {
+            Ok(cursor) => {
+                info!("Backfill of {} at {}: {} reports ingested, {} failed ({} and {} over every run){}", persona_id, cursor.timestamp,
+                    walk.reports_ingested - ingested_before, walk.reports_failed - failed_before, cursor.reports_ingested, cursor.reports_failed,
+                    if cursor.completed { ", completed" } else { "" });
+            },
+            Err(err) => {
+                error!("Backfill of {} failed because {}", persona_id, err);
+            },
+        }
+    }
+    info!("Backfill finished with {} reports ingested, {} failed", walk.reports_ingested, walk.reports_failed);
+
+    db.close().await;
+
+    Ok(())
+}
+
+/// Walks the history of the persona from its stored cursor, stops early on shutdown or once the
+/// request budget runs out.
+pub(super) async fn backfill_persona(db: &dyn BattlelogStore, walk: &mut HistoryWalk<'_>, persona_id: u64, shutdown: &CancellationToken) -> anyhow::Result<BackfillCursor> {
+    let mut cursor = match db.get_backfill_cursor(persona_id).await? {
+        Some(cursor) if cursor.completed && cursor.cutoff <= walk.cutoff => {
+            return Ok(cursor);
+        },
+        Some(mut cursor) => {
+            cursor.cutoff = walk.cutoff;
+            cursor.completed = false;
+            cursor
+        },
+        None => BackfillCursor::new(persona_id, unix_now(), walk.cutoff),
+    };
+
+    let persona_id_str = persona_id.to_string();
+    while !cursor.completed {
+        if shutdown.is_cancelled() || !walk.take_requests(1) {
+            break;
+        }
+        let reports = fetch_battlereports_page(walk.client, &persona_id_str, &cursor.timestamp.to_string()).await?;
+
+        let mut interrupted = false;
+        for report in reports.iter() {
+            if shutdown.is_cancelled() || !walk.budget_left() {
+                interrupted = true;
+                break;
+            }
+
+            // Pages are newest first, the rest of the history is older than the cutoff
+            if (report.created_at as u32) < cursor.cutoff {
+                cursor.completed = true;
+                break;
+            }
+
+            match backfill_report(db, walk, report).await {
+                Ok(true) => {
+                    cursor.reports_ingested += 1;
+                    walk.reports_ingested += 1;
+                },
+                Ok(false) => {},
+                Err(err) => {
+                    cursor.reports_failed += 1;
+                    walk.reports_failed += 1;
+                    error!("{} failed to backfill because {}", report.game_report_id, err);
+                },
+            }
+        }
+        interrupted |= !walk.budget_left();
+
+        match reports.last() {
+            None => cursor.completed = true,
+            // A page cut short by the cutoff, shutdown or the budget is requested again on the next run
+            Some(_) if cursor.completed || interrupted => {},
+            Some(last) if (last.created_at as u32) < cursor.timestamp => cursor.timestamp = last.created_at as u32,
+            Some(_) => {
+                warn!("Backfill of {} stopping because the page at {} didn't move back in time", persona_id, cursor.timestamp);
+                cursor.completed = true;
+            },
+        }
+
+        cursor.updated_at = unix_now();
+        db.upsert_backfill_cursor(&cursor).await?;
+
+        if interrupted {
+            break;
+        }
+    }
+
+    Ok(cursor)
+}
+
+/// Ingests the report if it was played on one of our servers and isn't stored yet, returns
+/// whether it was ingested. The page only names the server, so the report is fetched to match
+/// its GUID.
+async fn backfill_report(db: &dyn BattlelogStore, walk: &mut HistoryWalk<'_>, report: &GameReport) -> anyhow::Result<bool> {
+    let report_id = report.game_report_id.to_string();
+    let report_id_u64 = report_id.parse::<u64>()?;
+    if !walk.seen_reports.insert(report_id_u64) {
+        return Ok(false);
+    }
+
+    let existing = db.get_battlereport_by_report_id(report_id_u64).await?;
+    if existing.map(|br| br.processed > 0).unwrap_or(false) {
+        return Ok(false);
+    }
+
+    if !walk.take_requests(1) {
+        walk.seen_reports.remove(&report_id_u64);
+        return Ok(false);
+    }
+    let report_id = report_id.as_str();
+    let (response, body) = walk.client.request_raw::<BattlereportResponse>(BattlelogEndpoint::Battlereport { report_id }).await?;
+    match response.game_server.guid.as_ref() {
+        Some(guid) if walk.server_guids.contains(guid) => {},
+        _ => return Ok(false),
+    }
+
+    // A playerreport is fetched for every player, the last report may go over the budget
+    walk.take_requests(response.players.as_ref().map(|players| players.len() as u32).unwrap_or(0));
+
+    let result = ingest_battlereport(walk.client, db, report_id, &response, &body).await?;
+    if !result.success {
+        warn!("{} ingested with errors: {}", report_id, result.errors.unwrap_or_default().join(","));
+    }
+
+    Ok(true)
+}
+
+pub(super) fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|item| item.trim()).filter(|item| !item.is_empty())
+}
+
+fn unix_now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::sqlite::context::SqliteContext;
+    use crate::fixture_server::FixtureServer;
+
+    use super::*;
+
+    const PERSONA_ID: u64 = 1000000001;
+
+    fn server_guids() -> HashSet<String> {
+        vec!["4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string()].into_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn test_backfill_stops_at_cutoff() -> anyhow::Result<()> {
+        let server = FixtureServer::start().await?;
+        let client = server.client();
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let server_guids = server_guids();
+
+        // Only the newer of the two reports on the first page is after the cutoff
+        let mut walk = HistoryWalk::new(&client, &server_guids, 1666165000, None);
+        let cursor = backfill_persona(&db, &mut walk, PERSONA_ID, &CancellationToken::new()).await?;
+        assert!(cursor.completed);
+        assert_eq!((cursor.reports_ingested, cursor.reports_failed), (1, 0));
+        assert_eq!((walk.reports_ingested, walk.reports_failed), (1, 0));
+        assert_eq!(db.get_battlereport_by_report_id(1500000000000000001).await?.unwrap().processed, 1);
+        assert!(db.get_backfill_cursor(PERSONA_ID).await?.unwrap().completed);
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_from_cursor() -> anyhow::Result<()> {
+        let server = FixtureServer::start().await?;
+        let client = server.client();
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let server_guids = server_guids();
+
+        // The budget runs out with the first page, which is requested again on the next run
+        let mut walk = HistoryWalk::new(&client, &server_guids, 0, Some(1));
+        let cursor = backfill_persona(&db, &mut walk, PERSONA_ID, &CancellationToken::new()).await?;
+        assert!(!cursor.completed);
+        assert_eq!(cursor.reports_ingested, 0);
+        let first_page = cursor.timestamp;
+
+        // The older report has no battlereport fixture, the page after it is empty
+        let mut walk = HistoryWalk::new(&client, &server_guids, 0, None);
+        let cursor = backfill_persona(&db, &mut walk, PERSONA_ID, &CancellationToken::new()).await?;
+        assert!(cursor.completed);
+        assert_eq!(cursor.timestamp, 1666160000);
+        assert!(cursor.timestamp < first_page);
+        assert_eq!((cursor.reports_ingested, cursor.reports_failed), (1, 1));
+        assert_eq!((walk.reports_ingested, walk.reports_failed), (1, 1));
+
+        // A completed history isn't walked again, the cursor keeps the totals of the earlier runs
+        let mut walk = HistoryWalk::new(&client, &server_guids, 0, None);
+        let cursor = backfill_persona(&db, &mut walk, PERSONA_ID, &CancellationToken::new()).await?;
+        assert_eq!((cursor.reports_ingested, cursor.reports_failed), (1, 1));
+        assert_eq!((walk.reports_ingested, walk.reports_failed), (0, 0));
+
+        server.stop().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_budget_allows_the_last_request_to_go_over() {
+        let server_guids = HashSet::new();
+        let mut walk = HistoryWalk::new(battlelog(), &server_guids, 0, Some(3));
+
+        assert!(walk.take_requests(1));
+        assert!(walk.take_requests(5));
+        assert!(!walk.budget_left());
+        assert!(!walk.take_requests(1));
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let server_guids = HashSet::new();
+        let mut walk = HistoryWalk::new(battlelog(), &server_guids, 0, None);
+
+        assert!(walk.take_requests(1000));
+        assert!(walk.budget_left());
+    }
+
+    #[test]
+    fn test_split_list_skips_blanks() {
+        let items: Vec<&str> = split_list(" 824078704, ,1000000001,").collect();
+        assert_eq!(items, vec!["824078704", "1000000001"]);
+    }
+}
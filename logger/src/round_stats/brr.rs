@@ -0,0 +1,331 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::anyhow;
+use battlelog::BattlereportResponse;
+use serde_json::{json, Map, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::battlelog::personas::BattlelogPersona;
+use crate::database::store::{self, BattlelogStore};
+
+use super::battlereport::get_db_coninfo;
+use super::report_archive::decompress;
+use super::reprocess::ReprocessFilter;
+
+/// Tag of the lines holding a report, `read_brr` reads any tag other than `INDEX_TAG`.
+const REPORT_TAG: &str = "BR";
+/// Tag of the line in front of the lines of each battlereport.
+const INDEX_TAG: &str = "#IX#";
+
+/// Parses a `<tag> <report id> <persona id> <json>` line of a BRR file, the JSON being the
+/// battlereport with the persona's playerreport in it. Returns `None` for index lines.
+pub fn parse_brr_line(line: &str) -> Option<anyhow::Result<BattlereportResponse>> {
+    let split: Vec<&str> = line.splitn(4, ' ').collect();
+    if split[0].eq_ignore_ascii_case(INDEX_TAG) {
+        return None;
+    }
+
+    match split.get(3) {
+        Some(json) => Some(serde_json::from_str::<BattlereportResponse>(json).map_err(anyhow::Error::from)),
+        None => Some(Err(anyhow!("Expected <tag> <id> <x> <json>"))),
+    }
+}
+
+/// Report line of the persona, `report` being the battlereport JSON with the persona's
+/// playerreport under `playerReport`.
+pub fn brr_report_line(report_id: u64, persona_id: u64, report: &Value) -> String {
+    format!("{} {} {} {}", REPORT_TAG, report_id, persona_id, report)
+}
+
+/// Index line written before the report lines of a battlereport, `<lines>` of them follow.
+pub fn brr_index_line(report_id: u64, created_at: u32, lines: usize) -> String {
+    format!("{} {} {} {}", INDEX_TAG, report_id, lines, created_at)
+}
+
+/// Writes the battlereports of the filter to a BRR file that `read_brr` can import on another
+/// instance, a line per playerreport. Archived reports are written as Battlelog sent them, reports
+/// stored before archiving was added are rebuilt from their rows.
+pub async fn write_brr(path: &str, filter: &ReprocessFilter, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    let mut report_ids: BTreeSet<u64> = db.get_battlereport_archive_ids(filter.from, filter.to, filter.server_id).await?.into_iter().collect();
+    report_ids.extend(db.get_battlereport_ids(filter.from, filter.to, filter.server_id).await?);
+    info!("{} battlereports to write to {}", report_ids.len(), path);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    let total_to_write = report_ids.len();
+    let mut reports_written = 0;
+    let mut reports_failed = 0;
+    for report_id in report_ids {
+        if shutdown.is_cancelled() {
+            info!("Stopping the BRR export before {}", report_id);
+            break;
+        }
+
+        // Written only once the whole report is encoded, a failed report leaves no partial lines
+        match brr_report_lines(&*db, report_id).await {
+            Ok(lines) => {
+                file.write_all(lines.as_bytes())?;
+                reports_written += 1;
+            },
+            Err(err) => {
+                reports_failed += 1;
+                error!("{} failed to write because {}", report_id, err);
+            },
+        }
+
+        if (reports_written + reports_failed) % 100 == 0 {
+            info!("{}/{} reports written, {} failed", reports_written, total_to_write, reports_failed);
+        }
+    }
+    file.flush()?;
+    info!("{}/{} reports written, {} failed", reports_written, total_to_write, reports_failed);
+
+    db.close().await;
+
+    Ok(())
+}
+
+async fn brr_report_lines(db: &dyn BattlelogStore, report_id: u64) -> anyhow::Result<String> {
+    let (created_at, reports) = match db.get_battlereport_archive(report_id).await? {
+        Some(archive) => archived_reports(db, report_id, decompress(&archive.data)?).await?,
+        None => stored_reports(db, report_id).await?,
+    };
+
+    let mut lines = brr_index_line(report_id, created_at, reports.len());
+    lines.push('\n');
+    for (persona_id, report) in reports.iter() {
+        lines.push_str(&brr_report_line(report_id, *persona_id, report));
+        lines.push('\n');
+    }
+
+    Ok(lines)
+}
+
+/// The archived battlereport with each archived playerreport in it, untouched otherwise so fields
+/// `BattlereportResponse` doesn't know about are kept.
+async fn archived_reports(db: &dyn BattlelogStore, report_id: u64, mut report: Value) -> anyhow::Result<(u32, Vec<(u64, Value)>)> {
+    let created_at = report["createdAt"].as_u64().ok_or_else(|| anyhow!("Battlereport archive without createdAt"))? as u32;
+
+    let mut reports = Vec::new();
+    for archive in db.get_playerreport_archives(report_id).await? {
+        report["playerReport"] = decompress(&archive.data)?;
+        reports.push((archive.persona_id, report.clone()));
+    }
+
+    Ok((created_at, reports))
+}
+
+/// The battlereport and playerreports rebuilt from the rows, only the fields they were mapped from.
+async fn stored_reports(db: &dyn BattlelogStore, report_id: u64) -> anyhow::Result<(u32, Vec<(u64, Value)>)> {
+    let battlereport = match db.get_battlereport_by_report_id(report_id).await? {
+        Some(battlereport) => battlereport,
+        None => return Err(anyhow!("Battlereport not found")),
+    };
+    let server = match db.get_server_by_server_id(battlereport.server_id).await? {
+        Some(server) => server,
+        None => return Err(anyhow!("Server {} not found", battlereport.server_id)),
+    };
+    let playerreports = db.get_playerreports_by_report_id(report_id).await?;
+
+    let mut personas = Map::new();
+    let mut players = Map::new();
+    let mut teams = Map::new();
+    for playerreport in playerreports.iter() {
+        let persona = match db.get_persona_by_persona_id(playerreport.persona_id).await? {
+            Some(BattlelogPersona { name: Some(name), clan_tag, gravatar_md5, .. }) => json!({
+                "personaId": playerreport.persona_id,
+                "personaName": name,
+                "clanTag": clan_tag,
+                "user": { "gravatarMd5": gravatar_md5 },
+            }),
+            _ => Value::Null,
+        };
+        personas.insert(playerreport.persona_id.to_string(), persona.clone());
+
+        players.insert(playerreport.persona_id.to_string(), json!({
+            "personaId": playerreport.persona_id,
+            "persona": persona,
+            "team": playerreport.team,
+            "squadId": playerreport.squad_id,
+            "kills": playerreport.kills,
+            "deaths": playerreport.deaths,
+            "heals": playerreport.heals,
+            "revives": playerreport.revives,
+            "killStreak": playerreport.kill_streak,
+            "accuracy": playerreport.accuracy_detailed,
+            "dnf": playerreport.dnf,
+            "isCommander": playerreport.is_commander,
+            "isSoldier": playerreport.is_soldier,
+            "skill": playerreport.skill,
+        }));
+
+        let team = teams.entry(playerreport.team.to_string()).or_insert_with(|| json!({
+            "id": playerreport.team,
+            "isWinner": playerreport.team == battlereport.winner,
+            "players": [],
+        }));
+        if let Some(team_players) = team["players"].as_array_mut() {
+            team_players.push(json!(playerreport.persona_id));
+        }
+    }
+
+    let report = json!({
+        "id": report_id.to_string(),
+        "gameMode": battlereport.mode,
+        "duration": battlereport.duration,
+        "createdAt": battlereport.created_at,
+        "gameServer": {
+            "guid": server.guid,
+            "name": server.name,
+            "map": battlereport.map,
+            "mapMode": battlereport.mode,
+        },
+        "teams": teams,
+        "players": players,
+    });
+
+    let mut reports = Vec::new();
+    for playerreport in playerreports.iter() {
+        let mut report = report.clone();
+        report["playerReport"] = json!({
+            "personaId": playerreport.persona_id.to_string(),
+            "persona": personas[&playerreport.persona_id.to_string()],
+            "stats": {
+                "shotsHit": playerreport.shots_hit,
+                "shotsFired": playerreport.shots_fired,
+                "vehicleDestroyed": playerreport.vehicle_destroyed,
+                "assists": playerreport.assists,
+                "spm": playerreport.spm.to_string(),
+                "kdRatio": playerreport.kd_ratio,
+                "skill": playerreport.skill,
+                "vehicleAssists": playerreport.vehicle_assists,
+                "accuracy": playerreport.accuracy,
+                "kills": playerreport.kills,
+                "deaths": playerreport.deaths,
+            },
+            "scores": {
+                "sc_unlock": playerreport.sc_unlock,
+                "sc_bomber": playerreport.sc_bomber,
+                "sc_vehiclesh": playerreport.sc_vehiclesh,
+                "sc_vehicleajet": playerreport.sc_vehicleajet,
+                "sc_engineer": playerreport.sc_engineer,
+                "sc_commander": playerreport.sc_commander,
+                "sc_assault": playerreport.sc_assault,
+                "vehicle": playerreport.vehicle,
+                "sc_vehicleaa": playerreport.sc_vehicleaa,
+                "sc_award": playerreport.sc_award,
+                "sc_vehicleifv": playerreport.sc_vehicleifv,
+                "sc_recon": playerreport.sc_recon,
+                "sc_vehicleah": playerreport.sc_vehicleah,
+                "sc_support": playerreport.sc_support,
+                "sc_vehiclesjet": playerreport.sc_vehiclesjet,
+                "sc_vehiclembt": playerreport.sc_vehiclembt,
+                "sc_vehicleaboat": playerreport.sc_vehicleaboat,
+                "total": playerreport.total,
+            },
+            "gameReportId": report_id.to_string(),
+        });
+        reports.push((playerreport.persona_id, report));
+    }
+
+    Ok((battlereport.created_at, reports))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use battlelog::PlayerreportResponse;
+
+    use crate::database::battlelog::{battlereports::BattlelogBattlereport, playerreports::BattlelogPlayerreport, servers::BattlelogServer};
+    use crate::database::sqlite::context::SqliteContext;
+    use crate::fixture_server::fixtures_dir;
+    use crate::round_stats::report_archive::{archive_battlereport, archive_playerreport};
+
+    use super::*;
+
+    const REPORT_PATH: &str = "battlelog/battlereport/1500000000000000001.json";
+    const PLAYERREPORT_PATH: &str = "battlelog/playerreport/1500000000000000001_1000000001.json";
+
+    fn fixture<T: serde::de::DeserializeOwned>(path: &str) -> T {
+        serde_json::from_str(&fs::read_to_string(fixtures_dir().join(path)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_report_line_reads_back() -> anyhow::Result<()> {
+        let mut report: Value = fixture(REPORT_PATH);
+        report["playerReport"] = fixture(PLAYERREPORT_PATH);
+
+        let line = brr_report_line(1500000000000000001, 1000000001, &report);
+        assert!(line.starts_with("BR 1500000000000000001 1000000001 {"));
+
+        let parsed = parse_brr_line(&line).unwrap()?;
+        assert_eq!(parsed.id, "1500000000000000001");
+        assert_eq!(parsed.player_report.unwrap().persona_id, "1000000001");
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_line_is_skipped() {
+        let line = brr_index_line(1500000000000000001, 1666170000, 2);
+
+        assert_eq!(line, "#IX# 1500000000000000001 2 1666170000");
+        assert!(parse_brr_line(&line).is_none());
+    }
+
+    #[test]
+    fn test_short_line_is_an_error() {
+        assert!(parse_brr_line("BR 1500000000000000001").unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_archived_report_is_written_as_is() -> anyhow::Result<()> {
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let mut report: Value = fixture(REPORT_PATH);
+        report["unmodelled"] = json!({ "kept": true });
+        let playerreport: Value = fixture(PLAYERREPORT_PATH);
+
+        let mut transaction = db.begin().await?;
+        archive_battlereport(transaction.as_mut(), 1500000000000000001, &report.to_string()).await?;
+        archive_playerreport(transaction.as_mut(), 1500000000000000001, 1000000001, &playerreport.to_string()).await?;
+        transaction.commit().await?;
+
+        let lines = brr_report_lines(&db, 1500000000000000001).await?;
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines[0], "#IX# 1500000000000000001 1 1666170000");
+
+        let written: Value = serde_json::from_str(lines[1].splitn(4, ' ').nth(3).unwrap())?;
+        report["playerReport"] = playerreport;
+        assert_eq!(written, report);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unarchived_report_is_rebuilt_from_rows() -> anyhow::Result<()> {
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let report: BattlereportResponse = fixture(REPORT_PATH);
+        let playerreport: PlayerreportResponse = fixture(PLAYERREPORT_PATH);
+
+        let server_id = db.insert_server(&BattlelogServer::new("Stub Server | Conquest".to_string(), "4d0151b3-81ff-4268-b4e8-5e60d5bc8765".to_string())).await? as i32;
+        db.insert_battlereport(&BattlelogBattlereport::from_battlereport_response(&report, server_id)).await?;
+        db.insert_persona(&BattlelogPersona::from_playerreport_response_with_gravatar(&playerreport, Some("0123456789abcdef0123456789abcdef".to_string()))).await?;
+        let report_player = report.get_player_by_personaid(1000000001).unwrap();
+        db.insert_playerreport(&BattlelogPlayerreport::from_response_and_report(1500000000000000001, &playerreport, &report_player)).await?;
+
+        let lines = brr_report_lines(&db, 1500000000000000001).await?;
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines[0], "#IX# 1500000000000000001 1 1666170000");
+
+        let parsed = parse_brr_line(lines[1]).unwrap()?;
+        let parsed_player = parsed.get_player_by_personaid(1000000001).unwrap();
+        assert_eq!(parsed.game_server.guid.as_deref(), Some("4d0151b3-81ff-4268-b4e8-5e60d5bc8765"));
+        assert_eq!(parsed.created_at, 1666170000);
+        assert_eq!(parsed_player.kills, 31);
+        assert_eq!(parsed.player_report.unwrap().persona_id, "1000000001");
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashSet, VecDeque};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::battlelog_client::client::battlelog;
+use crate::database::store;
+
+use super::backfill::{backfill_persona, split_list, HistoryWalk};
+use super::battlereport::get_db_coninfo;
+use super::reprocess::env_date;
+
+/// Where the crawl starts, which servers' reports it ingests and how far it spreads.
+#[derive(Debug, Clone)]
+pub struct CrawlConfig {
+    pub seed_persona_ids: Vec<u64>,
+    pub server_guids: HashSet<String>,
+    /// Reports created before this are left out, 0 walks the whole histories.
+    pub cutoff: u32,
+    /// Co-players of the seeds are at depth 1, their co-players at depth 2 and so on.
+    pub max_depth: u32,
+    /// Battlelog requests the crawl may make in one run.
+    pub request_budget: u32,
+}
+
+impl CrawlConfig {
+    /// Reads the comma separated `CRAWL_SEED_PERSONA_IDS` and `CRAWL_SERVER_GUIDS`, `CRAWL_CUTOFF`
+    /// as a `YYYY-MM-DD` date in UTC, `CRAWL_MAX_DEPTH` and `CRAWL_REQUEST_BUDGET`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let seed_persona_ids = split_list(&dotenv::var("CRAWL_SEED_PERSONA_IDS").unwrap_or_default())
+            .map(|persona_id| persona_id.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()?;
+        let server_guids: HashSet<String> = split_list(&dotenv::var("CRAWL_SERVER_GUIDS").unwrap_or_default())
+            .map(|guid| guid.to_string())
+            .collect();
+        if server_guids.is_empty() {
+            return Err(anyhow::anyhow!("CRAWL_SERVER_GUIDS needs at least one server GUID"));
+        }
+
+        Ok(Self {
+            seed_persona_ids,
+            server_guids,
+            cutoff: env_date("CRAWL_CUTOFF")?.unwrap_or(0),
+            max_depth: dotenv::var("CRAWL_MAX_DEPTH").map(|var| var.parse::<u32>()).unwrap_or(Ok(1)).unwrap(),
+            request_budget: dotenv::var("CRAWL_REQUEST_BUDGET").map(|var| var.parse::<u32>()).unwrap_or(Ok(10000)).unwrap(),
+        })
+    }
+}
+
+/// Discovers the history of our servers through the players. Starting from the seeds, every
+/// persona's report history is walked like a backfill, then the personas met in its stored rounds
+/// are queued one level deeper. Histories keep their backfill cursors, so a crawl that runs out of
+/// budget continues on the next run and already walked personas only add their co-players.
+pub async fn crawl(config: &CrawlConfig, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    let mut walk = HistoryWalk::new(battlelog(), &config.server_guids, config.cutoff, Some(config.request_budget));
+    let mut queued: HashSet<u64> = config.seed_persona_ids.iter().cloned().collect();
+    let mut queue: VecDeque<(u64, u32)> = config.seed_persona_ids.iter().map(|persona_id| (*persona_id, 0)).collect();
+
+    let mut personas_walked = 0;
+    while let Some((persona_id, depth)) = queue.pop_front() {
+        if shutdown.is_cancelled() {
+            info!("Stopping the crawl before {}", persona_id);
+            break;
+        }
+        if !walk.budget_left() {
+            info!("Crawl request budget used up with {} personas queued", queue.len() + 1);
+            break;
+        }
+
+        let ingested_before = walk.reports_ingested;
+        match backfill_persona(&db, &mut walk, persona_id, shutdown).await {
+            Ok(_) => {
+                personas_walked += 1;
+                trace!("Crawled {} at depth {}, {} reports ingested", persona_id, depth, walk.reports_ingested - ingested_before);
+            },
+            Err(err) => {
+                error!("Crawl of {} failed because {}", persona_id, err);
+                continue;
+            },
+        }
+
+        if depth >= config.max_depth {
+            continue;
+        }
+
+        for co_player in db.get_co_player_persona_ids(persona_id).await? {
+            if queued.insert(co_player) {
+                queue.push_back((co_player, depth + 1));
+            }
+        }
+
+        if personas_walked % 100 == 0 {
+            info!("{} personas crawled, {} queued", personas_walked, queue.len());
+        }
+    }
+    info!("Crawl finished with {} personas walked, {} discovered, {} reports ingested, {} failed", personas_walked, queued.len(), walk.reports_ingested, walk.reports_failed);
+
+    db.close().await;
+
+    Ok(())
+}
@@ -1,2 +1,7 @@
 pub mod round_over;
-pub mod battlereport;
\ No newline at end of file
+pub mod battlereport;
+pub mod report_archive;
+pub mod reprocess;
+pub mod backfill;
+pub mod crawler;
+pub mod brr;
\ No newline at end of file
@@ -0,0 +1,82 @@
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use battlelog::{BattlereportResponse, PlayerreportResponse};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::de::DeserializeOwned;
+use crate::database::{battlelog::report_archives::{BattlereportArchive, PlayerreportArchive}, store::{BattlelogStore, StoreTransaction}};
+
+/// Stores the battlereport response body as Battlelog sent it, so columns added later can be
+/// derived without fetching the report again, including from fields `BattlereportResponse` lacks.
+pub async fn archive_battlereport(transaction: &mut (dyn StoreTransaction + '_), report_id: u64, body: &str) -> anyhow::Result<()> {
+    let archive = BattlereportArchive {
+        report_id,
+        data: compress(body.as_bytes())?,
+        archived_at: now(),
+    };
+    transaction.upsert_battlereport_archive(&archive).await?;
+
+    Ok(())
+}
+
+pub async fn archive_playerreport(transaction: &mut (dyn StoreTransaction + '_), report_id: u64, persona_id: u64, body: &str) -> anyhow::Result<()> {
+    let archive = PlayerreportArchive {
+        report_id,
+        persona_id,
+        data: compress(body.as_bytes())?,
+        archived_at: now(),
+    };
+    transaction.upsert_playerreport_archive(&archive).await?;
+
+    Ok(())
+}
+
+pub async fn load_battlereport(db: &dyn BattlelogStore, report_id: u64) -> anyhow::Result<Option<BattlereportResponse>> {
+    match db.get_battlereport_archive(report_id).await? {
+        Some(archive) => Ok(Some(decompress(&archive.data)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn load_playerreports(db: &dyn BattlelogStore, report_id: u64) -> anyhow::Result<Vec<PlayerreportResponse>> {
+    let mut playerreports = Vec::new();
+    for archive in db.get_playerreport_archives(report_id).await? {
+        playerreports.push(decompress(&archive.data)?);
+    }
+
+    Ok(playerreports)
+}
+
+pub fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn decompress<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+    let mut json = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let body = r#"{"id":"1297613665940962880","teams":{"1":{"isWinner":true}}}"#;
+        let compressed = compress(body.as_bytes()).unwrap();
+        let decompressed: Value = decompress(&compressed).unwrap();
+        assert_eq!(decompressed, json!({ "id": "1297613665940962880", "teams": { "1": { "isWinner": true } } }));
+    }
+}
@@ -0,0 +1,263 @@
+use battlelog::{BattlereportResponse, PlayerreportResponse};
+use chrono::NaiveDate;
+use tokio_util::sync::CancellationToken;
+
+use crate::database::battlelog::{battlereports::BattlelogBattlereport, persona_name_history::PersonaNameHistory, personas::BattlelogPersona, playerreports::BattlelogPlayerreport};
+use crate::database::store::{self, BattlelogStore, StoreTransaction};
+
+use super::battlereport::{check_usable, get_db_coninfo, get_gravatar, read_lines, upsert_server};
+use super::brr::parse_brr_line;
+use super::report_archive::{load_battlereport, load_playerreports};
+
+/// Limits which archived battlereports are reprocessed, `to` is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct ReprocessFilter {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    pub server_id: Option<i32>,
+}
+
+impl ReprocessFilter {
+    /// Reads `REPROCESS_FROM` and `REPROCESS_TO` as `YYYY-MM-DD` dates in UTC and `REPROCESS_SERVER_ID`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_env_with_prefix("REPROCESS")
+    }
+
+    /// Like `from_env` with another prefix than `REPROCESS` for the variables.
+    pub fn from_env_with_prefix(prefix: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            from: env_date(&format!("{}_FROM", prefix))?,
+            to: env_date(&format!("{}_TO", prefix))?,
+            server_id: match dotenv::var(format!("{}_SERVER_ID", prefix)) {
+                Ok(server_id) => Some(server_id.parse::<i32>()?),
+                Err(_) => None,
+            },
+        })
+    }
+}
+
+/// Rebuilds the battlereport, persona and playerreport rows from the archived Battlelog responses
+/// with the current mapping code, so new columns can be backfilled without Battlelog. The
+/// battlereport and playerreport rows are overwritten, personas are merged like on ingestion as
+/// they are made of every report the persona played.
+pub async fn reprocess_archives(filter: &ReprocessFilter, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    let report_ids = db.get_battlereport_archive_ids(filter.from, filter.to, filter.server_id).await?;
+    info!("{} archived battlereports to reprocess", report_ids.len());
+
+    let total_to_process = report_ids.len();
+    let mut reports_processed = 0;
+    let mut reports_failed = 0;
+    for report_id in report_ids {
+        if shutdown.is_cancelled() {
+            info!("Stopping the reprocessing before {}", report_id);
+            break;
+        }
+
+        match reprocess_battlereport(&db, report_id).await {
+            Ok(playerreports) => {
+                reports_processed += 1;
+                trace!("Reprocessed {} with {} playerreports", report_id, playerreports);
+            },
+            Err(err) => {
+                reports_failed += 1;
+                error!("{} failed to reprocess because {}", report_id, err);
+            },
+        }
+
+        if (reports_processed + reports_failed) % 100 == 0 {
+            info!("{}/{} reports reprocessed, {} failed", reports_processed, total_to_process, reports_failed);
+        }
+    }
+    info!("{}/{} reports reprocessed, {} failed", reports_processed, total_to_process, reports_failed);
+
+    db.close().await;
+
+    Ok(())
+}
+
+/// Reprocesses a single archived battlereport, returns the number of playerreports rebuilt.
+async fn reprocess_battlereport(db: &dyn BattlelogStore, report_id: u64) -> anyhow::Result<usize> {
+    let report = match load_battlereport(db, report_id).await? {
+        Some(report) => report,
+        None => return Err(anyhow::anyhow!("Battlereport archive not found")),
+    };
+    let playerreports = load_playerreports(db, report_id).await?;
+
+    check_usable(&report)?;
+    let server_id = upsert_server(db, &report).await?;
+
+    let mut transaction = db.begin().await?;
+    transaction.replace_battlereport(&BattlelogBattlereport::from_battlereport_response(&report, server_id)).await?;
+
+    let mut rebuilt = 0;
+    for data in playerreports.iter() {
+        if rebuild_playerreport(transaction.as_mut(), &report, report_id, data).await? {
+            rebuilt += 1;
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(rebuilt)
+}
+
+/// Like `reprocess_archives` with the reports of a BRR file, for rounds that were never archived.
+pub async fn reprocess_brr(path: &str, filter: &ReprocessFilter, shutdown: &CancellationToken) -> anyhow::Result<()> {
+    let uri = get_db_coninfo()?;
+    let db = store::connect(&uri).await?;
+
+    let mut lines_processed = 0;
+    let mut reports_processed = 0;
+    let mut reports_failed = 0;
+    for line in read_lines(path)? {
+        if shutdown.is_cancelled() {
+            info!("Stopping the BRR reprocessing after {} lines", lines_processed);
+            break;
+        }
+
+        let line = line?;
+        lines_processed += 1;
+        let report = match parse_brr_line(&line) {
+            Some(Ok(report)) => report,
+            Some(Err(err)) => {
+                reports_failed += 1;
+                error!("Line {} of {} failed to parse because {}", lines_processed, path, err);
+                continue;
+            },
+            None => continue,
+        };
+
+        match reprocess_brr_report(&*db, &report, filter).await {
+            Ok(true) => reports_processed += 1,
+            Ok(false) => {},
+            Err(err) => {
+                reports_failed += 1;
+                error!("{} failed to reprocess because {}", report.id, err);
+            },
+        }
+
+        if lines_processed % 1000 == 0 {
+            info!("{} lines read, {} reports reprocessed, {} failed", lines_processed, reports_processed, reports_failed);
+        }
+    }
+    info!("{} lines read, {} reports reprocessed, {} failed", lines_processed, reports_processed, reports_failed);
+
+    db.close().await;
+
+    Ok(())
+}
+
+/// Rebuilds the battlereport and the playerreport of one BRR line, returns false if the filter
+/// leaves it out.
+async fn reprocess_brr_report(db: &dyn BattlelogStore, report: &BattlereportResponse, filter: &ReprocessFilter) -> anyhow::Result<bool> {
+    if filter.from.map(|from| report.created_at < from).unwrap_or(false) || filter.to.map(|to| report.created_at >= to).unwrap_or(false) {
+        return Ok(false);
+    }
+    check_usable(report)?;
+    if let Some(server_id) = filter.server_id {
+        let server = db.get_server_by_server_guid(report.game_server.guid.as_ref().unwrap()).await?;
+        if server.map(|server| server.id) != Some(server_id) {
+            return Ok(false);
+        }
+    }
+
+    let report_id = report.id.parse::<u64>()?;
+    let server_id = upsert_server(db, report).await?;
+
+    let mut transaction = db.begin().await?;
+    transaction.replace_battlereport(&BattlelogBattlereport::from_battlereport_response(report, server_id)).await?;
+    if let Some(data) = report.player_report.as_ref() {
+        rebuild_playerreport(transaction.as_mut(), report, report_id, data).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Rebuilds the persona and the playerreport of a player, returns false if the persona isn't a
+/// player of the report.
+async fn rebuild_playerreport(transaction: &mut (dyn StoreTransaction + '_), report: &BattlereportResponse, report_id: u64, data: &PlayerreportResponse) -> anyhow::Result<bool> {
+    let persona_id = data.persona_id.parse::<u64>()?;
+
+    let battlelog_persona = BattlelogPersona::from_playerreport_response_with_gravatar(data, get_gravatar(report, persona_id));
+    transaction.upsert_persona(&battlelog_persona, report.created_at).await?;
+    if let Some(name_history) = PersonaNameHistory::from_persona(&battlelog_persona, report.created_at) {
+        transaction.upsert_persona_name_history(&name_history).await?;
+    }
+
+    match report.get_player_by_personaid(persona_id) {
+        Some(report_player) => {
+            transaction.upsert_playerreport(&BattlelogPlayerreport::from_response_and_report(report_id, data, &report_player)).await?;
+            Ok(true)
+        },
+        None => {
+            warn!("Persona {} missing from the players of {}", persona_id, report_id);
+            Ok(false)
+        },
+    }
+}
+
+pub(crate) fn env_date(name: &str) -> anyhow::Result<Option<u32>> {
+    match dotenv::var(name) {
+        Ok(date) => {
+            let midnight = NaiveDate::parse_from_str(&date, "%Y-%m-%d")?
+                .and_hms_opt(0, 0, 0)
+                .ok_or_else(|| anyhow::anyhow!("Invalid {} {}", name, date))?;
+            Ok(Some(midnight.and_utc().timestamp() as u32))
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::sqlite::context::SqliteContext;
+    use crate::fixture_server::fixtures_dir;
+
+    use super::*;
+
+    fn brr_report() -> anyhow::Result<BattlereportResponse> {
+        let mut report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(fixtures_dir().join("battlelog/battlereport/1500000000000000001.json"))?)?;
+        report["playerReport"] = serde_json::from_str(&std::fs::read_to_string(fixtures_dir().join("battlelog/playerreport/1500000000000000001_1000000001.json"))?)?;
+        let line = format!("BR 1500000000000000001 1000000001 {}", report);
+        parse_brr_line(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_brr_overwrites() -> anyhow::Result<()> {
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let report = brr_report()?;
+
+        let mut stale = BattlelogBattlereport::from_battlereport_response(&report, 1);
+        stale.duration = 9999;
+        stale.map = "MP_Stale".to_string();
+        let mut transaction = db.begin().await?;
+        transaction.upsert_battlereport(&stale).await?;
+        transaction.commit().await?;
+
+        assert!(reprocess_brr_report(&db, &report, &ReprocessFilter::default()).await?);
+
+        let battlereport = db.get_battlereport_by_report_id(1500000000000000001).await?.unwrap();
+        assert_eq!(battlereport.duration, 1825);
+        assert_eq!(battlereport.map, "MP_Prison");
+        assert!(db.get_playerreport_by_report_id_and_persona_id(1500000000000000001, 1000000001).await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_brr_filters() -> anyhow::Result<()> {
+        let db = SqliteContext::connect("sqlite::memory:").await?;
+        let report = brr_report()?;
+
+        let filter = ReprocessFilter { from: Some(report.created_at + 1), ..Default::default() };
+        assert!(!reprocess_brr_report(&db, &report, &filter).await?);
+        let filter = ReprocessFilter { server_id: Some(42), ..Default::default() };
+        assert!(!reprocess_brr_report(&db, &report, &filter).await?);
+        assert!(db.get_battlereport_by_report_id(1500000000000000001).await?.is_none());
+
+        Ok(())
+    }
+}
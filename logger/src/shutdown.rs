@@ -0,0 +1,33 @@
+use tokio_util::sync::CancellationToken;
+
+/// Cancels the token on SIGTERM or Ctrl-C. Background work checks the token between units of
+/// work, so whatever transaction is running gets to finish before the task stops.
+pub async fn cancel_on_signal(shutdown: CancellationToken) {
+    wait_for_signal().await;
+    info!("Shutdown requested, finishing the current work");
+    shutdown.cancel();
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            error!("Failed to listen for SIGTERM: {}", err);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        },
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => {},
+        _ = tokio::signal::ctrl_c() => {},
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}